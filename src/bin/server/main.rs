@@ -0,0 +1,147 @@
+use rs_tetris::{
+    online::{hub::Hub, server::Server},
+    tetris_game::{
+        garbage::{AttackTable, HolePlacement},
+        versus::VersusSettings,
+    },
+};
+
+/*
+*   The relay server of the online mode, see rs_tetris::online.
+*       cargo run --bin tetris-server -- --addr 0.0.0.0:9001 --attack attack.json --holes per-row --size 10x20
+*
+*   Clients connect with a websocket to ws://addr/ (the web frontend with the "server" prop).
+*   The server runs the garbage exchange, so the attack table, the holes and the board size are the same for everyone in it.
+*/
+
+/// command line options
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    addr: String,
+    /// seeds of the matches (and their holes), the same seed hands out the same matches
+    seed: Option<u64>,
+    /// the board size of every match
+    width: u32,
+    height: u32,
+    versus: VersusSettings,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            addr: "127.0.0.1:9001".into(),
+            seed: None,
+            width: 10,
+            height: 20,
+            versus: VersusSettings::default(),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--addr" => options.addr = value()?,
+                "--seed" => options.seed = Some(number(&value()?)?),
+                "--size" => {
+                    let size = value()?;
+                    let (width, height) = size
+                        .split_once('x')
+                        .ok_or(format!("not a size like 10x20: {}", size))?;
+                    (options.width, options.height) = (number(width)?, number(height)?);
+                    if options.width == 0 || options.height == 0 {
+                        return Err(format!("not a size like 10x20: {}", size));
+                    }
+                }
+                "--attack" => {
+                    let path = value()?;
+                    let json = std::fs::read_to_string(&path)
+                        .map_err(|err| format!("{}: {}", path, err))?;
+                    options.versus.attack =
+                        AttackTable::from_json(&json).map_err(|err| err.to_string())?
+                }
+                "--holes" => {
+                    options.versus.holes = match value()?.as_str() {
+                        "per-attack" => HolePlacement::PerAttack,
+                        "per-row" => HolePlacement::PerRow,
+                        column => HolePlacement::Column(number(column)?),
+                    }
+                }
+                _ => return Err(format!("unknown option: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("usage: tetris-server [--addr 127.0.0.1:9001] [--seed N] [--size 10x20] [--attack table.json] [--holes per-attack|per-row|COLUMN]");
+            std::process::exit(2);
+        }
+    };
+    // without a seed every start of the server hands out other matches:
+    let seed = options.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default()
+    });
+    let hub = Hub::new(options.width, options.height, options.versus, seed);
+    let server = match Server::bind(&options.addr, hub) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("{}: {}", options.addr, err);
+            std::process::exit(1);
+        }
+    };
+    if let Ok(addr) = server.local_addr() {
+        println!("listening on ws://{}/", addr);
+    }
+    if let Err(err) = server.run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse(&["--seed", "7", "--holes", "per-row"]).unwrap();
+        assert_eq!(options.addr, "127.0.0.1:9001");
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.versus.holes, HolePlacement::PerRow);
+        assert_eq!(
+            parse(&["--holes", "3"]).unwrap().versus.holes,
+            HolePlacement::Column(3)
+        );
+        assert_eq!(parse(&["--addr", "0.0.0.0:80"]).unwrap().addr, "0.0.0.0:80");
+        let options = parse(&["--size", "6x12"]).unwrap();
+        assert_eq!((options.width, options.height), (6, 12));
+        assert_eq!(
+            (parse(&[]).unwrap().width, parse(&[]).unwrap().height),
+            (10, 20)
+        );
+
+        assert!(parse(&["--seed"]).is_err());
+        assert!(parse(&["--holes", "everywhere"]).is_err());
+        assert!(parse(&["--attack", "/does/not/exist.json"]).is_err());
+        assert!(parse(&["--fast"]).is_err());
+        for size in ["10", "0x20", "tenxtwenty"] {
+            assert!(parse(&["--size", size]).is_err(), "{}", size);
+        }
+    }
+}
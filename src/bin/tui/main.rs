@@ -0,0 +1,310 @@
+use std::{
+    io::{self, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use rs_tetris::tetris_game::{
+    piece_set::PieceSet,
+    timing::{Gravity, Timing, FRAME_MS},
+    Direction, Settings, Tetris,
+};
+
+mod view;
+
+/*
+*   Plays tetris_game::Tetris in the terminal, no browser or wasm build needed.
+*       cargo run --bin tetris-tui -- --width 10 --height 20 --pieces standard --level 1
+*
+*   Terminals that support it (kitty keyboard protocol) report key releases,
+*   then holding left/right/down works like in the browser (DAS and soft drop by the game).
+*   Everywhere else every key press (and the terminals own key repeat) moves once.
+*/
+
+const HELP: [&str; 8] = [
+    "",
+    "←/→    move",
+    "↓      soft drop",
+    "space  hard drop",
+    "↑/x z  rotate cw/ccw",
+    "a      rotate 180",
+    "c      hold",
+    "p      pause  q quit",
+];
+
+/// command line options
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    width: u32,
+    height: u32,
+    pieces: String,
+    level: u32,
+    gravity: Option<f64>,
+    /// entry delay and line clear delay, in frames
+    are: u32,
+    line_clear: u32,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            width: 10,
+            height: 20,
+            pieces: "standard".into(),
+            level: 1,
+            gravity: None,
+            are: 6,
+            line_clear: 20,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--width" => options.width = number(&value()?)?,
+                "--height" => options.height = number(&value()?)?,
+                "--pieces" => options.pieces = value()?,
+                "--level" => options.level = number(&value()?)?,
+                "--gravity" => options.gravity = Some(number(&value()?)?),
+                "--are" => options.are = number(&value()?)?,
+                "--line-clear" => options.line_clear = number(&value()?)?,
+                _ => return Err(format!("unknown option: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    fn new_game(&self) -> Result<Tetris, String> {
+        let piece_set =
+            PieceSet::by_name(&self.pieces).ok_or(format!("unknown piece set: {}", self.pieces))?;
+        Ok(Tetris::with_settings(
+            self.width,
+            self.height,
+            Settings {
+                piece_set,
+                timing: Timing {
+                    gravity: self.gravity.map(Gravity::Fixed).unwrap_or(Gravity::Level),
+                    are_frames: self.are,
+                    line_clear_frames: self.line_clear,
+                    ..Default::default()
+                },
+                start_level: self.level,
+                ..Default::default()
+            },
+        ))
+    }
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))
+}
+
+/// Puts the terminal into raw mode on an alternate screen, and restores it when dropped (even on panic).
+struct Terminal {
+    out: Stdout,
+    /// the terminal reports key releases
+    releases: bool,
+}
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide)?;
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        Ok(Self { out, releases })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.releases {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// what the main loop should do after a key
+enum Next {
+    Play,
+    Restart,
+    Quit,
+}
+
+fn handle_key(tetris: &mut Tetris, key: KeyEvent, releases: bool) -> Next {
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return Next::Quit;
+    }
+    // held keys are handled by the game itself (if we get to know about releases):
+    if key.kind == KeyEventKind::Repeat {
+        return Next::Play;
+    }
+    if key.kind == KeyEventKind::Release {
+        match key.code {
+            KeyCode::Left => tetris.stop_shift(Direction::Left),
+            KeyCode::Right => tetris.stop_shift(Direction::Right),
+            KeyCode::Down => tetris.set_soft_drop(false),
+            _ => {}
+        }
+        return Next::Play;
+    }
+    match key.code {
+        KeyCode::Left | KeyCode::Right => {
+            let dir = if key.code == KeyCode::Left {
+                Direction::Left
+            } else {
+                Direction::Right
+            };
+            if releases {
+                tetris.start_shift(dir);
+            } else {
+                tetris.move_current_shape(dir);
+            }
+        }
+        KeyCode::Down if releases => tetris.set_soft_drop(true),
+        KeyCode::Down => {
+            tetris.move_down();
+        }
+        KeyCode::Char(' ') => tetris.hard_drop(),
+        KeyCode::Up | KeyCode::Char('x') => tetris.move_rotate(),
+        KeyCode::Char('z') => tetris.move_rotate_ccw(),
+        KeyCode::Char('a') => tetris.move_rotate_180(),
+        KeyCode::Char('c') => {
+            tetris.hold();
+        }
+        KeyCode::Char('p') | KeyCode::Esc => tetris.toggle_pause(),
+        KeyCode::Char('r') if tetris.is_game_over() => return Next::Restart,
+        KeyCode::Char('q') => return Next::Quit,
+        _ => {}
+    }
+    Next::Play
+}
+
+fn play(options: &Options) -> io::Result<()> {
+    let mut term = Terminal::enter()?;
+    let mut tetris = options
+        .new_game()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    view::clear(&mut term.out)?;
+
+    let frame = Duration::from_secs_f64(FRAME_MS / 1000.0);
+    let mut last = Instant::now();
+    let mut last_screen = vec![];
+    loop {
+        // handle all keys that came in until the next frame is due:
+        let deadline = last + frame;
+        while event::poll(deadline.saturating_duration_since(Instant::now()))? {
+            match event::read()? {
+                Event::Key(key) => match handle_key(&mut tetris, key, term.releases) {
+                    Next::Play => {}
+                    Next::Restart => {
+                        tetris = options
+                            .new_game()
+                            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?
+                    }
+                    Next::Quit => return Ok(()),
+                },
+                Event::Resize(_, _) => {
+                    view::clear(&mut term.out)?;
+                    last_screen.clear();
+                }
+                _ => {}
+            }
+        }
+
+        let now = Instant::now();
+        tetris.update(now.duration_since(last).as_secs_f64() * 1000.0);
+        last = now;
+
+        let help = if tetris.is_game_over() {
+            &["", "r      restart", "q      quit"][..]
+        } else {
+            &HELP[..]
+        };
+        // drawn into a buffer first, the terminal only gets it if something changed:
+        let mut screen = Vec::with_capacity(last_screen.len());
+        view::draw(&mut screen, &tetris.snapshot(), help)?;
+        if screen != last_screen {
+            term.out.write_all(&screen)?;
+            term.out.flush()?;
+            last_screen = screen;
+        }
+    }
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("usage: tetris-tui [--width 10] [--height 20] [--pieces standard|pentomino|tiny] [--level 1] [--gravity G] [--are 6] [--line-clear 20]");
+            std::process::exit(2);
+        }
+    };
+    if let Err(err) = play(&options) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse(&["--width", "8", "--pieces", "tiny", "--gravity", "0.5"]).unwrap();
+        assert_eq!(options.width, 8);
+        assert_eq!(options.height, 20);
+        assert_eq!(options.pieces, "tiny");
+        assert_eq!(options.gravity, Some(0.5));
+        assert_eq!((options.are, options.line_clear), (6, 20));
+        assert_eq!(parse(&["--are", "0"]).unwrap().are, 0);
+        assert!(options.new_game().is_ok());
+
+        assert!(parse(&["--width"]).is_err());
+        assert!(parse(&["--width", "wide"]).is_err());
+        assert!(parse(&["--fast"]).is_err());
+        assert!(parse(&["--pieces", "hexomino"])
+            .unwrap()
+            .new_game()
+            .is_err());
+    }
+
+    #[test]
+    fn keys_drive_the_game() {
+        let mut tetris = Tetris::new(10, 20);
+        let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert!(matches!(
+            handle_key(&mut tetris, press(KeyCode::Char(' ')), false),
+            Next::Play
+        ));
+        assert!(tetris
+            .get_pixels()
+            .any(|xy| xy.1 == 19 && tetris.get_kind(xy).is_some()));
+        assert!(matches!(
+            handle_key(&mut tetris, press(KeyCode::Char('q')), false),
+            Next::Quit
+        ));
+    }
+}
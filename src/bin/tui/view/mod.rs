@@ -0,0 +1,134 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+use rs_tetris::tetris_game::{
+    shapes::{PieceKind, XY},
+    snapshot::{Preview, Snapshot, Status},
+};
+
+/*
+*   Draws the game with plain ANSI escape codes (through crossterm):
+*       - every cell is two characters wide, so cells look roughly square
+*       - the board on the left, next boxes, hold box and the score panel to the right of it
+*       - rows that are about to collapse (line clear delay) get drawn as one white bar
+*/
+
+const BLOCK: &str = "██";
+const GHOST: &str = "░░";
+const EMPTY: &str = " .";
+const CLEARING: &str = "▓▓";
+
+/// the guideline colors, like the web frontend draws them
+pub fn color(kind: PieceKind) -> Color {
+    let (r, g, b) = match kind {
+        PieceKind::I => (0x00, 0xf0, 0xf0),
+        PieceKind::J => (0x00, 0x00, 0xf0),
+        PieceKind::L => (0xf0, 0xa0, 0x00),
+        PieceKind::O => (0xf0, 0xf0, 0x00),
+        PieceKind::S => (0x00, 0xf0, 0x00),
+        PieceKind::T => (0xa0, 0x00, 0xf0),
+        PieceKind::Z => (0xf0, 0x00, 0x00),
+        PieceKind::Garbage => (0x80, 0x80, 0x80),
+    };
+    Color::Rgb { r, g, b }
+}
+
+// one cell, at column x / row y of the terminal
+fn cell(out: &mut impl Write, x: u16, y: u16, text: &str, color: Color) -> io::Result<()> {
+    queue!(out, MoveTo(x, y), SetForegroundColor(color), Print(text))
+}
+
+// a box the size of a preview, with its title above it. Returns the row below the box.
+fn preview_box(
+    out: &mut impl Write,
+    snapshot: &Snapshot,
+    (x, y): (u16, u16),
+    title: &str,
+    preview: Option<&Preview>,
+) -> io::Result<u16> {
+    let (width, height) = snapshot.preview_size;
+    queue!(out, MoveTo(x, y), ResetColor, Print(title))?;
+    for row in 0..height {
+        for col in 0..width {
+            let (left, top) = (x + col as u16 * 2, y + 1 + row as u16);
+            match preview.filter(|preview| preview.cells.contains(&XY(col, row))) {
+                Some(preview) => cell(out, left, top, BLOCK, color(preview.kind))?,
+                None => cell(out, left, top, "  ", Color::Reset)?,
+            }
+        }
+    }
+    Ok(y + 2 + height as u16)
+}
+
+/// clears the whole screen, draw() only overwrites what it draws
+pub fn clear(out: &mut impl Write) -> io::Result<()> {
+    queue!(out, Clear(ClearType::All))
+}
+
+/// Draws the whole game. help gets printed below the score panel.
+pub fn draw(out: &mut impl Write, snapshot: &Snapshot, help: &[&str]) -> io::Result<()> {
+    let (width, height) = (snapshot.width, snapshot.height);
+
+    // the board, with a border around it:
+    let border = Color::Grey;
+    for y in 0..height {
+        cell(out, 0, y as u16, "│", border)?;
+        cell(out, 1 + width as u16 * 2, y as u16, "│", border)?;
+        for x in 0..width {
+            let xy = XY(x, y);
+            let (col, row) = (1 + x as u16 * 2, y as u16);
+            if snapshot.clearing_rows().contains(&y) {
+                cell(out, col, row, CLEARING, Color::White)?;
+                continue;
+            }
+            match snapshot.cell(xy) {
+                Some(kind) => cell(out, col, row, BLOCK, color(kind))?,
+                None if snapshot.is_ghost(xy) => {
+                    cell(out, col, row, GHOST, color(snapshot.active_kind))?
+                }
+                None => cell(out, col, row, EMPTY, Color::DarkGrey)?,
+            }
+        }
+    }
+    let bottom = format!("└{}┘", "──".repeat(width as usize));
+    cell(out, 0, height as u16, &bottom, border)?;
+
+    // next and hold boxes:
+    let panel = 4 + width as u16 * 2;
+    let mut row = 0;
+    for (n, preview) in snapshot.queue.iter().enumerate() {
+        let title = if n == 0 { "next" } else { "" };
+        row = preview_box(out, snapshot, (panel, row), title, Some(preview))?;
+    }
+    row = preview_box(out, snapshot, (panel, row), "hold", snapshot.hold.as_ref())?;
+
+    // score panel:
+    queue!(out, ResetColor)?;
+    let status = match snapshot.status {
+        Status::GameOver => "GAME OVER",
+        Status::Paused => "PAUSED",
+        Status::Playing => "",
+    };
+    let lines = [
+        format!("score: {}", snapshot.score),
+        format!("level: {}", snapshot.level),
+        format!("lines: {}", snapshot.lines),
+        status.to_string(),
+    ];
+    let text = lines.iter().map(String::as_str).chain(help.iter().copied());
+    for (i, line) in text.enumerate() {
+        // padded, so a shorter line fully covers the one drawn last frame:
+        queue!(
+            out,
+            MoveTo(panel, row + i as u16),
+            Print(format!("{:<24}", line))
+        )?;
+    }
+    queue!(out, ResetColor, MoveTo(0, height as u16 + 1))?;
+    out.flush()
+}
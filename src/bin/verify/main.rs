@@ -0,0 +1,155 @@
+use std::io::Read;
+
+use rs_tetris::tetris_game::{
+    replay::{self, Outcome, ReplayError, Ruleset, Submission},
+    Settings,
+};
+
+/*
+*   Checks score submissions (json, see rs_tetris::tetris_game::replay) by playing their replays again.
+*       cargo run --bin tetris-verify -- submission.json more.json
+*       curl .../submission | tetris-verify --width 10 --height 40 --settings settings.json -
+*
+*   Only games played by the rules count: a 10x20 board with the default settings,
+*   or what --width, --height and --settings (a json file like Settings) say.
+*   Prints a line per submission, exits with 1 if any of them got rejected.
+*   A game server can do the same with replay::verify() directly.
+*/
+
+/// command line options
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    /// files with a submission each, "-" reads stdin
+    paths: Vec<String>,
+    /// the rules the games have to be played by
+    rules: Ruleset,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut paths = vec![];
+        let (mut width, mut height) = Ruleset::default().size();
+        let mut settings = Settings::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--width" => width = number(&value()?)?,
+                "--height" => height = number(&value()?)?,
+                "--settings" => {
+                    let path = value()?;
+                    settings = serde_json::from_str(&read(&path)?)
+                        .map_err(|err| format!("{}: {}", path, err))?;
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+                _ => paths.push(arg),
+            }
+        }
+        if paths.is_empty() {
+            return Err("no submission to verify".to_string());
+        }
+        Ok(Options {
+            paths,
+            rules: Ruleset::new(width, height, settings),
+        })
+    }
+}
+
+fn number(value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))
+}
+
+fn read(path: &str) -> Result<String, String> {
+    let mut json = String::new();
+    if path == "-" {
+        std::io::stdin()
+            .read_to_string(&mut json)
+            .map_err(|err| err.to_string())?;
+    } else {
+        json = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    }
+    Ok(json)
+}
+
+fn check(json: &str, rules: &Ruleset) -> Result<Outcome, ReplayError> {
+    replay::verify(&Submission::from_json(json)?, rules)
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("usage: tetris-verify [--width 10] [--height 20] [--settings settings.json] SUBMISSION.json... (- reads stdin)");
+            std::process::exit(2);
+        }
+    };
+    let mut rejected = 0;
+    for path in &options.paths {
+        let rules = &options.rules;
+        match read(path).map(|json| check(&json, rules).map_err(|err| err.to_string())) {
+            Ok(Ok(outcome)) => println!("{}: ok, {} ({})", path, outcome, rules),
+            Ok(Err(err)) | Err(err) => {
+                println!("{}: rejected, {}", path, err);
+                rejected += 1;
+            }
+        }
+    }
+    if rejected > 0 {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rs_tetris::tetris_game::{
+        replay::{Command, Replay},
+        Settings,
+    };
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse(&["a.json", "-"]).unwrap();
+        assert_eq!(options.paths, vec!["a.json", "-"]);
+        assert_eq!(options.rules, Ruleset::default());
+        let options = parse(&["--height", "40", "a.json"]).unwrap();
+        assert_eq!(options.rules.size(), (10, 40));
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--fast", "a.json"]).is_err());
+        assert!(parse(&["--width", "wide", "a.json"]).is_err());
+        assert!(parse(&["--settings", "/does/not/exist.json", "a.json"]).is_err());
+    }
+
+    #[test]
+    fn checks_submissions() {
+        let mut replay = Replay::new(10, 20, Settings::default(), 9);
+        let mut tetris = replay.start().unwrap();
+        for _ in 0..3 {
+            tetris.update(100.0);
+            replay.record(&mut tetris, Command::HardDrop);
+        }
+        let mut submission = replay.finish(&tetris);
+        let rules = Ruleset::default();
+        assert!(check(&submission.to_json(), &rules).is_ok());
+        // other rules, other leaderboard:
+        assert!(matches!(
+            check(
+                &submission.to_json(),
+                &Ruleset::new(4, 12, Settings::default())
+            ),
+            Err(ReplayError::Ruleset { .. })
+        ));
+        submission.outcome.lines = 40;
+        assert!(matches!(
+            check(&submission.to_json(), &rules),
+            Err(ReplayError::Mismatch { .. })
+        ));
+        assert!(matches!(check("{}", &rules), Err(ReplayError::Parse(_))));
+    }
+}
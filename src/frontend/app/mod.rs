@@ -0,0 +1,812 @@
+use super::{
+    editor::BoardEditor,
+    game_loop::AnimationLoop,
+    input::{
+        gamepad::{self, GamepadInput, GamepadMap},
+        keymap::{Keymap, Preset},
+        touch::TouchGestures,
+        Action, Input,
+    },
+    online::OnlineView,
+    render::{CanvasRenderer, DomRenderer, Renderer, RendererKind},
+    settings::{self, KeymapSettings},
+    skin::Skin,
+    versus::VersusView,
+};
+use js_sys::{Object, Reflect};
+use crate::tetris_game::{
+    bot::Autoplay,
+    finesse::FinesseMode,
+    garbage::{AttackTable, HolePlacement},
+    piece_set::PieceSet,
+    position::Position,
+    puzzle::{Puzzle, PuzzleRun, PuzzleStatus},
+    snapshot::Snapshot,
+    timing::{Gravity, Timing},
+    versus::VersusSettings,
+    Settings, Tetris,
+};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_react::{
+    c, export_components, h,
+    hooks::{use_callback, use_effect, use_js_ref, use_ref, use_state, Deps, State},
+    props::Style,
+    Component,
+};
+use web_sys::{Element, Event, HtmlElement, KeyboardEvent, MouseEvent, PointerEvent, TouchEvent};
+
+/// how often the bot sends a move while "AI plays" is on (so it can be watched)
+const BOT_MOVE_MS: f64 = 50.0;
+
+/*
+*   The App component, what index.html mounts.
+*   Uses react-library for rust to generate some javascript that runs react
+*   and hits the wasm generated instance of the teris game with user input or timer-ticks...
+*/
+
+pub struct App {
+    width: u32,
+    height: u32,
+    /// how many upcoming shapes get shown in the preview column (1-6)
+    preview: usize,
+    /// how the different piece kinds get drawn
+    skin: Skin,
+    /// the pieces the game is played with (name of a built in set or a json definition)
+    pieces: PieceSet,
+    /// "canvas" (default) or the "dom" renderer
+    renderer: RendererKind,
+    /// a fixed gravity in G (cells per frame, 20 is instant), by default it speeds up with the level
+    gravity: Gravity,
+    /// the level the game starts at
+    level: u32,
+    /// entry delay (ARE) in frames (60 per second) before the next shape shows up
+    are: u32,
+    /// frames the full rows flash before they collapse
+    line_clear: u32,
+    /// check the finesse of every shape and count the faults (strict mode can be turned on with a button)
+    finesse: bool,
+    /// show on-screen buttons (an alternative to the touch gestures)
+    buttons: bool,
+    /// the key preset ("guideline", "wasd" or "vim") used until keys get rebound in the settings
+    keys: Keymap,
+    /// gamepad buttons to rebind, like { 0: "hard-drop", 12: null } (null unbinds the button)
+    gamepad: GamepadMap,
+    /// the rules of local versus: an attack table as json ("attack") and the holes
+    /// ("holes": "per-attack", "per-row" or the number of a column)
+    versus: VersusSettings,
+    /// ws:// url of an online server (src/bin/server), without it there is no online mode
+    server: Option<String>,
+    /// the name online opponents see
+    name: String,
+}
+
+impl TryFrom<JsValue> for App {
+    type Error = JsValue;
+
+    fn try_from(value: JsValue) -> Result<Self, Self::Error> {
+        Ok(App {
+            width: Reflect::get(&value, &"width".into())?
+                .as_f64()
+                .unwrap_or(10.0) as u32,
+            height: Reflect::get(&value, &"height".into())?
+                .as_f64()
+                .unwrap_or(20.0) as u32,
+            preview: Reflect::get(&value, &"preview".into())?
+                .as_f64()
+                .unwrap_or(3.0) as usize,
+            skin: Reflect::get(&value, &"skin".into())?
+                .as_string()
+                .and_then(|name| Skin::by_name(&name))
+                .unwrap_or_default(),
+            pieces: match Reflect::get(&value, &"pieces".into())?.as_string() {
+                Some(json) if json.trim_start().starts_with('{') => {
+                    PieceSet::from_json(&json).map_err(|err| JsValue::from(err.to_string()))?
+                }
+                Some(name) => PieceSet::by_name(&name)
+                    .ok_or_else(|| JsValue::from(format!("unknown piece set: {}", name)))?,
+                None => PieceSet::standard(),
+            },
+            renderer: Reflect::get(&value, &"renderer".into())?
+                .as_string()
+                .and_then(|name| RendererKind::by_name(&name))
+                .unwrap_or(RendererKind::Canvas),
+            gravity: Reflect::get(&value, &"gravity".into())?
+                .as_f64()
+                .map(Gravity::Fixed)
+                .unwrap_or(Gravity::Level),
+            level: Reflect::get(&value, &"level".into())?
+                .as_f64()
+                .unwrap_or(1.0) as u32,
+            are: Reflect::get(&value, &"are".into())?
+                .as_f64()
+                .unwrap_or(6.0) as u32,
+            line_clear: Reflect::get(&value, &"lineClear".into())?
+                .as_f64()
+                .unwrap_or(20.0) as u32,
+            finesse: Reflect::get(&value, &"finesse".into())?
+                .as_bool()
+                .unwrap_or(true),
+            buttons: Reflect::get(&value, &"buttons".into())?
+                .as_bool()
+                .unwrap_or(false),
+            keys: Reflect::get(&value, &"keys".into())?
+                .as_string()
+                .and_then(|name| Preset::by_name(&name))
+                .map(Keymap::preset)
+                .unwrap_or_default(),
+            gamepad: {
+                let rebinds = Reflect::get(&value, &"gamepad".into())?;
+                let mut map = GamepadMap::default();
+                if rebinds.is_object() {
+                    for (button, action) in Object::entries(&rebinds.into())
+                        .iter()
+                        .map(|entry| (Reflect::get_u32(&entry, 0), Reflect::get_u32(&entry, 1)))
+                    {
+                        let button = button?
+                            .as_string()
+                            .and_then(|button| button.parse::<usize>().ok())
+                            .ok_or_else(|| JsValue::from("gamepad buttons are numbers"))?;
+                        map = match action?.as_string() {
+                            Some(name) => map.bind(
+                                button,
+                                Action::by_name(&name).ok_or_else(|| {
+                                    JsValue::from(format!("unknown action: {}", name))
+                                })?,
+                            ),
+                            None => map.unbind(button),
+                        };
+                    }
+                }
+                map
+            },
+            versus: VersusSettings {
+                attack: match Reflect::get(&value, &"attack".into())?.as_string() {
+                    Some(json) => AttackTable::from_json(&json)
+                        .map_err(|err| JsValue::from(err.to_string()))?,
+                    None => AttackTable::default(),
+                },
+                holes: {
+                    let holes = Reflect::get(&value, &"holes".into())?;
+                    match (holes.as_f64(), holes.as_string().as_deref()) {
+                        (Some(column), _) => HolePlacement::Column(column as i32),
+                        (_, Some("per-row")) => HolePlacement::PerRow,
+                        _ => HolePlacement::PerAttack,
+                    }
+                },
+            },
+            server: Reflect::get(&value, &"server".into())?.as_string(),
+            name: Reflect::get(&value, &"name".into())?
+                .as_string()
+                .unwrap_or_else(|| "player".to_string()),
+        })
+    }
+}
+
+impl App {
+    // what a game gets started with (puzzles bring their own board size and pieces)
+    fn settings(&self) -> Settings {
+        Settings {
+            preview_len: self.preview,
+            piece_set: self.pieces.clone(),
+            timing: Timing {
+                gravity: self.gravity,
+                are_frames: self.are,
+                line_clear_frames: self.line_clear,
+                ..Default::default()
+            },
+            start_level: self.level,
+            finesse: if self.finesse { FinesseMode::Report } else { FinesseMode::Off },
+        }
+    }
+}
+
+impl Component for App {
+    fn render(&self) -> wasm_react::VNode {
+        let tetris = use_state(|| Tetris::with_settings(self.width, self.height, self.settings()));
+        // the puzzle being played (index in the pack), and how it goes so far:
+        let pack = use_state(Puzzle::pack);
+        let puzzle = use_state(|| None::<usize>);
+        let run = use_ref(None::<PuzzleRun>);
+        // the board editor (the game waits paused while it is open):
+        let editing = use_state(|| false);
+        let position = use_state(|| Position::empty(self.width, self.height));
+        // local versus (the single player game waits paused meanwhile):
+        let versus = use_state(|| false);
+        // online versus, only with a server (the single player game waits paused as well):
+        let online = use_state(|| false);
+
+        // autofocus the div handling key_down events once mounted:
+        let container = use_js_ref::<Element>(None);
+        use_effect(
+            {
+                let container = container.clone();
+                move || {
+                    container
+                        .current()
+                        .and_then(|el| el.dyn_into::<HtmlElement>().ok())
+                        .map(|el| el.focus().ok());
+
+                    || ()
+                }
+            },
+            Deps::none(),
+        );
+
+        // the game loop runs on requestAnimationFrame:
+        //  - every browser frame the gamepads get polled (they have no events)
+        //  - while "AI plays" is on, the bot sends a move every BOT_MOVE_MS
+        //  - then the real elapsed time gets fed into the game
+        //  - the game simulates gravity, lock delay and DAS on its own fixed timestep
+        let ai = use_state(|| false);
+        use_effect(
+            {
+                let mut tetris = tetris.clone();
+                let ai = ai.clone();
+                let mut gamepad = GamepadInput::new(self.gamepad.clone());
+                let mut autoplay = Autoplay::default();
+                let mut bot_ms = 0.0;
+                let mut run = run.clone();
+                move || {
+                    let game_loop = AnimationLoop::start(move |elapsed| {
+                        let inputs = gamepad.poll(&gamepad::read_buttons());
+                        let ai = *ai.value();
+                        tetris.set(|mut tetris| {
+                            for input in inputs {
+                                input.apply(&mut tetris);
+                            }
+                            if ai {
+                                bot_ms += elapsed;
+                                while bot_ms >= BOT_MOVE_MS {
+                                    bot_ms -= BOT_MOVE_MS;
+                                    autoplay.step(&mut tetris);
+                                }
+                            } else {
+                                autoplay.reset();
+                                bot_ms = 0.0;
+                            }
+                            tetris.update(elapsed);
+                            if let Some(run) = run.current_mut().as_mut() {
+                                for event in tetris.take_events() {
+                                    run.observe(&event);
+                                }
+                            }
+                            tetris
+                        })
+                    });
+                    move || drop(game_loop)
+                }
+            },
+            Deps::none(),
+        );
+
+        // keyboard input, which key does what is up to the keymap (saved in local storage):
+        let keymap = use_state(|| settings::load_keymap().unwrap_or_else(|| self.keys.clone()));
+        // while the settings panel listens for a new key, keys go there instead of the game:
+        let listening = use_state(|| None::<Action>);
+        let settings_message = use_state(|| None::<String>);
+        let handle_key_down = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                let mut keymap = keymap.clone();
+                let mut listening = listening.clone();
+                let mut settings_message = settings_message.clone();
+                let editing = editing.clone();
+                let versus = versus.clone();
+                let online = online.clone();
+
+                move |ev: KeyboardEvent| {
+                    let waiting = *listening.value();
+                    if let Some(action) = waiting {
+                        ev.prevent_default();
+                        let message = settings::rebind(&mut keymap, action, &ev.code());
+                        settings_message.set(|_| Some(message));
+                        listening.set(|_| None);
+                        return;
+                    }
+                    // typing into the editor is not playing, in versus the keys belong to VersusView (or OnlineView):
+                    if *editing.value() || *versus.value() || *online.value() {
+                        return;
+                    }
+                    // holding a key is handled by the game itself, so we ignore the browsers key-repeat
+                    let action = keymap.value().action(&ev.code());
+                    if let Some(action) = action {
+                        ev.prevent_default();
+                        if !ev.repeat() {
+                            apply_inputs(&mut tetris, vec![Input::Press(action)]);
+                        }
+                    }
+                }
+            },
+            Deps::none(),
+        );
+        let handle_key_up = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                let keymap = keymap.clone();
+                move |ev: KeyboardEvent| {
+                    let action = keymap.value().action(&ev.code());
+                    if let Some(action) = action {
+                        apply_inputs(&mut tetris, vec![Input::Release(action)]);
+                    }
+                }
+            },
+            Deps::none(),
+        );
+
+        // touch input on the board, see TouchGestures for what the gestures do:
+        let gestures = use_ref(TouchGestures::default());
+        let handle_touch = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                let mut gestures = gestures.clone();
+                move |ev: TouchEvent| {
+                    let touches = ev.touches().length() as usize;
+                    let (x, y) = ev
+                        .changed_touches()
+                        .get(0)
+                        .map(|touch| (touch.client_x() as f64, touch.client_y() as f64))
+                        .unwrap_or_default();
+                    let time = ev.time_stamp();
+                    let inputs = {
+                        let mut gestures = gestures.current_mut();
+                        match &*ev.type_() {
+                            "touchstart" => gestures.start(touches, x, y, time),
+                            "touchmove" => gestures.moved(x, y, time),
+                            "touchend" => gestures.end(touches, time),
+                            _ => gestures.cancel(),
+                        }
+                    };
+                    apply_inputs(&mut tetris, inputs);
+                }
+            },
+            Deps::none(),
+        );
+
+        // the optional on-screen buttons, each one knows its action through data-action:
+        let handle_button = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                move |ev: PointerEvent| {
+                    let action = ev
+                        .current_target()
+                        .and_then(|target| target.dyn_into::<Element>().ok())
+                        .and_then(|el| el.get_attribute("data-action"))
+                        .and_then(|name| Action::by_name(&name));
+                    if let Some(action) = action {
+                        let input = match &*ev.type_() {
+                            "pointerdown" => Input::Press(action),
+                            _ => Input::Release(action),
+                        };
+                        apply_inputs(&mut tetris, vec![input]);
+                    }
+                }
+            },
+            Deps::none(),
+        );
+        // versus or online, the single player game is out of the way:
+        let away = *versus.value() || *online.value();
+        let buttons = (self.buttons && !away).then(|| {
+            h!(div)
+                .style(
+                    &Style::new()
+                        .display("flex")
+                        .gap("0.3rem")
+                        .margin_top("0.5rem")
+                        .margin_left("1.5rem"),
+                )
+                .build(c![..Action::ALL.into_iter().map(|action| {
+                    h!(button)
+                        .key(Some(action.name()))
+                        .attr("data-action", &action.name().into())
+                        .on_pointerdown(&handle_button)
+                        .on_pointerup(&handle_button)
+                        .on_pointerleave(&handle_button)
+                        .on_pointercancel(&handle_button)
+                        .style(
+                            &Style::new()
+                                .font_size("1.5rem")
+                                .min_width("3rem")
+                                .touch_action("none"),
+                        )
+                        .build(c![action.label()])
+                })])
+        });
+
+        // the renderer, canvas unless asked for (or falling back to) the div-per-cell renderer:
+        let canvas = use_js_ref::<Element>(None);
+        let last_frame = use_ref(None::<Snapshot>);
+        let renderer_kind = use_state(|| self.renderer);
+        let renderer: Box<dyn Renderer> = match *renderer_kind.value() {
+            RendererKind::Canvas => Box::new(CanvasRenderer::new(
+                self.skin.clone(),
+                canvas.clone(),
+                last_frame.clone(),
+            )),
+            RendererKind::Dom => Box::new(DomRenderer::new(self.skin.clone())),
+        };
+        let frame = tetris.value().snapshot();
+        let view = renderer.view(&frame);
+
+        // after every render the renderer gets to paint (it skips the work if the frame did not change):
+        use_effect(
+            {
+                let mut renderer_kind = renderer_kind.clone();
+                move || {
+                    if renderer.draw(&frame).is_err() {
+                        renderer_kind.set(|_| RendererKind::Dom);
+                    }
+                    || ()
+                }
+            },
+            Deps::all(),
+        );
+
+        // the key settings, opening them pauses the game:
+        let show_settings = use_state(|| false);
+        let handle_settings = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                let mut show_settings = show_settings.clone();
+                let mut listening = listening.clone();
+                move |_: MouseEvent| {
+                    let open = !*show_settings.value();
+                    if open && !tetris.value().is_paused() {
+                        apply_inputs(&mut tetris, vec![Input::Press(Action::Pause)]);
+                    }
+                    listening.set(|_| None);
+                    show_settings.set(|_| open);
+                }
+            },
+            Deps::none(),
+        );
+        let keep_focus = settings::use_keep_focus();
+        // the bot does not play perfect finesse, so it would start over forever in strict mode:
+        let handle_ai = use_callback(
+            {
+                let mut ai = ai.clone();
+                let mut tetris = tetris.clone();
+                move |_: MouseEvent| {
+                    let on = !*ai.value();
+                    if on && tetris.value().finesse_mode() == FinesseMode::Strict {
+                        tetris.set(|mut tetris| {
+                            tetris.set_finesse_mode(FinesseMode::Report);
+                            tetris
+                        });
+                    }
+                    ai.set(|_| on);
+                }
+            },
+            Deps::none(),
+        );
+        // strict finesse: a shape with a finesse fault starts over from the top
+        let handle_strict = use_callback(
+            {
+                let mut ai = ai.clone();
+                let mut tetris = tetris.clone();
+                move |_: MouseEvent| {
+                    tetris.set(|mut tetris| {
+                        let mode = match tetris.finesse_mode() {
+                            FinesseMode::Strict => FinesseMode::Report,
+                            _ => FinesseMode::Strict,
+                        };
+                        tetris.set_finesse_mode(mode);
+                        tetris
+                    });
+                    ai.set(|_| false);
+                }
+            },
+            Deps::none(),
+        );
+        // picking a puzzle (or endless) starts a new game, so does retry:
+        let start = {
+            let mut tetris = tetris.clone();
+            let mut puzzle = puzzle.clone();
+            let mut run = run.clone();
+            let pack = pack.clone();
+            let (width, height, settings) = (self.width, self.height, self.settings());
+            move |picked: Option<usize>| {
+                let settings = Settings {
+                    finesse: tetris.value().finesse_mode(),
+                    ..settings.clone()
+                };
+                // (a puzzle that does not start falls back to endless)
+                let started = picked
+                    .and_then(|idx| pack.value().get(idx).cloned())
+                    .and_then(|picked| {
+                        let game = picked.start(settings.clone()).ok()?;
+                        Some((PuzzleRun::new(&picked), game))
+                    });
+                let picked = picked.filter(|_| started.is_some());
+                let game = match started {
+                    Some((picked_run, game)) => {
+                        *run.current_mut() = Some(picked_run);
+                        game
+                    }
+                    None => {
+                        *run.current_mut() = None;
+                        Tetris::with_settings(width, height, settings)
+                    }
+                };
+                tetris.set(|_| game);
+                puzzle.set(|_| picked);
+            }
+        };
+        let handle_puzzle = use_callback(
+            {
+                let mut start = start.clone();
+                move |ev: Event| {
+                    let picked = ev
+                        .target()
+                        .and_then(|target| Reflect::get(&target, &"value".into()).ok())
+                        .and_then(|value| value.as_string())
+                        .and_then(|value| value.parse::<usize>().ok());
+                    start(picked);
+                }
+            },
+            Deps::none(),
+        );
+        let handle_retry = use_callback(
+            {
+                let mut start = start.clone();
+                let puzzle = puzzle.clone();
+                move |_: MouseEvent| {
+                    let picked = *puzzle.value();
+                    start(picked);
+                }
+            },
+            Deps::none(),
+        );
+        // the editor starts from the position of the game, and a game from the edited position:
+        let handle_edit = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                let mut editing = editing.clone();
+                let mut position = position.clone();
+                move |_: MouseEvent| {
+                    let open = !*editing.value();
+                    if open {
+                        let current = Position::of(&tetris.value());
+                        position.set(|_| current);
+                        if !tetris.value().is_paused() {
+                            apply_inputs(&mut tetris, vec![Input::Press(Action::Pause)]);
+                        }
+                    }
+                    editing.set(|_| open);
+                }
+            },
+            Deps::none(),
+        );
+        let handle_play_position = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                let mut editing = editing.clone();
+                let mut puzzle = puzzle.clone();
+                let mut run = run.clone();
+                let settings = self.settings();
+                move |position: Position| {
+                    let settings = Settings {
+                        finesse: tetris.value().finesse_mode(),
+                        ..settings.clone()
+                    };
+                    *run.current_mut() = None;
+                    tetris.set(|_| position.start(settings));
+                    puzzle.set(|_| None);
+                    editing.set(|_| false);
+                }
+            },
+            Deps::none(),
+        );
+        let handle_versus = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                let mut versus = versus.clone();
+                let mut online = online.clone();
+                let mut ai = ai.clone();
+                let mut editing = editing.clone();
+                move |_: MouseEvent| {
+                    let on = !*versus.value();
+                    if on && !tetris.value().is_paused() {
+                        apply_inputs(&mut tetris, vec![Input::Press(Action::Pause)]);
+                    }
+                    ai.set(|_| false);
+                    editing.set(|_| false);
+                    online.set(|_| false);
+                    versus.set(|_| on);
+                }
+            },
+            Deps::none(),
+        );
+        let handle_online = use_callback(
+            {
+                let mut tetris = tetris.clone();
+                let mut versus = versus.clone();
+                let mut online = online.clone();
+                let mut ai = ai.clone();
+                let mut editing = editing.clone();
+                move |_: MouseEvent| {
+                    let on = !*online.value();
+                    if on && !tetris.value().is_paused() {
+                        apply_inputs(&mut tetris, vec![Input::Press(Action::Pause)]);
+                    }
+                    ai.set(|_| false);
+                    editing.set(|_| false);
+                    versus.set(|_| false);
+                    online.set(|_| on);
+                }
+            },
+            Deps::none(),
+        );
+        let versus_view = versus.value().then(|| {
+            VersusView {
+                width: self.width,
+                height: self.height,
+                settings: Settings {
+                    finesse: FinesseMode::Off,
+                    ..self.settings()
+                },
+                versus: self.versus.clone(),
+                skin: self.skin.clone(),
+                renderer: self.renderer,
+            }
+            .build()
+        });
+        let online_view = self.server.as_ref().filter(|_| *online.value()).map(|server| {
+            OnlineView {
+                server: server.clone(),
+                name: self.name.clone(),
+                settings: Settings {
+                    finesse: FinesseMode::Off,
+                    ..self.settings()
+                },
+                keymap: keymap.value().clone(),
+                skin: self.skin.clone(),
+                renderer: self.renderer,
+            }
+            .build()
+        });
+        let editor = (*editing.value() && !away).then(|| {
+            BoardEditor {
+                position: position.clone(),
+                skin: self.skin.clone(),
+                on_play: handle_play_position.clone(),
+            }
+            .build()
+        });
+        let puzzle_status = run.current().as_ref().map(|run| match run.status() {
+            PuzzleStatus::Playing => format!(
+                "goal: {} ({} piece{} left)",
+                run.goal().describe(),
+                run.pieces_left(),
+                if run.pieces_left() == 1 { "" } else { "s" }
+            ),
+            PuzzleStatus::Solved => "solved!".to_string(),
+            PuzzleStatus::Failed => "failed".to_string(),
+        });
+        let puzzles = h!(div)
+            .style(&Style::new().margin_top("0.5rem").margin_left("1.5rem"))
+            .build(c![
+                h!(select)
+                    .value(puzzle.value().map(|idx| idx.to_string()).unwrap_or_default())
+                    .on_change(&handle_puzzle)
+                    .build(c![
+                        h!(option).value("").build(c!["endless"]),
+                        ..pack.value().iter().enumerate().map(|(idx, puzzle)| {
+                            h!(option)
+                                .key(Some(idx))
+                                .value(idx.to_string())
+                                .build(c![format!("puzzle: {}", puzzle.name)])
+                        })
+                    ]),
+                puzzle_status.map(|status| {
+                    h!(span)
+                        .style(&Style::new().margin_left("0.5rem"))
+                        .build(c![status])
+                }),
+                puzzle.value().map(|_| {
+                    h!(button)
+                        .on_mousedown(&keep_focus)
+                        .on_click(&handle_retry)
+                        .style(&Style::new().margin_left("0.5rem"))
+                        .build(c!["retry"])
+                }),
+            ]);
+        let finesse = (self.finesse && !away).then(|| {
+            let strict = tetris.value().finesse_mode() == FinesseMode::Strict;
+            h!(div)
+                .style(&Style::new().margin_top("0.5rem").margin_left("1.5rem"))
+                .build(c![
+                    format!("finesse faults: {}", tetris.value().finesse_faults()),
+                    h!(button)
+                        .on_mousedown(&keep_focus)
+                        .on_click(&handle_strict)
+                        .style(&Style::new().margin_left("0.5rem"))
+                        .build(c![if strict { "strict finesse: on" } else { "strict finesse: off" }]),
+                ])
+        });
+        let settings_panel = show_settings.value().then(|| {
+            KeymapSettings {
+                keymap: keymap.clone(),
+                listening: listening.clone(),
+                message: settings_message.clone(),
+            }
+            .build()
+        });
+
+        // div for the 'whole page' to just listen for on_keydown everywhere, gets autofocus with use_effect
+        h!(div)
+            .ref_container(&container)
+            .tabindex(0)
+            .on_keydown(&handle_key_down)
+            .on_keyup(&handle_key_up)
+            .style(
+                &Style::new()
+                    .width("100%")
+                    .height("100%")
+                    .outline("none")
+            )
+            .build(c![
+                // touch gestures only on the game itself, touch-action keeps the browser from scrolling:
+                (!away).then(|| {
+                    h!(div)
+                        .attr_callback("onTouchStart", &handle_touch)
+                        .attr_callback("onTouchMove", &handle_touch)
+                        .attr_callback("onTouchEnd", &handle_touch)
+                        .attr_callback("onTouchCancel", &handle_touch)
+                        .style(&Style::new().display("inline-block").touch_action("none"))
+                        .build(c![view])
+                }),
+                versus_view,
+                online_view,
+                buttons,
+                h!(div)
+                    .style(&Style::new().margin_top("0.5rem").margin_left("1.5rem"))
+                    .build(c![
+                        h!(button)
+                            .on_mousedown(&keep_focus)
+                            .on_click(&handle_settings)
+                            .build(c!["keys"]),
+                        h!(button)
+                            .on_mousedown(&keep_focus)
+                            .on_click(&handle_ai)
+                            .style(&Style::new().margin_left("0.5rem"))
+                            .build(c![if *ai.value() { "AI plays: on" } else { "AI plays: off" }]),
+                        h!(button)
+                            .on_mousedown(&keep_focus)
+                            .on_click(&handle_edit)
+                            .style(&Style::new().margin_left("0.5rem"))
+                            .build(c![if *editing.value() { "close editor" } else { "edit board" }]),
+                        h!(button)
+                            .on_mousedown(&keep_focus)
+                            .on_click(&handle_versus)
+                            .style(&Style::new().margin_left("0.5rem"))
+                            .build(c![if *versus.value() { "versus: on" } else { "versus: off" }]),
+                        self.server.as_ref().map(|_| {
+                            h!(button)
+                                .on_mousedown(&keep_focus)
+                                .on_click(&handle_online)
+                                .style(&Style::new().margin_left("0.5rem"))
+                                .build(c![if *online.value() { "online: on" } else { "online: off" }])
+                        }),
+                    ]),
+                finesse,
+                (!away).then_some(puzzles),
+                editor,
+                settings_panel,
+            ])
+    }
+}
+
+// feeds inputs into the game (without re-rendering if there are none)
+fn apply_inputs(tetris: &mut State<Tetris>, inputs: Vec<Input>) {
+    if inputs.is_empty() {
+        return;
+    }
+    tetris.set(|mut tetris| {
+        for input in inputs {
+            input.apply(&mut tetris);
+        }
+        tetris
+    });
+}
+
+export_components! {App}
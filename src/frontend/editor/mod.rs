@@ -0,0 +1,332 @@
+use wasm_bindgen::JsCast;
+use wasm_react::{
+    c,
+    callback::PersistedCallback,
+    h,
+    hooks::{use_callback, use_state, Deps, State},
+    props::Style,
+    Component, VNode,
+};
+use web_sys::{Element, Event, MouseEvent};
+
+use super::{settings::use_keep_focus, skin::Skin};
+use crate::tetris_game::{
+    fumen,
+    position::{self, Position},
+    shapes::{PieceKind, XY},
+};
+
+/*
+*   The board editor, for building a position to play from.
+*       - pick a brush (a piece kind, garbage or the eraser) and click cells to paint them,
+*         clicking a cell that already has the brush kind empties it again
+*       - the queue (current piece first) and hold get typed in as letters, like "TIOJ"
+*       - the code of the position can be copied and loaded again (see tetris_game::position),
+*         fumen links/strings load too (their first page) and 10 wide boards get a fumen to copy
+*   App owns the position, "play from here" hands it back to App to start a game.
+*/
+
+pub struct BoardEditor {
+    pub position: State<Position>,
+    pub skin: Skin,
+    /// starts a game from the position
+    pub on_play: PersistedCallback<Position>,
+}
+
+// the value of the input (or select) an event came from
+fn input_value(ev: &Event) -> String {
+    ev.target()
+        .and_then(|target| js_sys::Reflect::get(&target, &"value".into()).ok())
+        .and_then(|value| value.as_string())
+        .unwrap_or_default()
+}
+
+// the attribute a clicked element carries its cell / brush in
+fn data(ev: &MouseEvent, name: &str) -> Option<String> {
+    ev.current_target()
+        .and_then(|target| target.dyn_into::<Element>().ok())
+        .and_then(|el| el.get_attribute(name))
+}
+
+// the position as a fumen page: the board, and the pieces as a quiz comment (fumen is always 10 wide)
+fn fumen_page(position: &Position) -> Option<fumen::Page> {
+    let (width, height) = position.size();
+    if width != fumen::WIDTH {
+        return None;
+    }
+    let mut page = fumen::Page::default();
+    for (XY(x, y), kind) in position.cells() {
+        page.set(XY(x, height - 1 - y), Some(kind));
+    }
+    let quiz = fumen::Quiz {
+        hold: position.hold,
+        current: position.current,
+        queue: position.queue.clone(),
+    };
+    if quiz != fumen::Quiz::default() {
+        page.comment = quiz.comment();
+    }
+    Some(page)
+}
+
+impl Component for BoardEditor {
+    fn render(&self) -> VNode {
+        // None is the eraser:
+        let brush = use_state(|| Some(PieceKind::Garbage));
+        let code = use_state(String::new);
+        let message = use_state(|| None::<String>);
+
+        let handle_cell = use_callback(
+            {
+                let mut position = self.position.clone();
+                let brush = brush.clone();
+                move |ev: MouseEvent| {
+                    let xy = data(&ev, "data-cell").and_then(|cell| {
+                        let (x, y) = cell.split_once(' ')?;
+                        Some(XY(x.parse().ok()?, y.parse().ok()?))
+                    });
+                    if let Some(xy) = xy {
+                        let brush = *brush.value();
+                        position.set(|mut position| {
+                            let kind = if position.get(xy) == brush {
+                                None
+                            } else {
+                                brush
+                            };
+                            position.set(xy, kind);
+                            position
+                        });
+                    }
+                }
+            },
+            Deps::none(),
+        );
+        let handle_brush = use_callback(
+            {
+                let mut brush = brush.clone();
+                move |ev: MouseEvent| {
+                    let kind = data(&ev, "data-brush")
+                        .and_then(|letter| letter.chars().next())
+                        .and_then(PieceKind::from_letter);
+                    brush.set(|_| kind);
+                }
+            },
+            Deps::none(),
+        );
+        let handle_queue = use_callback(
+            {
+                let mut position = self.position.clone();
+                let mut message = message.clone();
+                move |ev: Event| match position::parse_pieces(&input_value(&ev)) {
+                    Ok(pieces) => {
+                        let mut pieces = pieces.into_iter();
+                        position.set(|mut position| {
+                            position.current = pieces.next();
+                            position.queue = pieces.collect();
+                            position
+                        });
+                        message.set(|_| None);
+                    }
+                    Err(err) => message.set(|_| Some(err.to_string())),
+                }
+            },
+            Deps::none(),
+        );
+        let handle_hold = use_callback(
+            {
+                let mut position = self.position.clone();
+                let mut message = message.clone();
+                move |ev: Event| match position::parse_pieces(&input_value(&ev)) {
+                    // typing a new letter replaces the old one:
+                    Ok(pieces) => {
+                        position.set(|mut position| {
+                            position.hold = pieces.last().copied();
+                            position
+                        });
+                        message.set(|_| None);
+                    }
+                    Err(err) => message.set(|_| Some(err.to_string())),
+                }
+            },
+            Deps::none(),
+        );
+        let handle_code = use_callback(
+            {
+                let mut code = code.clone();
+                move |ev: Event| {
+                    let text = input_value(&ev);
+                    code.set(|_| text);
+                }
+            },
+            Deps::none(),
+        );
+        let handle_load = use_callback(
+            {
+                let mut position = self.position.clone();
+                let code = code.clone();
+                let mut message = message.clone();
+                move |_: MouseEvent| {
+                    let code = code.value();
+                    let loaded = if code.contains("115@") {
+                        let height = position.value().size().1 as u32;
+                        fumen::decode_positions(&code, height)
+                            .map_err(|err| err.to_string())
+                            .and_then(|pages| pages.into_iter().next().ok_or("empty fumen".into()))
+                    } else {
+                        Position::from_code(&code).map_err(|err| err.to_string())
+                    };
+                    match loaded {
+                        Ok(loaded) => {
+                            position.set(|_| loaded);
+                            message.set(|_| Some("position loaded".into()));
+                        }
+                        Err(err) => message.set(|_| Some(err)),
+                    }
+                }
+            },
+            Deps::none(),
+        );
+        let handle_clear = use_callback(
+            {
+                let mut position = self.position.clone();
+                move |_: MouseEvent| {
+                    position.set(|mut position| {
+                        position.clear_board();
+                        position
+                    })
+                }
+            },
+            Deps::none(),
+        );
+        let handle_play = use_callback(
+            {
+                let position = self.position.clone();
+                let mut on_play = self.on_play.to_closure();
+                move |_: MouseEvent| on_play(position.value().clone())
+            },
+            Deps::none(),
+        );
+
+        let keep_focus = use_keep_focus();
+        let position = self.position.value();
+        let (width, height) = position.size();
+        let selected = *brush.value();
+        let mut queue: Vec<PieceKind> = position.current.into_iter().collect();
+        queue.extend(position.queue.iter().copied());
+        let brushes = PieceKind::ALL
+            .into_iter()
+            .map(Some)
+            .chain([None])
+            .map(|kind| {
+                let letter = kind.map(|kind| kind.letter()).unwrap_or('_');
+                h!(button)
+                    .key(Some(letter.to_string()))
+                    .attr("data-brush", &letter.to_string().into())
+                    .on_mousedown(&keep_focus)
+                    .on_click(&handle_brush)
+                    .style(&Style::new().outline(if selected == kind {
+                        "2px solid lightcyan"
+                    } else {
+                        "none"
+                    }))
+                    .build(c![kind
+                        .map(|kind| self.skin.glyph(kind))
+                        .unwrap_or("eraser")])
+            });
+        let row = Style::new().margin_top("0.5rem");
+
+        h!(div)
+            .style(
+                &Style::new()
+                    .margin_top("0.5rem")
+                    .margin_left("1.5rem")
+                    .color("lightcyan")
+                    .font_size("1rem"),
+            )
+            .build(c![
+                h!(div).build(c!["brush: ", ..brushes]),
+                h!(div)
+                    .style(
+                        &Style::new()
+                            .display("inline-grid")
+                            .grid_template(format!(
+                                "repeat({}, 1.2em) / repeat({}, 1.2em)",
+                                height, width
+                            ))
+                            .margin_top("0.5rem")
+                            .border("1px solid lightcyan")
+                            .cursor("pointer"),
+                    )
+                    .build(c![..(0..height)
+                        .flat_map(|y| (0..width).map(move |x| XY(x, y)))
+                        .map(|xy| {
+                            h!(div)
+                                .key(Some(format!("{} {}", xy.0, xy.1)))
+                                .attr("data-cell", &format!("{} {}", xy.0, xy.1).into())
+                                .on_click(&handle_cell)
+                                .style(&Style::new().outline("1px solid #333"))
+                                .build(c![position.get(xy).map(|kind| self.skin.glyph(kind))])
+                        })]),
+                h!(div).style(&row).build(c![
+                    "queue: ",
+                    h!(input)
+                        .value(position::pieces_text(&queue))
+                        .on_change(&handle_queue)
+                        .build(c![]),
+                    " hold: ",
+                    h!(input)
+                        .value(
+                            position
+                                .hold
+                                .map(|kind| kind.letter().to_string())
+                                .unwrap_or_default()
+                        )
+                        .on_change(&handle_hold)
+                        .style(&Style::new().width("2em"))
+                        .build(c![]),
+                ]),
+                h!(div).style(&row).build(c![
+                    "code: ",
+                    h!(input)
+                        .value(position.to_code())
+                        .attr("readOnly", &true.into())
+                        .style(&Style::new().width("20em"))
+                        .build(c![]),
+                ]),
+                fumen_page(&position).map(|page| {
+                    h!(div).style(&row).build(c![
+                        "fumen: ",
+                        h!(input)
+                            .value(fumen::encode(&[page]))
+                            .attr("readOnly", &true.into())
+                            .style(&Style::new().width("20em"))
+                            .build(c![]),
+                    ])
+                }),
+                h!(div).style(&row).build(c![
+                    h!(input)
+                        .value(code.value().clone())
+                        .attr("placeholder", &"paste a code or fumen".into())
+                        .on_change(&handle_code)
+                        .style(&Style::new().width("20em"))
+                        .build(c![]),
+                    h!(button)
+                        .on_mousedown(&keep_focus)
+                        .on_click(&handle_load)
+                        .build(c!["load"]),
+                ]),
+                h!(div).style(&row).build(c![
+                    h!(button)
+                        .on_mousedown(&keep_focus)
+                        .on_click(&handle_clear)
+                        .build(c!["clear board"]),
+                    h!(button)
+                        .on_mousedown(&keep_focus)
+                        .on_click(&handle_play)
+                        .style(&Style::new().margin_left("0.5rem"))
+                        .build(c!["play from here"]),
+                ]),
+                h!(div).build(c![message.value().clone()]),
+            ])
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use js_sys::Function;
+use wasm_bindgen::{prelude::Closure, JsCast, UnwrapThrowExt};
+use web_sys::window;
+
+type FrameClosure = Closure<dyn FnMut(f64)>;
+
+/// ### AnimationLoop
+/// Calls on_frame once per browser frame (requestAnimationFrame) with the milliseconds
+/// that passed since the last call. The game itself runs on its own fixed timestep (see tetris_game::timing),
+/// so it does not matter how regular the browser delivers these frames.
+///
+/// The loop stops once this gets dropped.
+pub struct AnimationLoop {
+    handle: Rc<Cell<i32>>,
+    closure: Rc<RefCell<Option<FrameClosure>>>,
+}
+
+impl AnimationLoop {
+    pub fn start(mut on_frame: impl FnMut(f64) + 'static) -> Self {
+        let handle = Rc::new(Cell::new(0));
+        let closure: Rc<RefCell<Option<FrameClosure>>> = Rc::new(RefCell::new(None));
+        let last_timestamp = Cell::new(None::<f64>);
+
+        *closure.borrow_mut() = Some(Closure::new({
+            let handle = handle.clone();
+            let closure = closure.clone();
+            move |timestamp: f64| {
+                let elapsed = last_timestamp
+                    .replace(Some(timestamp))
+                    .map(|last| timestamp - last)
+                    .unwrap_or(0.0);
+                on_frame(elapsed);
+                // schedule the next frame (unless we got stopped in on_frame):
+                if let Some(next) = closure.borrow().as_ref() {
+                    handle.set(request_animation_frame(next));
+                }
+            }
+        }));
+        handle.set(request_animation_frame(
+            closure.borrow().as_ref().unwrap_throw(),
+        ));
+
+        Self { handle, closure }
+    }
+}
+
+impl Drop for AnimationLoop {
+    fn drop(&mut self) {
+        window()
+            .unwrap_throw()
+            .cancel_animation_frame(self.handle.get())
+            .unwrap_throw();
+        // breaks the reference cycle between the closure and itself:
+        self.closure.borrow_mut().take();
+    }
+}
+
+fn request_animation_frame(closure: &FrameClosure) -> i32 {
+    window()
+        .unwrap_throw()
+        .request_animation_frame(closure.as_ref().dyn_ref::<Function>().unwrap_throw())
+        .unwrap_throw()
+}
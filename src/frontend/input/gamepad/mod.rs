@@ -0,0 +1,215 @@
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton};
+
+use super::{Action, Input};
+
+/*
+*   Controller input through the browser Gamepad API.
+*   Gamepads can not send events, so the game loop polls them every frame:
+*       - read_buttons() collects which buttons are held on any connected gamepad
+*       - GamepadInput compares that with the last poll and turns the changes into Inputs
+*   From there on it is the same as the keyboard, so DAS / soft drop work while a button is held.
+*/
+
+// button indices of the "standard" gamepad layout (xbox names)
+pub const BUTTON_A: usize = 0;
+pub const BUTTON_B: usize = 1;
+pub const BUTTON_X: usize = 2;
+pub const BUTTON_Y: usize = 3;
+pub const SHOULDER_LEFT: usize = 4;
+pub const SHOULDER_RIGHT: usize = 5;
+pub const START: usize = 9;
+pub const DPAD_UP: usize = 12;
+pub const DPAD_DOWN: usize = 13;
+pub const DPAD_LEFT: usize = 14;
+pub const DPAD_RIGHT: usize = 15;
+
+/// how far the left stick has to be pushed to count as the matching d-pad direction
+const STICK_DEADZONE: f64 = 0.5;
+
+/// Which button triggers which action (several buttons can share one action)
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadMap {
+    buttons: Vec<(usize, Action)>,
+}
+
+impl GamepadMap {
+    /// a map without any buttons bound
+    pub fn empty() -> Self {
+        Self { buttons: vec![] }
+    }
+
+    /// binds the button to the action, replacing what the button did before
+    pub fn bind(mut self, button: usize, action: Action) -> Self {
+        self.buttons.retain(|(b, _)| *b != button);
+        self.buttons.push((button, action));
+        self
+    }
+
+    /// removes whatever the button was bound to
+    pub fn unbind(mut self, button: usize) -> Self {
+        self.buttons.retain(|(b, _)| *b != button);
+        self
+    }
+
+    pub fn action(&self, button: usize) -> Option<Action> {
+        self.buttons
+            .iter()
+            .find(|(b, _)| *b == button)
+            .map(|(_, action)| *action)
+    }
+}
+
+/// ### the default layout
+/// - d-pad left/right/down :move and soft drop, up :hard drop
+/// - A :rotate clockwise, B :counter clockwise, Y :180
+/// - X and the shoulders :hold
+/// - start :pause
+impl Default for GamepadMap {
+    fn default() -> Self {
+        Self::empty()
+            .bind(DPAD_LEFT, Action::MoveLeft)
+            .bind(DPAD_RIGHT, Action::MoveRight)
+            .bind(DPAD_DOWN, Action::SoftDrop)
+            .bind(DPAD_UP, Action::HardDrop)
+            .bind(BUTTON_A, Action::RotateCW)
+            .bind(BUTTON_B, Action::RotateCCW)
+            .bind(BUTTON_Y, Action::Rotate180)
+            .bind(BUTTON_X, Action::Hold)
+            .bind(SHOULDER_LEFT, Action::Hold)
+            .bind(SHOULDER_RIGHT, Action::Hold)
+            .bind(START, Action::Pause)
+    }
+}
+
+/// Remembers which actions were held at the last poll
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GamepadInput {
+    map: GamepadMap,
+    held: Vec<Action>,
+}
+
+impl GamepadInput {
+    pub fn new(map: GamepadMap) -> Self {
+        Self { map, held: vec![] }
+    }
+
+    /// buttons: which button index is pressed right now (see read_buttons())
+    pub fn poll(&mut self, buttons: &[bool]) -> Vec<Input> {
+        let mut held: Vec<Action> = vec![];
+        for (button, _) in buttons.iter().enumerate().filter(|(_, pressed)| **pressed) {
+            match self.map.action(button) {
+                Some(action) if !held.contains(&action) => held.push(action),
+                _ => {}
+            }
+        }
+
+        let released = self
+            .held
+            .iter()
+            .filter(|action| !held.contains(action))
+            .map(|action| Input::Release(*action));
+        let pressed = held
+            .iter()
+            .filter(|action| !self.held.contains(action))
+            .map(|action| Input::Press(*action));
+        let inputs = released.chain(pressed).collect();
+        self.held = held;
+        inputs
+    }
+}
+
+/// Pressed state of every button, combined over all connected gamepads.
+/// The left stick counts as the d-pad.
+pub fn read_buttons() -> Vec<bool> {
+    let gamepads = match web_sys::window().and_then(|w| w.navigator().get_gamepads().ok()) {
+        Some(gamepads) => gamepads,
+        // no Gamepad API in this browser:
+        None => return vec![],
+    };
+    let mut buttons: Vec<bool> = vec![];
+    // unused slots in the list are null:
+    for gamepad in gamepads
+        .iter()
+        .filter_map(|pad| pad.dyn_into::<Gamepad>().ok())
+    {
+        if !gamepad.connected() {
+            continue;
+        }
+        let pad_buttons = gamepad.buttons();
+        buttons.resize(
+            buttons.len().max(pad_buttons.length() as usize).max(16),
+            false,
+        );
+        for (i, button) in pad_buttons.iter().enumerate() {
+            buttons[i] |= button
+                .dyn_into::<GamepadButton>()
+                .map(|button| button.pressed())
+                .unwrap_or(false);
+        }
+
+        let axes = gamepad.axes();
+        let (x, y) = (
+            axes.get(0).as_f64().unwrap_or(0.0),
+            axes.get(1).as_f64().unwrap_or(0.0),
+        );
+        buttons[DPAD_LEFT] |= x < -STICK_DEADZONE;
+        buttons[DPAD_RIGHT] |= x > STICK_DEADZONE;
+        buttons[DPAD_DOWN] |= y > STICK_DEADZONE;
+    }
+    buttons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(indices: &[usize]) -> Vec<bool> {
+        (0..16).map(|i| indices.contains(&i)).collect()
+    }
+
+    #[test]
+    fn only_changes_become_inputs() {
+        let mut gamepad = GamepadInput::default();
+        assert_eq!(
+            gamepad.poll(&pressed(&[DPAD_LEFT])),
+            vec![Input::Press(Action::MoveLeft)]
+        );
+        // still held, the game does the auto repeat:
+        assert_eq!(gamepad.poll(&pressed(&[DPAD_LEFT])), vec![]);
+        assert_eq!(
+            gamepad.poll(&pressed(&[DPAD_DOWN])),
+            vec![
+                Input::Release(Action::MoveLeft),
+                Input::Press(Action::SoftDrop)
+            ]
+        );
+        assert_eq!(gamepad.poll(&[]), vec![Input::Release(Action::SoftDrop)]);
+    }
+
+    #[test]
+    fn buttons_sharing_an_action_press_it_once() {
+        let mut gamepad = GamepadInput::default();
+        assert_eq!(
+            gamepad.poll(&pressed(&[SHOULDER_LEFT, SHOULDER_RIGHT])),
+            vec![Input::Press(Action::Hold)]
+        );
+        // letting go of one of them keeps the action held:
+        assert_eq!(gamepad.poll(&pressed(&[SHOULDER_RIGHT])), vec![]);
+    }
+
+    #[test]
+    fn buttons_can_be_remapped() {
+        let map = GamepadMap::default()
+            .bind(BUTTON_A, Action::HardDrop)
+            .unbind(DPAD_UP);
+        assert_eq!(map.action(BUTTON_A), Some(Action::HardDrop));
+        assert_eq!(map.action(DPAD_UP), None);
+
+        let mut gamepad = GamepadInput::new(map);
+        assert_eq!(
+            gamepad.poll(&pressed(&[BUTTON_A, DPAD_UP])),
+            vec![Input::Press(Action::HardDrop)]
+        );
+    }
+}
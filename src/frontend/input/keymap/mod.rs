@@ -0,0 +1,302 @@
+use super::Action;
+
+/*
+*   Which key (KeyboardEvent.code) triggers which Action.
+*       - a key triggers at most one action, an action can have several keys
+*       - starts from one of the presets, every action can be rebound
+*       - gets saved as a small json list of [key, action] pairs (see App, it lives in local storage)
+*/
+
+/// Keymaps everyone can start from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// arrow keys, space hard drop, z/x rotate, c hold
+    Guideline,
+    /// wasd to move, j/k/l rotate
+    Wasd,
+    /// h/l move, j soft drop, k hard drop
+    Vim,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Guideline, Preset::Wasd, Preset::Vim];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Guideline => "guideline",
+            Preset::Wasd => "wasd",
+            Preset::Vim => "vim",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    fn bindings(self) -> &'static [(&'static str, Action)] {
+        match self {
+            Preset::Guideline => &[
+                ("ArrowLeft", Action::MoveLeft),
+                ("ArrowRight", Action::MoveRight),
+                ("ArrowDown", Action::SoftDrop),
+                ("Space", Action::HardDrop),
+                ("ArrowUp", Action::RotateCW),
+                ("KeyX", Action::RotateCW),
+                ("KeyZ", Action::RotateCCW),
+                ("ControlLeft", Action::RotateCCW),
+                ("KeyA", Action::Rotate180),
+                ("KeyC", Action::Hold),
+                ("ShiftLeft", Action::Hold),
+                ("Escape", Action::Pause),
+                ("F1", Action::Pause),
+            ],
+            Preset::Wasd => &[
+                ("KeyA", Action::MoveLeft),
+                ("KeyD", Action::MoveRight),
+                ("KeyS", Action::SoftDrop),
+                ("KeyW", Action::HardDrop),
+                ("KeyK", Action::RotateCW),
+                ("KeyJ", Action::RotateCCW),
+                ("KeyL", Action::Rotate180),
+                ("Space", Action::Hold),
+                ("Escape", Action::Pause),
+            ],
+            Preset::Vim => &[
+                ("KeyH", Action::MoveLeft),
+                ("KeyL", Action::MoveRight),
+                ("KeyJ", Action::SoftDrop),
+                ("KeyK", Action::HardDrop),
+                ("KeyF", Action::RotateCW),
+                ("KeyD", Action::RotateCCW),
+                ("KeyS", Action::Rotate180),
+                ("Space", Action::Hold),
+                ("Escape", Action::Pause),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeymapError {
+    /// the key already triggers another action
+    Conflict { key: String, action: Action },
+    /// the saved keymap could not be read
+    Parse(String),
+}
+
+impl std::fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeymapError::Conflict { key, action } => {
+                write!(f, "{} is already bound to {}", key, action.name())
+            }
+            KeymapError::Parse(err) => write!(f, "invalid keymap: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(String, Action)>,
+}
+
+impl Keymap {
+    pub fn preset(preset: Preset) -> Self {
+        Self {
+            bindings: preset
+                .bindings()
+                .iter()
+                .map(|(key, action)| (key.to_string(), *action))
+                .collect(),
+        }
+    }
+
+    /// the action the key triggers
+    pub fn action(&self, key: &str) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, action)| *action)
+    }
+
+    /// all keys bound to the action
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = &str> {
+        self.bindings
+            .iter()
+            .filter(move |(_, a)| *a == action)
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// Makes key the only key of the action.
+    /// Fails (without changing anything) if the key is already used by another action.
+    pub fn rebind(&mut self, action: Action, key: &str) -> Result<(), KeymapError> {
+        match self.action(key) {
+            Some(other) if other != action => Err(KeymapError::Conflict {
+                key: key.to_string(),
+                action: other,
+            }),
+            _ => {
+                self.bindings.retain(|(_, a)| *a != action);
+                self.bindings.push((key.to_string(), action));
+                Ok(())
+            }
+        }
+    }
+
+    /// adds another key to the action, fails if the key is already used by another action
+    pub fn bind(&mut self, action: Action, key: &str) -> Result<(), KeymapError> {
+        match self.action(key) {
+            Some(other) if other != action => Err(KeymapError::Conflict {
+                key: key.to_string(),
+                action: other,
+            }),
+            Some(_) => Ok(()),
+            None => {
+                self.bindings.push((key.to_string(), action));
+                Ok(())
+            }
+        }
+    }
+
+    /// the keymap as json, like [["ArrowLeft", "left"], ...]
+    pub fn to_json(&self) -> String {
+        let pairs: Vec<(&str, &str)> = self
+            .bindings
+            .iter()
+            .map(|(key, action)| (key.as_str(), action.name()))
+            .collect();
+        serde_json::to_string(&pairs).expect("keymap always serializes")
+    }
+
+    /// reads a keymap saved with to_json(), conflicting keys are an error
+    pub fn from_json(json: &str) -> Result<Self, KeymapError> {
+        let pairs: Vec<(String, String)> =
+            serde_json::from_str(json).map_err(|err| KeymapError::Parse(err.to_string()))?;
+        let mut keymap = Self { bindings: vec![] };
+        for (key, name) in pairs {
+            let action = Action::by_name(&name)
+                .ok_or_else(|| KeymapError::Parse(format!("unknown action: {}", name)))?;
+            keymap.bind(action, &key)?;
+        }
+        Ok(keymap)
+    }
+}
+
+/// the keys of the left player in versus, WASD and the keys around it
+const VERSUS_LEFT: &[(&str, Action)] = &[
+    ("KeyA", Action::MoveLeft),
+    ("KeyD", Action::MoveRight),
+    ("KeyS", Action::SoftDrop),
+    ("KeyW", Action::HardDrop),
+    ("KeyE", Action::RotateCW),
+    ("KeyQ", Action::RotateCCW),
+    ("KeyR", Action::Rotate180),
+    ("ShiftLeft", Action::Hold),
+    ("Escape", Action::Pause),
+];
+
+/// the keys of the right player in versus, the arrows and the keys left of right shift
+const VERSUS_RIGHT: &[(&str, Action)] = &[
+    ("ArrowLeft", Action::MoveLeft),
+    ("ArrowRight", Action::MoveRight),
+    ("ArrowDown", Action::SoftDrop),
+    ("ArrowUp", Action::HardDrop),
+    ("Period", Action::RotateCW),
+    ("Comma", Action::RotateCCW),
+    ("Slash", Action::Rotate180),
+    ("ShiftRight", Action::Hold),
+    ("Escape", Action::Pause),
+];
+
+impl Keymap {
+    /// Two keymaps for two players on one keyboard (only pause is shared)
+    pub fn versus() -> [Self; 2] {
+        [VERSUS_LEFT, VERSUS_RIGHT].map(|bindings| Self {
+            bindings: bindings
+                .iter()
+                .map(|(key, action)| (key.to_string(), *action))
+                .collect(),
+        })
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset(Preset::Guideline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_bind_every_action_without_conflicts() {
+        for preset in Preset::ALL {
+            let keymap = Keymap::preset(preset);
+            for action in Action::ALL {
+                assert!(keymap.keys(action).next().is_some(), "{:?}", action);
+            }
+            // rebuilding the preset key by key would fail on a key used twice:
+            assert_eq!(Keymap::from_json(&keymap.to_json()), Ok(keymap));
+            assert_eq!(Preset::by_name(preset.name()), Some(preset));
+        }
+    }
+
+    #[test]
+    fn versus_keymaps_split_the_keyboard() {
+        let [left, right] = Keymap::versus();
+        for action in Action::ALL {
+            assert!(left.keys(action).next().is_some(), "{:?}", action);
+            assert!(right.keys(action).next().is_some(), "{:?}", action);
+        }
+        for (key, action) in &left.bindings {
+            let shared = right.action(key);
+            assert!(
+                shared.is_none() || (*action == Action::Pause && shared == Some(Action::Pause)),
+                "{}",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn rebind_replaces_the_keys_of_an_action() {
+        let mut keymap = Keymap::default();
+        keymap.rebind(Action::Hold, "KeyQ").unwrap();
+        assert_eq!(keymap.keys(Action::Hold).collect::<Vec<_>>(), ["KeyQ"]);
+        assert_eq!(keymap.action("KeyC"), None);
+        assert_eq!(keymap.action("KeyQ"), Some(Action::Hold));
+        // binding the key it already has is fine:
+        assert_eq!(keymap.rebind(Action::Hold, "KeyQ"), Ok(()));
+    }
+
+    #[test]
+    fn detects_conflicts() {
+        let mut keymap = Keymap::default();
+        let before = keymap.clone();
+        assert_eq!(
+            keymap.rebind(Action::Hold, "Space"),
+            Err(KeymapError::Conflict {
+                key: "Space".into(),
+                action: Action::HardDrop
+            })
+        );
+        assert_eq!(keymap, before);
+        assert!(Keymap::from_json(r#"[["KeyA", "left"], ["KeyA", "right"]]"#).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        assert!(matches!(
+            Keymap::from_json("nope"),
+            Err(KeymapError::Parse(_))
+        ));
+        assert!(matches!(
+            Keymap::from_json(r#"[["KeyA", "jump"]]"#),
+            Err(KeymapError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+pub mod gamepad;
+pub mod keymap;
+pub mod touch;
+
+use crate::tetris_game::{Direction, Tetris};
+
+/*
+*   The action layer between the different inputs and the game.
+*   Keyboard, gamepads, touch gestures and the on-screen buttons all get turned into
+*   Input::Press / Input::Release of an Action, and only those touch the game.
+*   Which key does what is up to the Keymap.
+*/
+
+/// Something the player can do, independent of how it got triggered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCW,
+    RotateCCW,
+    Rotate180,
+    Hold,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateCW,
+        Action::RotateCCW,
+        Action::Rotate180,
+        Action::Hold,
+        Action::Pause,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "left",
+            Action::MoveRight => "right",
+            Action::SoftDrop => "soft-drop",
+            Action::HardDrop => "hard-drop",
+            Action::RotateCW => "rotate-cw",
+            Action::RotateCCW => "rotate-ccw",
+            Action::Rotate180 => "rotate-180",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    /// what the on-screen button for the action shows
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "◀",
+            Action::MoveRight => "▶",
+            Action::SoftDrop => "▼",
+            Action::HardDrop => "⤓",
+            Action::RotateCW => "⟳",
+            Action::RotateCCW => "⟲",
+            Action::Rotate180 => "180",
+            Action::Hold => "hold",
+            Action::Pause => "⏸",
+        }
+    }
+}
+
+/// An Action starting (key down, finger down...) or ending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Press(Action),
+    Release(Action),
+}
+
+impl Input {
+    /// Moves start the auto shift and soft drop lasts until released,
+    /// everything else happens once on press.
+    pub fn apply(self, tetris: &mut Tetris) {
+        match self {
+            Input::Press(Action::MoveLeft) => tetris.start_shift(Direction::Left),
+            Input::Press(Action::MoveRight) => tetris.start_shift(Direction::Right),
+            Input::Press(Action::SoftDrop) => tetris.set_soft_drop(true),
+            Input::Press(Action::HardDrop) => tetris.hard_drop(),
+            Input::Press(Action::RotateCW) => tetris.move_rotate(),
+            Input::Press(Action::RotateCCW) => tetris.move_rotate_ccw(),
+            Input::Press(Action::Rotate180) => tetris.move_rotate_180(),
+            Input::Press(Action::Hold) => {
+                tetris.hold();
+            }
+            Input::Press(Action::Pause) => tetris.toggle_pause(),
+            Input::Release(Action::MoveLeft) => tetris.stop_shift(Direction::Left),
+            Input::Release(Action::MoveRight) => tetris.stop_shift(Direction::Right),
+            Input::Release(Action::SoftDrop) => tetris.set_soft_drop(false),
+            Input::Release(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for action in Action::ALL {
+            assert_eq!(Action::by_name(action.name()), Some(action));
+        }
+        assert_eq!(Action::by_name("jump"), None);
+    }
+
+    #[test]
+    fn press_and_release_drive_the_game() {
+        let mut tetris = Tetris::new(10, 20);
+        let x = |tetris: &Tetris| tetris.get_current_shape().get_pixels().map(|xy| xy.0).min();
+        let start = x(&tetris);
+        Input::Press(Action::MoveLeft).apply(&mut tetris);
+        Input::Release(Action::MoveLeft).apply(&mut tetris);
+        assert_eq!(x(&tetris), start.map(|x| x - 1));
+
+        Input::Press(Action::Hold).apply(&mut tetris);
+        assert!(tetris.get_hold().is_some());
+        Input::Press(Action::HardDrop).apply(&mut tetris);
+        assert!(tetris
+            .get_pixels()
+            .any(|xy| xy.1 == 19 && tetris.get_kind(xy).is_some()));
+    }
+}
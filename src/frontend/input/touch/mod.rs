@@ -0,0 +1,247 @@
+use super::{Action, Input};
+
+/*
+*   Turns raw touch events into Inputs, kept free of web-sys so it can be tested.
+*       - tap                  :rotate
+*       - horizontal drag      :move one cell for every cell_px the finger travels
+*       - swipe down           :soft drop until the finger lifts
+*       - flick down (fast)    :hard drop
+*       - two-finger tap       :hold
+*/
+
+/// how far a finger has to drag to move the shape by one cell (one cell of the canvas renderer)
+pub const DRAG_CELL_PX: f64 = 32.0;
+/// a touch that ends within this time without moving is a tap
+const TAP_MS: f64 = 250.0;
+/// how far down a finger has to go before soft drop starts
+const SWIPE_PX: f64 = 48.0;
+/// a swipe down faster than this (px per ms) is a flick
+const FLICK_SPEED: f64 = 1.0;
+
+// where and when the first finger touched down
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Start {
+    x: f64,
+    y: f64,
+    time: f64,
+}
+
+/// ### TouchGestures
+/// - start(): a finger touched down (touches = fingers on the screen now)
+/// - moved(): the first finger moved
+/// - end(): a finger lifted (touches = fingers still on the screen)
+/// - cancel(): the browser took over the touch
+///
+/// Positions are in css pixels, times in ms (event.timeStamp).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchGestures {
+    cell_px: f64,
+    start: Option<Start>,
+    // x position the last horizontal move was counted at
+    anchor_x: f64,
+    last: (f64, f64, f64),
+    max_touches: usize,
+    moved: bool,
+    soft_dropping: bool,
+}
+
+impl TouchGestures {
+    pub fn new(cell_px: f64) -> Self {
+        Self {
+            cell_px,
+            start: None,
+            anchor_x: 0.0,
+            last: (0.0, 0.0, 0.0),
+            max_touches: 0,
+            moved: false,
+            soft_dropping: false,
+        }
+    }
+
+    pub fn start(&mut self, touches: usize, x: f64, y: f64, time: f64) -> Vec<Input> {
+        if self.start.is_none() {
+            self.start = Some(Start { x, y, time });
+            self.anchor_x = x;
+            self.last = (x, y, time);
+            self.moved = false;
+        }
+        self.max_touches = self.max_touches.max(touches);
+        vec![]
+    }
+
+    pub fn moved(&mut self, x: f64, y: f64, time: f64) -> Vec<Input> {
+        let start = match self.start {
+            Some(start) if self.max_touches == 1 => start,
+            // multi-finger gestures only ever end up as hold:
+            _ => return vec![],
+        };
+        self.last = (x, y, time);
+        let mut inputs = vec![];
+
+        // once soft dropping, the shape stays in its column
+        while !self.soft_dropping && (x - self.anchor_x).abs() >= self.cell_px {
+            let (action, step) = if x > self.anchor_x {
+                (Action::MoveRight, self.cell_px)
+            } else {
+                (Action::MoveLeft, -self.cell_px)
+            };
+            inputs.push(Input::Press(action));
+            inputs.push(Input::Release(action));
+            self.anchor_x += step;
+            self.moved = true;
+        }
+
+        let (dx, dy) = (x - start.x, y - start.y);
+        if !self.soft_dropping && dy >= SWIPE_PX && dy > dx.abs() {
+            inputs.push(Input::Press(Action::SoftDrop));
+            self.soft_dropping = true;
+            self.moved = true;
+        }
+        inputs
+    }
+
+    pub fn end(&mut self, touches: usize, time: f64) -> Vec<Input> {
+        let start = match self.start {
+            // other fingers still down, the gesture is not over yet:
+            Some(_) if touches > 0 => return vec![],
+            Some(start) => start,
+            None => return vec![],
+        };
+        let (x, y, _) = self.last;
+        let (dx, dy) = (x - start.x, y - start.y);
+        let duration = (time - start.time).max(1.0);
+
+        let mut inputs = vec![];
+        if self.soft_dropping {
+            inputs.push(Input::Release(Action::SoftDrop));
+        }
+        if self.max_touches > 1 {
+            if !self.moved && duration <= TAP_MS {
+                inputs.push(Input::Press(Action::Hold));
+            }
+        } else if dy >= SWIPE_PX && dy > dx.abs() && dy / duration >= FLICK_SPEED {
+            inputs.push(Input::Press(Action::HardDrop));
+        } else if !self.moved && duration <= TAP_MS {
+            inputs.push(Input::Press(Action::RotateCW));
+        }
+        self.reset();
+        inputs
+    }
+
+    pub fn cancel(&mut self) -> Vec<Input> {
+        let inputs = if self.soft_dropping {
+            vec![Input::Release(Action::SoftDrop)]
+        } else {
+            vec![]
+        };
+        self.reset();
+        inputs
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.cell_px);
+    }
+}
+
+impl Default for TouchGestures {
+    fn default() -> Self {
+        Self::new(DRAG_CELL_PX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presses(inputs: &[Input]) -> Vec<Action> {
+        inputs
+            .iter()
+            .filter_map(|input| match input {
+                Input::Press(action) => Some(*action),
+                Input::Release(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tap_rotates() {
+        let mut touch = TouchGestures::default();
+        touch.start(1, 100.0, 100.0, 0.0);
+        touch.moved(103.0, 102.0, 50.0);
+        assert_eq!(touch.end(0, 100.0), vec![Input::Press(Action::RotateCW)]);
+
+        // holding the finger down too long is no tap:
+        touch.start(1, 100.0, 100.0, 0.0);
+        assert_eq!(touch.end(0, 1000.0), vec![]);
+    }
+
+    #[test]
+    fn drag_moves_by_cell_distance() {
+        let mut touch = TouchGestures::new(30.0);
+        touch.start(1, 100.0, 100.0, 0.0);
+        assert_eq!(touch.moved(120.0, 100.0, 10.0), vec![]);
+        assert_eq!(
+            touch.moved(165.0, 100.0, 20.0),
+            vec![
+                Input::Press(Action::MoveRight),
+                Input::Release(Action::MoveRight),
+                Input::Press(Action::MoveRight),
+                Input::Release(Action::MoveRight),
+            ]
+        );
+        // going back counts from where the last move happened (160px):
+        assert_eq!(
+            presses(&touch.moved(125.0, 100.0, 30.0)),
+            vec![Action::MoveLeft]
+        );
+        // a drag is no tap:
+        assert_eq!(touch.end(0, 40.0), vec![]);
+    }
+
+    #[test]
+    fn swipe_down_soft_drops_until_lifted() {
+        let mut touch = TouchGestures::default();
+        touch.start(1, 100.0, 100.0, 0.0);
+        assert_eq!(touch.moved(100.0, 140.0, 200.0), vec![]);
+        assert_eq!(
+            touch.moved(105.0, 200.0, 400.0),
+            vec![Input::Press(Action::SoftDrop)]
+        );
+        assert_eq!(touch.moved(105.0, 300.0, 600.0), vec![]);
+        assert_eq!(touch.end(0, 900.0), vec![Input::Release(Action::SoftDrop)]);
+    }
+
+    #[test]
+    fn flick_down_hard_drops() {
+        let mut touch = TouchGestures::default();
+        touch.start(1, 100.0, 100.0, 0.0);
+        touch.moved(100.0, 250.0, 60.0);
+        assert_eq!(
+            touch.end(0, 80.0),
+            vec![
+                Input::Release(Action::SoftDrop),
+                Input::Press(Action::HardDrop)
+            ]
+        );
+    }
+
+    #[test]
+    fn two_finger_tap_holds() {
+        let mut touch = TouchGestures::default();
+        touch.start(1, 100.0, 100.0, 0.0);
+        touch.start(2, 200.0, 100.0, 20.0);
+        // the second finger moving around does nothing:
+        assert_eq!(touch.moved(300.0, 300.0, 40.0), vec![]);
+        assert_eq!(touch.end(1, 80.0), vec![]);
+        assert_eq!(touch.end(0, 100.0), vec![Input::Press(Action::Hold)]);
+    }
+
+    #[test]
+    fn cancel_releases_soft_drop() {
+        let mut touch = TouchGestures::default();
+        touch.start(1, 100.0, 100.0, 0.0);
+        touch.moved(100.0, 200.0, 300.0);
+        assert_eq!(touch.cancel(), vec![Input::Release(Action::SoftDrop)]);
+        assert_eq!(touch, TouchGestures::default());
+    }
+}
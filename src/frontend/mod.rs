@@ -0,0 +1,14 @@
+pub mod app;
+pub mod editor;
+pub mod game_loop;
+pub mod input;
+pub mod online;
+pub mod render;
+pub mod settings;
+pub mod skin;
+pub mod versus;
+
+/*
+*   The react frontend: the App component and its helpers.
+*   Only input::Input::apply() changes the game state, the game logic lives in tetris_game.
+*/
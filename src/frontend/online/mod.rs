@@ -0,0 +1,353 @@
+use wasm_bindgen::{prelude::Closure, JsCast};
+use wasm_react::{
+    c, h,
+    hooks::{use_callback, use_effect, use_js_ref, use_ref, use_state, Deps},
+    props::Style,
+    Component, VNode,
+};
+use web_sys::{
+    CloseEvent, Element, Event, HtmlElement, KeyboardEvent, MessageEvent, MouseEvent, WebSocket,
+};
+
+use super::{
+    game_loop::AnimationLoop,
+    input::{keymap::Keymap, Action, Input},
+    render::{CanvasRenderer, DomRenderer, Renderer, RendererKind},
+    settings::use_keep_focus,
+    skin::Skin,
+};
+use crate::online::{
+    protocol::{ClientMessage, ServerMessage},
+    session::{Session, Status},
+};
+use crate::tetris_game::{position::Position, shapes::XY, snapshot::Snapshot, Settings};
+
+/*
+*   Online versus against a player somewhere else, through the relay server (src/bin/server).
+*       - connects to the "server" prop of App with a WebSocket, says hello and looks for a match right away
+*       - "find a match" joins the next free opponent, a room name pairs up with whoever types the same name
+*       - the own board plays with the keys of the App (no pausing online), next to it the garbage meter
+*         and the board of the opponent after its last placement
+*   The state of the match is an online::session::Session, this component only moves messages in and out of it.
+*/
+
+pub struct OnlineView {
+    /// ws:// url of the server
+    pub server: String,
+    /// the name the opponent sees
+    pub name: String,
+    /// (the board size is up to the server)
+    pub settings: Settings,
+    pub keymap: Keymap,
+    pub skin: Skin,
+    pub renderer: RendererKind,
+}
+
+// sends a message if the socket is (still) there
+fn send(socket: &Option<WebSocket>, message: &ClientMessage) {
+    if let Some(socket) = socket {
+        let _ = socket.send_with_str(&message.to_json());
+    }
+}
+
+// the value of the input an event came from
+fn input_value(ev: &Event) -> String {
+    ev.target()
+        .and_then(|target| js_sys::Reflect::get(&target, &"value".into()).ok())
+        .and_then(|value| value.as_string())
+        .unwrap_or_default()
+}
+
+impl OnlineView {
+    // the board of the opponent, small and without pieces in the air
+    fn opponent_view(&self, position: &Position) -> VNode {
+        let (width, height) = position.size();
+        h!(div)
+            .style(
+                &Style::new()
+                    .display("inline-grid")
+                    .grid_template(format!(
+                        "repeat({}, 0.6rem) / repeat({}, 0.6rem)",
+                        height, width
+                    ))
+                    .border("1px solid lightcyan"),
+            )
+            .build(c![..(0..height)
+                .flat_map(|y| (0..width).map(move |x| XY(x, y)))
+                .map(|xy| {
+                    let color = position
+                        .get(xy)
+                        .map(|kind| self.skin.color(kind))
+                        .unwrap_or("transparent");
+                    h!(div)
+                        .key(Some(format!("{} {}", xy.0, xy.1)))
+                        .style(&Style::new().background_color(color))
+                        .build(c![])
+                })])
+    }
+}
+
+impl Component for OnlineView {
+    fn render(&self) -> VNode {
+        let session = use_state(|| Session::new(&self.name, self.settings.clone()));
+        let socket = use_ref(None::<WebSocket>);
+        let closed = use_state(|| false);
+        let room = use_state(String::new);
+
+        // the keys only reach us while we have the focus:
+        let container = use_js_ref::<Element>(None);
+        use_effect(
+            {
+                let container = container.clone();
+                move || {
+                    container
+                        .current()
+                        .and_then(|el| el.dyn_into::<HtmlElement>().ok())
+                        .map(|el| el.focus().ok());
+                    || ()
+                }
+            },
+            Deps::none(),
+        );
+
+        // the connection lives as long as the component, everything the server sends goes into the session:
+        use_effect(
+            {
+                let mut session = session.clone();
+                let mut socket = socket.clone();
+                let mut closed = closed.clone();
+                let url = self.server.clone();
+                let hello = ClientMessage::hello(&self.name);
+                move || {
+                    let ws = WebSocket::new(&url).ok();
+                    let handlers = ws.as_ref().map(|ws| {
+                        let on_open = Closure::<dyn FnMut()>::new({
+                            let ws = Some(ws.clone());
+                            move || {
+                                send(&ws, &hello);
+                                send(&ws, &ClientMessage::Join { room: None });
+                            }
+                        });
+                        let on_message =
+                            Closure::<dyn FnMut(MessageEvent)>::new(move |ev: MessageEvent| {
+                                let message = ev
+                                    .data()
+                                    .as_string()
+                                    .and_then(|text| ServerMessage::from_json(&text).ok());
+                                if let Some(message) = message {
+                                    session.set(|mut session| {
+                                        session.receive(message);
+                                        session
+                                    });
+                                }
+                            });
+                        let on_close = Closure::<dyn FnMut(CloseEvent)>::new({
+                            let mut closed = closed.clone();
+                            move |_: CloseEvent| closed.set(|_| true)
+                        });
+                        ws.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+                        ws.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+                        ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+                        (on_open, on_message, on_close)
+                    });
+                    if ws.is_none() {
+                        closed.set(|_| true);
+                    }
+                    *socket.current_mut() = ws.clone();
+                    move || {
+                        if let Some(ws) = ws {
+                            ws.set_onopen(None);
+                            ws.set_onmessage(None);
+                            ws.set_onclose(None);
+                            let _ = ws.close();
+                        }
+                        drop(handlers);
+                    }
+                }
+            },
+            Deps::none(),
+        );
+
+        // the own board runs on the animation frames, what happened on it goes to the server:
+        use_effect(
+            {
+                let mut session = session.clone();
+                let socket = socket.clone();
+                move || {
+                    let game_loop = AnimationLoop::start(move |elapsed| {
+                        session.set(|mut session| {
+                            session.update(elapsed);
+                            for message in session.outgoing() {
+                                send(&socket.current(), &message);
+                            }
+                            session
+                        })
+                    });
+                    move || drop(game_loop)
+                }
+            },
+            Deps::none(),
+        );
+
+        let handle_key = use_callback(
+            {
+                let mut session = session.clone();
+                let keymap = self.keymap.clone();
+                move |ev: KeyboardEvent| {
+                    let Some(action) = keymap.action(&ev.code()) else {
+                        return;
+                    };
+                    ev.prevent_default();
+                    // no pausing against somebody else:
+                    if ev.repeat() || action == Action::Pause {
+                        return;
+                    }
+                    let input = if ev.type_() == "keydown" {
+                        Input::Press(action)
+                    } else {
+                        Input::Release(action)
+                    };
+                    session.set(|mut session| {
+                        if let Some(tetris) = session.board_mut() {
+                            input.apply(tetris);
+                        }
+                        session
+                    });
+                }
+            },
+            Deps::none(),
+        );
+        let handle_room = use_callback(
+            {
+                let mut room = room.clone();
+                move |ev: Event| {
+                    let name = input_value(&ev);
+                    room.set(|_| name);
+                }
+            },
+            Deps::none(),
+        );
+        let handle_join = use_callback(
+            {
+                let socket = socket.clone();
+                let room = room.clone();
+                move |_: MouseEvent| {
+                    let name = room.value().trim().to_string();
+                    let room = (!name.is_empty()).then_some(name);
+                    send(&socket.current(), &ClientMessage::Join { room });
+                }
+            },
+            Deps::none(),
+        );
+        let keep_focus = use_keep_focus();
+
+        let canvas = use_js_ref::<Element>(None);
+        let last_frame = use_ref(None::<Snapshot>);
+        let renderer_kind = use_state(|| self.renderer);
+        let renderer: Box<dyn Renderer> = match *renderer_kind.value() {
+            RendererKind::Canvas => Box::new(CanvasRenderer::new(
+                self.skin.clone(),
+                canvas.clone(),
+                last_frame.clone(),
+            )),
+            RendererKind::Dom => Box::new(DomRenderer::new(self.skin.clone())),
+        };
+        let frame = session.value().board().map(|tetris| tetris.snapshot());
+        let board = frame.as_ref().map(|frame| renderer.view(frame));
+
+        use_effect(
+            {
+                let mut renderer_kind = renderer_kind.clone();
+                move || {
+                    if let Some(frame) = &frame {
+                        if renderer.draw(frame).is_err() {
+                            renderer_kind.set(|_| RendererKind::Dom);
+                        }
+                    }
+                    || ()
+                }
+            },
+            Deps::all(),
+        );
+
+        let session = session.value();
+        let incoming = session.incoming();
+        let height = session.board().map_or(1, |tetris| tetris.get_size().1);
+        let filled = (incoming * 100 / height.max(1) as u32).min(100);
+        let mut status = match session.status() {
+            _ if *closed.value() => format!("disconnected from {}", self.server),
+            Status::Connecting => format!("connecting to {}", self.server),
+            Status::Lobby => "connected".to_string(),
+            Status::Waiting { room } => format!("waiting for an opponent in {}", room),
+            Status::Playing { opponent, .. } => format!("playing against {}", opponent),
+            Status::Over { won: true } => "you won!".to_string(),
+            Status::Over { won: false } => "you lost".to_string(),
+        };
+        if let Some(error) = session.error() {
+            status = format!("{} ({})", status, error);
+        }
+        let can_join =
+            !*closed.value() && matches!(session.status(), Status::Lobby | Status::Over { .. });
+
+        h!(div)
+            .ref_container(&container)
+            .tabindex(0)
+            .on_keydown(&handle_key)
+            .on_keyup(&handle_key)
+            .style(&Style::new().outline("none"))
+            .build(c![
+                h!(div)
+                    .style(&Style::new().display("flex").align_items("flex-end"))
+                    .build(c![
+                        h!(div)
+                            .style(
+                                &Style::new()
+                                    .display("flex")
+                                    .flex_direction("column")
+                                    .justify_content("flex-end")
+                                    .width("0.6rem")
+                                    .height("20rem")
+                                    .border("1px solid lightcyan"),
+                            )
+                            .build(c![h!(div)
+                                .style(
+                                    &Style::new()
+                                        .height(format!("{}%", filled))
+                                        .background_color("crimson"),
+                                )
+                                .build(c![])]),
+                        board,
+                        h!(div)
+                            .style(&Style::new().margin_left("1.5rem"))
+                            .build(c![session
+                                .opponent()
+                                .map(|position| self.opponent_view(position))]),
+                    ]),
+                h!(div)
+                    .style(
+                        &Style::new()
+                            .margin_top("0.5rem")
+                            .margin_left("1.5rem")
+                            .color("lightcyan"),
+                    )
+                    .build(c![
+                        status,
+                        can_join.then(|| {
+                            h!(span).build(c![
+                                h!(input)
+                                    .value(room.value().clone())
+                                    .on_change(&handle_room)
+                                    .attr("placeholder", &"room (optional)".into())
+                                    .style(&Style::new().margin_left("0.5rem").width("8em"))
+                                    .build(c![]),
+                                h!(button)
+                                    .on_mousedown(&keep_focus)
+                                    .on_click(&handle_join)
+                                    .style(&Style::new().margin_left("0.5rem"))
+                                    .build(c!["find a match"]),
+                            ])
+                        }),
+                    ]),
+            ])
+    }
+}
@@ -0,0 +1,221 @@
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_react::{
+    c, h,
+    hooks::{JsRefContainer, RefContainer},
+    props::Style,
+    VNode,
+};
+use web_sys::{CanvasRenderingContext2d, Element, HtmlCanvasElement};
+
+use super::{level_and_lines, preview_boxes, score_text, Renderer};
+use crate::frontend::skin::Skin;
+use crate::tetris_game::{
+    shapes::{PieceKind, XY},
+    snapshot::{Snapshot, Status},
+};
+
+/// size of one cell in canvas pixels
+const CELL: f64 = 32.0;
+const BACKGROUND: &str = "black";
+const BORDER: &str = "grey";
+const TEXT: &str = "lightcyan";
+
+/// ### CanvasRenderer
+/// Paints the whole game onto a single <canvas>:
+/// - the board on the left, with the ghost of the current shape and flashing rows while they get cleared
+/// - the preview column to the right of it, with the hold box (dashed) at the bottom
+/// - the score (HUD) below the previews
+///
+/// The last drawn frame is kept, so nothing gets repainted if the game state did not change.
+pub struct CanvasRenderer {
+    skin: Skin,
+    canvas: JsRefContainer<Element>,
+    last_frame: RefContainer<Option<Snapshot>>,
+}
+
+impl CanvasRenderer {
+    /// canvas and last_frame have to outlive a single render (use_js_ref / use_ref in the component)
+    pub fn new(
+        skin: Skin,
+        canvas: JsRefContainer<Element>,
+        last_frame: RefContainer<Option<Snapshot>>,
+    ) -> Self {
+        Self {
+            skin,
+            canvas,
+            last_frame,
+        }
+    }
+
+    // size of the canvas in cells (board | gap | previews)
+    fn size_in_cells(frame: &Snapshot) -> (i32, i32) {
+        let (preview_width, preview_height) = frame.preview_size;
+        // the previews and two lines of HUD below them:
+        let previews_height = (frame.queue.len() as i32 + 1) * (preview_height + 1) + 4;
+        (
+            frame.width + 1 + preview_width + 1,
+            frame.height.max(previews_height) + 1,
+        )
+    }
+
+    fn context(&self) -> Result<Option<CanvasRenderingContext2d>, JsValue> {
+        let canvas = match self
+            .canvas
+            .current()
+            .and_then(|el| el.dyn_into::<HtmlCanvasElement>().ok())
+        {
+            Some(canvas) => canvas,
+            // not mounted (yet), nothing to draw on:
+            None => return Ok(None),
+        };
+        let context = canvas
+            .get_context("2d")?
+            .ok_or_else(|| JsValue::from("canvas has no 2d context"))?
+            .dyn_into::<CanvasRenderingContext2d>()
+            .map_err(JsValue::from)?;
+        Ok(Some(context))
+    }
+
+    fn fill_cell(
+        &self,
+        ctx: &CanvasRenderingContext2d,
+        offset: (f64, f64),
+        xy: XY,
+        kind: PieceKind,
+    ) {
+        ctx.set_fill_style(&JsValue::from_str(self.skin.color(kind)));
+        ctx.fill_rect(
+            offset.0 + xy.0 as f64 * CELL + 1.0,
+            offset.1 + xy.1 as f64 * CELL + 1.0,
+            CELL - 2.0,
+            CELL - 2.0,
+        );
+    }
+
+    fn paint(&self, ctx: &CanvasRenderingContext2d, frame: &Snapshot) -> Result<(), JsValue> {
+        let (width, height) = Self::size_in_cells(frame);
+        ctx.set_fill_style(&JsValue::from_str(BACKGROUND));
+        ctx.fill_rect(0.0, 0.0, width as f64 * CELL, height as f64 * CELL);
+        ctx.set_stroke_style(&JsValue::from_str(BORDER));
+        ctx.set_line_width(3.0);
+
+        // the board:
+        let board = (CELL / 2.0, CELL / 2.0);
+        ctx.stroke_rect(
+            board.0,
+            board.1,
+            frame.width as f64 * CELL,
+            frame.height as f64 * CELL,
+        );
+        ctx.set_global_alpha(0.3);
+        for xy in &frame.ghost {
+            self.fill_cell(ctx, board, *xy, frame.active_kind);
+        }
+        ctx.set_global_alpha(1.0);
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                if let Some(kind) = frame.cell(XY(x, y)) {
+                    self.fill_cell(ctx, board, XY(x, y), kind);
+                }
+            }
+        }
+        // rows about to collapse flash up and fade out over the line clear delay:
+        ctx.set_fill_style(&JsValue::from_str(TEXT));
+        ctx.set_global_alpha(1.0 - frame.phase.progress());
+        for y in frame.clearing_rows() {
+            ctx.fill_rect(
+                board.0,
+                board.1 + *y as f64 * CELL,
+                frame.width as f64 * CELL,
+                CELL,
+            );
+        }
+        ctx.set_global_alpha(1.0);
+
+        // the preview column, the last box holds the hold piece:
+        let (preview_width, preview_height) = frame.preview_size;
+        for (n, preview) in preview_boxes(frame).iter().enumerate() {
+            let offset = (
+                board.0 + (frame.width + 1) as f64 * CELL,
+                board.1 + (n as i32 * (preview_height + 1)) as f64 * CELL,
+            );
+            let dashes = if n == frame.queue.len() {
+                js_sys::Array::of2(&6.0.into(), &6.0.into())
+            } else {
+                js_sys::Array::new()
+            };
+            ctx.set_line_dash(&dashes)?;
+            ctx.stroke_rect(
+                offset.0,
+                offset.1,
+                preview_width as f64 * CELL,
+                preview_height as f64 * CELL,
+            );
+            for (i, kind) in preview.iter().enumerate() {
+                if let Some(kind) = kind {
+                    let xy = XY(i as i32 % preview_width, i as i32 / preview_width);
+                    self.fill_cell(ctx, offset, xy, *kind);
+                }
+            }
+        }
+
+        ctx.set_line_dash(&js_sys::Array::new())?;
+
+        // the HUD:
+        ctx.set_fill_style(&JsValue::from_str(TEXT));
+        ctx.set_font(&format!("{}px sans-serif", CELL * 0.6));
+        let hud_y =
+            board.1 + ((frame.queue.len() as i32 + 1) * (preview_height + 1) + 1) as f64 * CELL;
+        let hud_x = board.0 + (frame.width + 1) as f64 * CELL;
+        ctx.fill_text(&score_text(frame), hud_x, hud_y)?;
+        ctx.fill_text(&level_and_lines(frame), hud_x, hud_y + CELL)?;
+        let message = match frame.status {
+            Status::GameOver => Some("GAME OVER"),
+            Status::Paused => Some("PAUSED"),
+            Status::Playing => None,
+        };
+        if let Some(message) = message {
+            ctx.set_font(&format!("bold {}px sans-serif", CELL));
+            ctx.set_text_align("center");
+            ctx.fill_text(
+                message,
+                board.0 + frame.width as f64 * CELL / 2.0,
+                board.1 + frame.height as f64 * CELL / 2.0,
+            )?;
+            ctx.set_text_align("start");
+        }
+        Ok(())
+    }
+}
+
+impl Renderer for CanvasRenderer {
+    fn view(&self, frame: &Snapshot) -> VNode {
+        let (width, height) = Self::size_in_cells(frame);
+        h!(canvas)
+            .ref_container(&self.canvas)
+            .attr("width", &JsValue::from_f64(width as f64 * CELL))
+            .attr("height", &JsValue::from_f64(height as f64 * CELL))
+            .style(
+                &Style::new()
+                    .display("block")
+                    .margin_top("1.5rem")
+                    .margin_left("1.5rem"),
+            )
+            .build(c![])
+    }
+
+    fn draw(&self, frame: &Snapshot) -> Result<(), JsValue> {
+        if self.last_frame.current().as_ref() == Some(frame) {
+            // nothing changed since the last draw:
+            return Ok(());
+        }
+        let ctx = match self.context()? {
+            Some(ctx) => ctx,
+            None => return Ok(()),
+        };
+        self.paint(&ctx, frame)?;
+        let mut last_frame = self.last_frame.clone();
+        last_frame.set_current(Some(frame.clone()));
+        Ok(())
+    }
+}
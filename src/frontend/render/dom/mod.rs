@@ -0,0 +1,135 @@
+use wasm_bindgen::JsValue;
+use wasm_react::{c, h, props::Style, VNode};
+
+use super::{level_and_lines, preview_boxes, score_text, Renderer};
+use crate::frontend::skin::Skin;
+use crate::tetris_game::{
+    shapes::{PieceKind, XY},
+    snapshot::{Snapshot, Status},
+};
+
+/// ### DomRenderer
+/// The original renderer: one styled div per cell, filled with the glyph of the skin.
+/// Simple and works everywhere, but react has to diff every cell on every tick.
+pub struct DomRenderer {
+    skin: Skin,
+}
+
+impl DomRenderer {
+    pub fn new(skin: Skin) -> Self {
+        Self { skin }
+    }
+
+    // one 'pixel' of a grid, the ghost gets drawn see-through
+    fn cell(&self, kind: Option<PieceKind>, ghost: Option<PieceKind>) -> VNode {
+        let style = Style::new().text_indent("-.1em").margin_top("-.1em");
+        match (kind, ghost) {
+            (Some(kind), _) => h!(div).style(&style).build(c![self.skin.glyph(kind)]),
+            (None, Some(ghost)) => h!(div)
+                .style(&style.opacity(0.3))
+                .build(c![self.skin.glyph(ghost)]),
+            (None, None) => h!(div).style(&style).build(c![]),
+        }
+    }
+
+    // a cell of a row that is about to collapse, it fades out over the line clear delay
+    fn clearing_cell(&self, kind: Option<PieceKind>, progress: f64) -> VNode {
+        h!(div)
+            .style(
+                &Style::new()
+                    .text_indent("-.1em")
+                    .margin_top("-.1em")
+                    .background_color("lightcyan")
+                    .opacity(1.0 - progress),
+            )
+            .build(c![kind.map(|kind| self.skin.glyph(kind))])
+    }
+
+    // a box the size of one preview, used for the upcoming shapes and the hold box
+    fn preview_box(&self, frame: &Snapshot, cells: &[Option<PieceKind>], border: &str) -> VNode {
+        let (preview_width, preview_height) = frame.preview_size;
+        h!(div)
+            .style(
+                &Style::new()
+                    .display("inline-grid")
+                    .grid_template(format!(
+                        "repeat({}, 1em) / repeat({}, 1em)",
+                        preview_height, preview_width
+                    ))
+                    .outline("none")
+                    .border(border)
+                    .margin_left("0.5rem")
+                    .margin_bottom("0.5rem"),
+            )
+            .build(c![..cells.iter().map(|kind| self.cell(*kind, None))])
+    }
+}
+
+impl Renderer for DomRenderer {
+    fn view(&self, frame: &Snapshot) -> VNode {
+        h!(div)
+            .style(&Style::new().display("inline-block"))
+            .build(c![
+                // the div that holds the game-canvas-pixels:
+                h!(div)
+                    .style(
+                        &Style::new()
+                            .display("inline-grid")
+                            .grid_template(format!(
+                                "repeat({}, 1em) / repeat({}, 1em)",
+                                frame.height, frame.width
+                            ))
+                            .outline("none")
+                            .border("3px solid grey")
+                            .margin_top("2rem")
+                            .margin_left("2rem")
+                    )
+                    // divs making up the canvas-pixels:
+                    .build(c![..(0..frame.height)
+                        .flat_map(|y| (0..frame.width).map(move |x| XY(x, y)))
+                        .map(|xy| {
+                            if frame.clearing_rows().contains(&xy.1) {
+                                return self.clearing_cell(frame.cell(xy), frame.phase.progress());
+                            }
+                            let ghost = frame.ghost.contains(&xy).then_some(frame.active_kind);
+                            self.cell(frame.cell(xy), ghost)
+                        })]),
+                // the preview-column for the upcoming blocks:
+                h!(div)
+                    .style(
+                        &Style::new()
+                            .display("inline-flex")
+                            .flex_direction("column")
+                            .vertical_align("top")
+                            .margin_top("2rem")
+                    )
+                    .build(c![
+                        // the hold box is the last one, below the previews:
+                        ..preview_boxes(frame).iter().enumerate().map(|(n, cells)| {
+                            let border = if n == frame.queue.len() {
+                                "3px dashed grey"
+                            } else {
+                                "3px solid grey"
+                            };
+                            self.preview_box(frame, cells, border)
+                        })
+                    ]),
+                // score:
+                h!(div)
+                    .style(&Style::new().margin_left("2.1rem").color("lightcyan"))
+                    .build(c![
+                        score_text(frame),
+                        (frame.status == Status::GameOver).then_some(" - game over"),
+                        (frame.status == Status::Paused).then_some(" - paused"),
+                    ]),
+                h!(div)
+                    .style(&Style::new().margin_left("2.1rem").color("lightcyan"))
+                    .build(c![level_and_lines(frame)]),
+            ])
+    }
+
+    // react already put everything on screen in view()
+    fn draw(&self, _frame: &Snapshot) -> Result<(), JsValue> {
+        Ok(())
+    }
+}
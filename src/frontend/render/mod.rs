@@ -0,0 +1,69 @@
+pub mod canvas;
+pub mod dom;
+
+use wasm_bindgen::JsValue;
+use wasm_react::VNode;
+
+use crate::tetris_game::{shapes::PieceKind, snapshot::Snapshot};
+
+pub use self::canvas::CanvasRenderer;
+pub use self::dom::DomRenderer;
+
+/*
+*   Renderers turn the game state into something visible.
+*   App picks one (canvas by default, the div-per-cell dom renderer as fallback)
+*   and only talks to it through the Renderer trait.
+*   Renderers draw a tetris_game::snapshot::Snapshot (one per render, called frame here),
+*   the ones that paint themselves compare it with the last drawn one to skip redraws.
+*/
+
+/// the score line of the HUD
+pub fn score_text(frame: &Snapshot) -> String {
+    format!("score: {}", frame.score)
+}
+
+/// the level and lines line of the HUD
+pub fn level_and_lines(frame: &Snapshot) -> String {
+    format!("level: {} lines: {}", frame.level, frame.lines)
+}
+
+/// the cells of every preview box (row by row), the hold box last
+pub fn preview_boxes(frame: &Snapshot) -> Vec<Vec<Option<PieceKind>>> {
+    let size = frame.preview_size;
+    let hold = match &frame.hold {
+        Some(hold) => hold.grid(size),
+        None => vec![None; (size.0 * size.1) as usize],
+    };
+    frame
+        .queue
+        .iter()
+        .map(|preview| preview.grid(size))
+        .chain([hold])
+        .collect()
+}
+
+/// ### Renderer
+/// - view(): the elements the renderer lives in, mounted by the App on every render
+/// - draw(): gets called after the elements are mounted, for renderers that paint on their own.
+///   An Err means the renderer can not work in this browser, App then falls back to the dom renderer.
+pub trait Renderer {
+    fn view(&self, frame: &Snapshot) -> VNode;
+    fn draw(&self, frame: &Snapshot) -> Result<(), JsValue>;
+}
+
+/// which renderer the App uses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RendererKind {
+    Canvas,
+    Dom,
+}
+
+impl RendererKind {
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "canvas" => Some(RendererKind::Canvas),
+            "dom" => Some(RendererKind::Dom),
+            _ => None,
+        }
+    }
+}
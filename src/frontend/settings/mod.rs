@@ -0,0 +1,156 @@
+use wasm_bindgen::JsCast;
+use wasm_react::{
+    c,
+    callback::PersistedCallback,
+    h,
+    hooks::{use_callback, Deps, State},
+    props::Style,
+    Component, VNode,
+};
+use web_sys::{Element, MouseEvent};
+
+use super::input::{
+    keymap::{Keymap, Preset},
+    Action,
+};
+
+/*
+*   The settings panel for rebinding keys.
+*   App owns the state, so its key handler can send the next pressed key here (see App::render):
+*       - clicking 'rebind' next to an action starts listening for a key
+*       - the next key pressed becomes the only key of that action, unless another action already uses it
+*       - the presets replace the whole keymap
+*   Every change gets saved in local storage.
+*/
+
+/// key the keymap gets saved under in local storage
+const STORAGE_KEY: &str = "rs_tetris.keymap";
+
+/// the keymap saved in local storage (if there is one and it is still valid)
+pub fn load_keymap() -> Option<Keymap> {
+    let storage = web_sys::window()?.local_storage().ok()??;
+    let json = storage.get_item(STORAGE_KEY).ok()??;
+    Keymap::from_json(&json).ok()
+}
+
+/// saves the keymap in local storage (silently does nothing if the browser does not allow it)
+pub fn save_keymap(keymap: &Keymap) {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.set_item(STORAGE_KEY, &keymap.to_json());
+    }
+}
+
+/// Buttons normally take the focus away from the game when clicked (and with it the key events).
+/// Preventing the default of mousedown keeps the focus where it is, the click still happens.
+pub fn use_keep_focus() -> PersistedCallback<MouseEvent> {
+    use_callback(|ev: MouseEvent| ev.prevent_default(), Deps::none())
+}
+
+/// Binds key to the action the panel is listening for. Returns what the panel should show.
+pub fn rebind(keymap: &mut State<Keymap>, action: Action, key: &str) -> String {
+    let mut changed = keymap.value().clone();
+    match changed.rebind(action, key) {
+        Ok(()) => {
+            save_keymap(&changed);
+            keymap.set(|_| changed);
+            format!("{} is now bound to {}", action.name(), key)
+        }
+        Err(err) => err.to_string(),
+    }
+}
+
+pub struct KeymapSettings {
+    pub keymap: State<Keymap>,
+    /// the action waiting for its new key
+    pub listening: State<Option<Action>>,
+    /// result of the last change (or conflict)
+    pub message: State<Option<String>>,
+}
+
+// the attribute a clicked element carries its action / preset in
+fn data(ev: &MouseEvent, name: &str) -> Option<String> {
+    ev.current_target()
+        .and_then(|target| target.dyn_into::<Element>().ok())
+        .and_then(|el| el.get_attribute(name))
+}
+
+impl Component for KeymapSettings {
+    fn render(&self) -> VNode {
+        let handle_rebind = use_callback(
+            {
+                let mut listening = self.listening.clone();
+                let mut message = self.message.clone();
+                move |ev: MouseEvent| {
+                    let action = data(&ev, "data-action").and_then(|name| Action::by_name(&name));
+                    listening.set(|_| action);
+                    message
+                        .set(|_| action.map(|action| format!("press a key for {}", action.name())));
+                }
+            },
+            Deps::none(),
+        );
+        let handle_preset = use_callback(
+            {
+                let mut keymap = self.keymap.clone();
+                let mut message = self.message.clone();
+                move |ev: MouseEvent| {
+                    if let Some(preset) =
+                        data(&ev, "data-preset").and_then(|name| Preset::by_name(&name))
+                    {
+                        let preset_map = Keymap::preset(preset);
+                        save_keymap(&preset_map);
+                        keymap.set(|_| preset_map);
+                        message.set(|_| Some(format!("loaded the {} keys", preset.name())));
+                    }
+                }
+            },
+            Deps::none(),
+        );
+
+        let keep_focus = use_keep_focus();
+
+        let keymap = self.keymap.value();
+        let listening = *self.listening.value();
+        let cell = Style::new().padding("0 0.5rem");
+
+        h!(div)
+            .style(
+                &Style::new()
+                    .margin_top("0.5rem")
+                    .margin_left("1.5rem")
+                    .color("lightcyan")
+                    .font_size("1rem"),
+            )
+            .build(c![
+                h!(div).build(c![
+                    "presets: ",
+                    ..Preset::ALL.into_iter().map(|preset| {
+                        h!(button)
+                            .key(Some(preset.name()))
+                            .on_mousedown(&keep_focus)
+                            .attr("data-preset", &preset.name().into())
+                            .on_click(&handle_preset)
+                            .build(c![preset.name()])
+                    })
+                ]),
+                h!(table).build(c![h!(tbody).build(c![..Action::ALL.into_iter().map(
+                    |action| {
+                        let keys = keymap.keys(action).collect::<Vec<_>>().join(", ");
+                        h!(tr).key(Some(action.name())).build(c![
+                            h!(td).style(&cell).build(c![action.name()]),
+                            h!(td).style(&cell).build(c![keys]),
+                            h!(td).style(&cell).build(c![h!(button)
+                                .attr("data-action", &action.name().into())
+                                .on_click(&handle_rebind)
+                                .build(c![if listening == Some(action) {
+                                    "press a key…"
+                                } else {
+                                    "rebind"
+                                }])]),
+                        ])
+                    }
+                )])]),
+                h!(div).build(c![self.message.value().clone()]),
+            ])
+    }
+}
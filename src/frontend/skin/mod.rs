@@ -0,0 +1,63 @@
+use crate::tetris_game::shapes::PieceKind;
+
+/// ### Skin
+/// Maps a PieceKind to how it gets drawn.
+/// - glyph: the text that fills one cell (for the div-per-cell renderer)
+/// - color: a css color for renderers that paint the cells themselves
+///
+/// Both are indexed with `kind as usize` (same order as PieceKind::ALL)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skin {
+    pub name: &'static str,
+    pub glyphs: [&'static str; 8],
+    pub colors: [&'static str; 8],
+}
+
+/// the classic guideline colors, shared by the built in skins
+const GUIDELINE_COLORS: [&str; 8] = [
+    "#00f0f0", // I
+    "#0000f0", // J
+    "#f0a000", // L
+    "#f0f000", // O
+    "#00f000", // S
+    "#a000f0", // T
+    "#f00000", // Z
+    "#808080", // Garbage
+];
+
+impl Skin {
+    /// the original look, one emoji-square per cell
+    pub const EMOJI: Skin = Skin {
+        name: "emoji",
+        glyphs: ["🟦", "🟫", "🟧", "🟨", "🟩", "🟪", "🟥", "⬜"],
+        colors: GUIDELINE_COLORS,
+    };
+
+    /// plain letters, readable even without emoji-fonts
+    pub const LETTERS: Skin = Skin {
+        name: "letters",
+        glyphs: ["I", "J", "L", "O", "S", "T", "Z", "#"],
+        colors: GUIDELINE_COLORS,
+    };
+
+    pub const ALL: [Skin; 2] = [Skin::EMOJI, Skin::LETTERS];
+
+    /// finds a built in skin by its name
+    pub fn by_name(name: &str) -> Option<Skin> {
+        Skin::ALL.into_iter().find(|skin| skin.name == name)
+    }
+
+    pub fn glyph(&self, kind: PieceKind) -> &'static str {
+        self.glyphs[kind as usize]
+    }
+
+    pub fn color(&self, kind: PieceKind) -> &'static str {
+        self.colors[kind as usize]
+    }
+}
+
+impl Default for Skin {
+    fn default() -> Self {
+        Skin::EMOJI
+    }
+}
@@ -0,0 +1,268 @@
+use wasm_bindgen::JsCast;
+use wasm_react::{
+    c, h,
+    hooks::{use_callback, use_effect, use_js_ref, use_ref, use_state, Deps},
+    props::Style,
+    Component, VNode,
+};
+use web_sys::{Element, HtmlElement, KeyboardEvent, MouseEvent};
+
+use super::{
+    game_loop::AnimationLoop,
+    input::{keymap::Keymap, Action, Input},
+    render::{CanvasRenderer, DomRenderer, Renderer, RendererKind},
+    settings::use_keep_focus,
+    skin::Skin,
+};
+use crate::tetris_game::{
+    snapshot::Snapshot,
+    versus::{Outcome, Versus, VersusSettings},
+    Settings,
+};
+
+/*
+*   Local versus: two players on one keyboard, see tetris_game::versus for the rules.
+*       - the left player plays with WASD (Q/E rotate, R 180, left shift hold),
+*         the right one with the arrows (,/. rotate, / 180, right shift hold), Escape pauses both
+*       - next to each board a meter shows the garbage waiting to rise
+*   App mounts this instead of the single player game while versus is on,
+*   it runs its own game loop and takes the focus (and with it the keys).
+*/
+
+pub struct VersusView {
+    pub width: u32,
+    pub height: u32,
+    pub settings: Settings,
+    pub versus: VersusSettings,
+    pub skin: Skin,
+    pub renderer: RendererKind,
+}
+
+const PLAYERS: [&str; 2] = ["left", "right"];
+
+impl VersusView {
+    fn new_match(&self) -> Versus {
+        Versus::new(
+            self.width,
+            self.height,
+            self.settings.clone(),
+            self.versus.clone(),
+        )
+    }
+}
+
+impl Component for VersusView {
+    fn render(&self) -> VNode {
+        let versus = use_state(|| self.new_match());
+
+        // the keys only reach us while we have the focus:
+        let container = use_js_ref::<Element>(None);
+        use_effect(
+            {
+                let container = container.clone();
+                move || {
+                    container
+                        .current()
+                        .and_then(|el| el.dyn_into::<HtmlElement>().ok())
+                        .map(|el| el.focus().ok());
+                    || ()
+                }
+            },
+            Deps::none(),
+        );
+
+        // both games run on the same loop, garbage gets exchanged after every frame:
+        use_effect(
+            {
+                let mut versus = versus.clone();
+                move || {
+                    let game_loop = AnimationLoop::start(move |elapsed| {
+                        versus.set(|mut versus| {
+                            versus.update(elapsed);
+                            versus
+                        })
+                    });
+                    move || drop(game_loop)
+                }
+            },
+            Deps::none(),
+        );
+
+        // each key belongs to one of the players, pause to both:
+        let handle_key = use_callback(
+            {
+                let mut versus = versus.clone();
+                let keymaps = Keymap::versus();
+                move |ev: KeyboardEvent| {
+                    let pressed = ev.type_() == "keydown";
+                    let found = keymaps
+                        .iter()
+                        .enumerate()
+                        .find_map(|(player, keymap)| Some((player, keymap.action(&ev.code())?)));
+                    let Some((player, action)) = found else {
+                        return;
+                    };
+                    ev.prevent_default();
+                    if ev.repeat() {
+                        return;
+                    }
+                    versus.set(|mut versus| {
+                        match (action, pressed) {
+                            (Action::Pause, true) => versus.toggle_pause(),
+                            (Action::Pause, false) => {}
+                            (action, true) => Input::Press(action).apply(versus.board_mut(player)),
+                            (action, false) => {
+                                Input::Release(action).apply(versus.board_mut(player))
+                            }
+                        }
+                        versus
+                    });
+                }
+            },
+            Deps::none(),
+        );
+        let handle_rematch = use_callback(
+            {
+                let mut versus = versus.clone();
+                let (width, height) = (self.width, self.height);
+                let (settings, rules) = (self.settings.clone(), self.versus.clone());
+                move |_: MouseEvent| {
+                    let rematch = Versus::new(width, height, settings.clone(), rules.clone());
+                    versus.set(|_| rematch);
+                }
+            },
+            Deps::none(),
+        );
+        let keep_focus = use_keep_focus();
+
+        // a renderer per board (the canvas one needs its own canvas and last frame for each):
+        let canvas = [use_js_ref::<Element>(None), use_js_ref::<Element>(None)];
+        let last_frame = [use_ref(None::<Snapshot>), use_ref(None::<Snapshot>)];
+        let renderer_kind = use_state(|| self.renderer);
+        let renderers: Vec<Box<dyn Renderer>> = (0..2)
+            .map(|player| -> Box<dyn Renderer> {
+                match *renderer_kind.value() {
+                    RendererKind::Canvas => Box::new(CanvasRenderer::new(
+                        self.skin.clone(),
+                        canvas[player].clone(),
+                        last_frame[player].clone(),
+                    )),
+                    RendererKind::Dom => Box::new(DomRenderer::new(self.skin.clone())),
+                }
+            })
+            .collect();
+        let frames: Vec<Snapshot> = (0..2)
+            .map(|player| versus.value().board(player).snapshot())
+            .collect();
+        let boards: Vec<VNode> = (0..2)
+            .map(|player| {
+                let versus = versus.value();
+                let incoming = versus.incoming(player).total();
+                // the meter fills up from the bottom, a full meter is a board full of garbage:
+                let filled = (incoming * 100 / self.height.max(1)).min(100);
+                let attacker = versus.attacker(player);
+                let streak = [
+                    attacker
+                        .combo()
+                        .filter(|combo| *combo > 0)
+                        .map(|combo| format!("combo {}", combo)),
+                    attacker
+                        .is_back_to_back()
+                        .then(|| "back to back".to_string()),
+                ];
+                h!(div)
+                    .key(Some(PLAYERS[player]))
+                    .style(&Style::new().margin_right("1.5rem"))
+                    .build(c![
+                        h!(div)
+                            .style(&Style::new().display("flex").align_items("flex-end"))
+                            .build(c![
+                                h!(div)
+                                    .style(
+                                        &Style::new()
+                                            .display("flex")
+                                            .flex_direction("column")
+                                            .justify_content("flex-end")
+                                            .width("0.6rem")
+                                            .height("20rem")
+                                            .border("1px solid lightcyan"),
+                                    )
+                                    .build(c![h!(div)
+                                        .style(
+                                            &Style::new()
+                                                .height(format!("{}%", filled))
+                                                .background_color("crimson"),
+                                        )
+                                        .build(c![])]),
+                                renderers[player].view(&frames[player]),
+                            ]),
+                        h!(div)
+                            .style(&Style::new().margin_left("1.5rem").color("lightcyan"))
+                            .build(c![format!(
+                                "{}: incoming {} sent {} {}",
+                                PLAYERS[player],
+                                incoming,
+                                versus.sent(player),
+                                streak.into_iter().flatten().collect::<Vec<_>>().join(", ")
+                            )]),
+                    ])
+            })
+            .collect();
+
+        // after every render the renderers get to paint (they skip the work if their frame did not change):
+        use_effect(
+            {
+                let mut renderer_kind = renderer_kind.clone();
+                move || {
+                    let failed = renderers
+                        .iter()
+                        .zip(&frames)
+                        .any(|(renderer, frame)| renderer.draw(frame).is_err());
+                    if failed {
+                        renderer_kind.set(|_| RendererKind::Dom);
+                    }
+                    || ()
+                }
+            },
+            Deps::all(),
+        );
+
+        let status = match versus.value().outcome() {
+            Some(Outcome::Win(player)) => format!("{} wins!", PLAYERS[player]),
+            Some(Outcome::Draw) => "draw".to_string(),
+            None if versus.value().is_paused() => "paused".to_string(),
+            None => String::new(),
+        };
+
+        h!(div)
+            .ref_container(&container)
+            .tabindex(0)
+            .on_keydown(&handle_key)
+            .on_keyup(&handle_key)
+            .style(&Style::new().outline("none"))
+            .build(c![
+                h!(div)
+                    .style(&Style::new().display("flex"))
+                    .build(c![..boards.into_iter()]),
+                h!(div)
+                    .style(
+                        &Style::new()
+                            .margin_top("0.5rem")
+                            .margin_left("1.5rem")
+                            .color("lightcyan"),
+                    )
+                    .build(c![
+                        "left: WASD, Q/E rotate, R 180, left shift hold | ",
+                        "right: arrows, ,/. rotate, / 180, right shift hold | Esc pauses ",
+                        h!(button)
+                            .on_mousedown(&keep_focus)
+                            .on_click(&handle_rematch)
+                            .style(&Style::new().margin_left("0.5rem"))
+                            .build(c!["rematch"]),
+                        h!(span)
+                            .style(&Style::new().margin_left("0.5rem"))
+                            .build(c![status]),
+                    ]),
+            ])
+    }
+}
@@ -0,0 +1,348 @@
+use js_sys::{Function, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::tetris_game::{
+    event::Event,
+    replay::{Command, Replay},
+    rng::{self, SeededRng},
+    shapes::PieceKind,
+    snapshot::Snapshot,
+    Direction, Settings, Tetris,
+};
+
+/*
+*   The engine for plain javascript, without react:
+*       const game = new TetrisHandle(10, 20);
+*       game.onEvent(event => console.log(event.type, event));
+*       requestAnimationFrame loop: game.tick(elapsedMs); draw(game.board(), game.width(), game.height());
+*
+*   Cells in board()/ghost()/queue() are bytes: 0 is empty, otherwise PieceKind + 1 (see cell_byte()).
+*/
+
+/// byte of an empty cell, every other cell is its PieceKind + 1
+pub const CELL_EMPTY: u8 = 0;
+
+/// the byte a cell of this kind gets in board() / queue()
+pub fn cell_byte(kind: Option<PieceKind>) -> u8 {
+    kind.map(|kind| kind as u8 + 1).unwrap_or(CELL_EMPTY)
+}
+
+/// every cell of the board (with the current shape), row by row
+pub fn board_bytes(snapshot: &Snapshot) -> Vec<u8> {
+    snapshot.cells().into_iter().map(cell_byte).collect()
+}
+
+/// the ghost of the current shape (where the shape does not cover it), as a board sized grid
+pub fn ghost_bytes(snapshot: &Snapshot) -> Vec<u8> {
+    let mut bytes = vec![CELL_EMPTY; (snapshot.width * snapshot.height) as usize];
+    // (custom pieces can stick out above the board)
+    for idx in snapshot.ghost.iter().filter_map(|xy| snapshot.index(*xy)) {
+        bytes[idx] = cell_byte(Some(snapshot.active_kind));
+    }
+    bytes
+}
+
+// an engine event as a plain js object: { type: "lineClear", lines: 2, score: 100 }
+fn event_object(event: &Event) -> Result<JsValue, JsValue> {
+    let object = Object::new();
+    let set = |key: &str, value: JsValue| Reflect::set(&object, &key.into(), &value);
+    set("type", event.name().into())?;
+    match *event {
+        Event::Spawn(kind) | Event::Lock(kind) | Event::Hold(kind) => {
+            set("kind", cell_byte(Some(kind)).into())?;
+        }
+        Event::LineClear { lines, score } => {
+            set("lines", lines.into())?;
+            set("score", score.into())?;
+        }
+        Event::LevelUp(level) => {
+            set("level", level.into())?;
+        }
+        Event::Pause(paused) => {
+            set("paused", paused.into())?;
+        }
+        Event::Finesse {
+            kind,
+            inputs,
+            minimum,
+        } => {
+            set("kind", cell_byte(Some(kind)).into())?;
+            set("inputs", inputs.into())?;
+            set("minimum", minimum.into())?;
+        }
+        Event::TSpin { lines } | Event::Garbage { lines } => {
+            set("lines", lines.into())?;
+        }
+        Event::PerfectClear | Event::GameOver => {}
+    }
+    Ok(object.into())
+}
+
+/// ### TetrisHandle
+/// A game of tetris for javascript. Everything that changes the game passes the
+/// events it caused to the onEvent() callback before returning.
+#[wasm_bindgen]
+pub struct TetrisHandle {
+    tetris: Tetris,
+    on_event: Option<Function>,
+    /// the inputs of a seeded game, for submission()
+    replay: Option<Replay>,
+}
+
+#[wasm_bindgen]
+impl TetrisHandle {
+    /// a new game, the same seed always deals the same pieces (random without one).
+    /// Seeded games record their inputs, see submission().
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32, seed: Option<f64>) -> TetrisHandle {
+        let replay = seed.map(|seed| Replay::new(width, height, Settings::default(), seed as u64));
+        let rng: Box<dyn rng::Rng> = match seed {
+            Some(seed) => Box::new(SeededRng::new(seed as u64)),
+            None => rng::default_rng(),
+        };
+        TetrisHandle {
+            tetris: Tetris::with_rng(width, height, Settings::default(), rng),
+            on_event: None,
+            replay,
+        }
+    }
+
+    /// the score with the replay that proves it, as json for the server to verify
+    /// (see tetris_game::replay::verify(), undefined for games without a seed)
+    pub fn submission(&mut self) -> Option<String> {
+        let tetris = &self.tetris;
+        self.replay
+            .as_mut()
+            .map(|replay| replay.finish(tetris).to_json())
+    }
+
+    // writes the input down for the replay
+    fn record(&mut self, command: Command) {
+        if let Some(replay) = &mut self.replay {
+            replay.inputs.push((self.tetris.frame_count(), command));
+        }
+    }
+
+    /// callback(event) for every event of the game, like { type: "lock", kind: 6 }
+    #[wasm_bindgen(js_name = onEvent)]
+    pub fn on_event(&mut self, callback: Option<Function>) {
+        self.on_event = callback;
+    }
+
+    // hands the events of the last call to the callback
+    fn dispatch(&mut self) -> Result<(), JsValue> {
+        let events = self.tetris.take_events();
+        if let Some(callback) = &self.on_event {
+            for event in &events {
+                callback.call1(&JsValue::NULL, &event_object(event)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// advances the game by the ms that passed since the last call, returns the simulated frames
+    pub fn tick(&mut self, elapsed_ms: f64) -> Result<u32, JsValue> {
+        let frames = self.tetris.update(elapsed_ms);
+        self.dispatch()?;
+        Ok(frames)
+    }
+
+    /// moves the current shape one cell left (dx < 0) or right (dx > 0), false if it did not fit
+    #[wasm_bindgen(js_name = "move")]
+    pub fn move_shape(&mut self, dx: i32) -> Result<bool, JsValue> {
+        let dir = if dx < 0 {
+            self.record(Command::Left);
+            Direction::Left
+        } else {
+            self.record(Command::Right);
+            Direction::Right
+        };
+        let moved = self.tetris.move_current_shape(dir);
+        self.dispatch()?;
+        Ok(moved)
+    }
+
+    /// starts (held = true) or stops auto shifting left (dx < 0) or right (dx > 0), like holding the key down
+    pub fn shift(&mut self, dx: i32, held: bool) -> Result<(), JsValue> {
+        let dir = if dx < 0 {
+            Direction::Left
+        } else {
+            Direction::Right
+        };
+        self.record(match (dir, held) {
+            (Direction::Left, true) => Command::ShiftLeft,
+            (Direction::Right, true) => Command::ShiftRight,
+            (Direction::Left, false) => Command::StopLeft,
+            (Direction::Right, false) => Command::StopRight,
+        });
+        if held {
+            self.tetris.start_shift(dir);
+        } else {
+            self.tetris.stop_shift(dir);
+        }
+        self.dispatch()
+    }
+
+    /// 1 :clockwise, -1 :counter clockwise, 2 :180 degrees
+    pub fn rotate(&mut self, turns: i32) -> Result<(), JsValue> {
+        let command = match turns {
+            -1 => Command::RotateCcw,
+            2 | -2 => Command::Rotate180,
+            _ => Command::RotateCw,
+        };
+        self.record(command);
+        command.apply(&mut self.tetris);
+        self.dispatch()
+    }
+
+    /// soft drop while held
+    #[wasm_bindgen(js_name = softDrop)]
+    pub fn soft_drop(&mut self, held: bool) -> Result<(), JsValue> {
+        self.record(if held {
+            Command::SoftDrop
+        } else {
+            Command::StopSoftDrop
+        });
+        self.tetris.set_soft_drop(held);
+        self.dispatch()
+    }
+
+    /// drops the current shape and locks it right away
+    #[wasm_bindgen(js_name = hardDrop)]
+    pub fn hard_drop(&mut self) -> Result<(), JsValue> {
+        self.record(Command::HardDrop);
+        self.tetris.hard_drop();
+        self.dispatch()
+    }
+
+    /// false if hold was already used for this shape
+    pub fn hold(&mut self) -> Result<bool, JsValue> {
+        self.record(Command::Hold);
+        let held = self.tetris.hold();
+        self.dispatch()?;
+        Ok(held)
+    }
+
+    #[wasm_bindgen(js_name = togglePause)]
+    pub fn toggle_pause(&mut self) -> Result<(), JsValue> {
+        self.record(Command::Pause);
+        self.tetris.toggle_pause();
+        self.dispatch()
+    }
+
+    pub fn width(&self) -> i32 {
+        self.tetris.get_size().0
+    }
+
+    pub fn height(&self) -> i32 {
+        self.tetris.get_size().1
+    }
+
+    /// width * height cells, row by row (including the current shape)
+    pub fn board(&self) -> Uint8Array {
+        Uint8Array::from(&board_bytes(&self.tetris.snapshot())[..])
+    }
+
+    /// width * height cells, only the ghost of the current shape is filled
+    pub fn ghost(&self) -> Uint8Array {
+        Uint8Array::from(&ghost_bytes(&self.tetris.snapshot())[..])
+    }
+
+    /// the upcoming shapes, next one first
+    pub fn queue(&self) -> Uint8Array {
+        let kinds: Vec<u8> = self
+            .tetris
+            .snapshot()
+            .queue
+            .iter()
+            .map(|preview| cell_byte(Some(preview.kind)))
+            .collect();
+        Uint8Array::from(&kinds[..])
+    }
+
+    /// the shape put aside, undefined if there is none
+    #[wasm_bindgen(js_name = holdPiece)]
+    pub fn hold_piece(&self) -> Option<u8> {
+        self.tetris.get_hold().map(|kind| cell_byte(Some(kind)))
+    }
+
+    pub fn score(&self) -> String {
+        self.tetris.get_score()
+    }
+
+    pub fn level(&self) -> u32 {
+        self.tetris.get_level()
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.tetris.get_lines()
+    }
+
+    #[wasm_bindgen(js_name = isGameOver)]
+    pub fn is_game_over(&self) -> bool {
+        self.tetris.is_game_over()
+    }
+
+    #[wasm_bindgen(js_name = isPaused)]
+    pub fn is_paused(&self) -> bool {
+        self.tetris.is_paused()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::replay::{verify, Ruleset, Submission};
+    use crate::tetris_game::shapes::XY;
+
+    #[test]
+    fn board_bytes_follow_the_kinds() {
+        let mut tetris = Tetris::new(10, 20);
+        let kind = tetris.get_current_shape().get_kind();
+        let board = board_bytes(&tetris.snapshot());
+        assert_eq!(board.len(), 200);
+        assert_eq!(board.iter().filter(|cell| **cell != CELL_EMPTY).count(), 4);
+        assert!(board
+            .iter()
+            .all(|cell| [CELL_EMPTY, kind as u8 + 1].contains(cell)));
+
+        // the ghost is on the floor, the board has it once it got dropped:
+        let ghost = ghost_bytes(&tetris.snapshot());
+        tetris.hard_drop();
+        let board = board_bytes(&tetris.snapshot());
+        for (i, cell) in ghost
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| **cell != CELL_EMPTY)
+        {
+            assert_eq!(board[i], *cell);
+            assert!(i / 10 >= 18);
+        }
+
+        // ghost cells outside the board get left out:
+        let mut snapshot = tetris.snapshot();
+        snapshot.ghost = vec![XY(0, -1), XY(10, 0), XY(1, 0)];
+        let ghost = ghost_bytes(&snapshot);
+        assert_eq!(ghost.len(), 200);
+        assert_eq!(ghost.iter().filter(|cell| **cell != CELL_EMPTY).count(), 1);
+    }
+
+    #[test]
+    fn seeded_handles_record_a_replay() {
+        let mut handle = TetrisHandle::new(10, 20, Some(5.0));
+        handle.shift(-1, true).unwrap();
+        handle.tick(300.0).unwrap();
+        handle.shift(-1, false).unwrap();
+        handle.rotate(2).unwrap();
+        handle.hard_drop().unwrap();
+        handle.tick(100.0).unwrap();
+        let json = handle.submission().unwrap();
+        let submission = Submission::from_json(&json).unwrap();
+        assert_eq!(submission.replay.inputs.len(), 4);
+        assert_eq!(
+            verify(&submission, &Ruleset::default()),
+            Ok(submission.outcome)
+        );
+        assert_eq!(TetrisHandle::new(10, 20, None).submission(), None);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{io, net::ToSocketAddrs, time::Duration};
+
+use super::{
+    hub::ClientId,
+    protocol::{ClientMessage, ServerMessage},
+    ws::WebSocket,
+};
+
+/*
+    A headless client of the relay server, for tests and bots (the browser has its own, see frontend::online).
+    connect() does the websocket handshake and the Hello, recv() waits for the next message of the server
+    (at most TIMEOUT, a silent server is an error instead of a hanging test).
+    Drive a session::Session with it to play an actual game.
+*/
+
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub struct Client {
+    socket: WebSocket,
+    id: ClientId,
+}
+
+impl Client {
+    /// connects to the server and says hello
+    pub fn connect(addr: impl ToSocketAddrs, name: &str) -> io::Result<Self> {
+        let socket = WebSocket::connect(addr, "/")?;
+        socket.set_read_timeout(Some(TIMEOUT))?;
+        let mut client = Self { socket, id: 0 };
+        client.send(&ClientMessage::hello(name))?;
+        match client.recv()? {
+            ServerMessage::Welcome { id, .. } => client.id = id,
+            ServerMessage::Error { message } => {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, message))
+            }
+            message => return Err(unexpected(message)),
+        }
+        Ok(client)
+    }
+
+    /// the id the server gave us
+    pub fn id(&self) -> ClientId {
+        self.id
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) -> io::Result<()> {
+        self.socket.send_text(&message.to_json())
+    }
+
+    /// the next message of the server (a closed connection is an UnexpectedEof error)
+    pub fn recv(&mut self) -> io::Result<ServerMessage> {
+        let text = self
+            .socket
+            .recv()?
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        ServerMessage::from_json(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// how long recv() waits, None waits forever
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+}
+
+fn unexpected(message: ServerMessage) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected message: {}", message.to_json()),
+    )
+}
@@ -0,0 +1,563 @@
+use std::collections::BTreeMap;
+
+use super::protocol::{ClientMessage, ServerMessage, PROTOCOL_VERSION};
+use crate::tetris_game::{
+    garbage::{self, Attacker, Clear, Incoming},
+    position::Position,
+    rng::SeededRng,
+    versus::VersusSettings,
+};
+
+/*
+    The state of the server, without any networking: clients, rooms and the matches in them.
+        - the server feeds it what the clients send (handle()) and who comes and goes (connect()/disconnect()),
+          it answers with the messages to send to whom (Outbox)
+        - matchmaking: Join without a name goes into the oldest room waiting for a second player (or opens one)
+        - the garbage exchange is the one of local versus (see tetris_game::garbage::exchange()), just over the network:
+          placements attack, attacks cancel the own incoming garbage first, a placement without lines lets it rise
+        - all boards are of the size of the hub: Start tells the clients, placements with another board get turned away
+          (and so do clears that can not happen, see the protocol for what the hub trusts)
+        - a match ends when a board tops out or a player leaves
+    Seeds and holes come from one SeededRng, the same hub seed plays out the same.
+*/
+
+pub type ClientId = u64;
+
+/// messages to send, and to whom
+pub type Outbox = Vec<(ClientId, ServerMessage)>;
+
+/// the most lines a single placement can clear
+const MAX_LINES: u32 = 4;
+
+#[derive(Debug, Default)]
+struct Client {
+    /// None until the client said hello
+    name: Option<String>,
+    room: Option<String>,
+}
+
+#[derive(Debug)]
+struct Room {
+    /// one or two players, in the order they joined
+    players: Vec<ClientId>,
+    /// rooms matchmaking opened, anyone can end up in them
+    public: bool,
+    game: Option<Match>,
+}
+
+#[derive(Debug, Default)]
+struct Match {
+    attackers: [Attacker; 2],
+    incoming: [Incoming; 2],
+}
+
+#[derive(Debug)]
+pub struct Hub {
+    clients: BTreeMap<ClientId, Client>,
+    rooms: BTreeMap<String, Room>,
+    next_client: ClientId,
+    next_room: u64,
+    /// the size of the boards of all matches
+    width: u32,
+    height: u32,
+    settings: VersusSettings,
+    rng: SeededRng,
+}
+
+impl Hub {
+    /// a hub for matches on boards of the given size
+    pub fn new(width: u32, height: u32, settings: VersusSettings, seed: u64) -> Self {
+        Self {
+            clients: BTreeMap::new(),
+            rooms: BTreeMap::new(),
+            next_client: 1,
+            next_room: 1,
+            width,
+            height,
+            settings,
+            rng: SeededRng::new(seed),
+        }
+    }
+
+    /// a new client, it has to say hello before anything else
+    pub fn connect(&mut self) -> ClientId {
+        let id = self.next_client;
+        self.next_client += 1;
+        self.clients.insert(id, Client::default());
+        id
+    }
+
+    /// the client is gone (a running match counts as lost)
+    pub fn disconnect(&mut self, id: ClientId) -> Outbox {
+        let outbox = self.leave(id);
+        self.clients.remove(&id);
+        outbox
+    }
+
+    /// rooms with their players, for the server log and tests
+    pub fn rooms(&self) -> impl Iterator<Item = (&str, &[ClientId])> {
+        self.rooms
+            .iter()
+            .map(|(name, room)| (name.as_str(), room.players.as_slice()))
+    }
+
+    /// what the client sent, and what to answer
+    pub fn handle(&mut self, id: ClientId, message: ClientMessage) -> Outbox {
+        let Some(client) = self.clients.get_mut(&id) else {
+            return vec![];
+        };
+        match (message, client.name.is_some()) {
+            (ClientMessage::Hello { version, name }, _) => {
+                if version != PROTOCOL_VERSION {
+                    let message = format!(
+                        "protocol version {} is not supported, this server speaks {}",
+                        version, PROTOCOL_VERSION
+                    );
+                    return vec![(id, ServerMessage::error(message))];
+                }
+                client.name = Some(name);
+                vec![(
+                    id,
+                    ServerMessage::Welcome {
+                        version: PROTOCOL_VERSION,
+                        id,
+                    },
+                )]
+            }
+            (_, false) => vec![(id, ServerMessage::error("say hello first"))],
+            (ClientMessage::Join { .. }, true) if client.room.is_some() => {
+                vec![(id, ServerMessage::error("already in a room"))]
+            }
+            (ClientMessage::Join { room }, true) => self.join(id, room),
+            (ClientMessage::Placement { .. } | ClientMessage::TopOut, true)
+                if self.playing(id).is_none() =>
+            {
+                vec![(id, ServerMessage::error("not in a match"))]
+            }
+            (
+                ClientMessage::Placement {
+                    lines,
+                    t_spin,
+                    perfect_clear,
+                    board,
+                },
+                true,
+            ) => {
+                let clear = Clear {
+                    kind: None,
+                    lines,
+                    t_spin,
+                    perfect_clear,
+                };
+                match self.check_placement(clear, &board) {
+                    Ok(()) => self.placement(id, clear, board),
+                    Err(message) => vec![(id, ServerMessage::error(message))],
+                }
+            }
+            (ClientMessage::TopOut, true) => self.leave(id),
+            (ClientMessage::Leave, true) => self.leave(id),
+        }
+    }
+
+    // the room and the seat of a client in a running match
+    fn playing(&self, id: ClientId) -> Option<(String, usize)> {
+        let name = self.clients.get(&id)?.room.clone()?;
+        let room = self.rooms.get(&name)?;
+        room.game.as_ref()?;
+        let seat = room.players.iter().position(|player| *player == id)?;
+        Some((name, seat))
+    }
+
+    // turns away what a placement can not be
+    fn check_placement(&self, clear: Clear, board: &str) -> Result<(), String> {
+        if clear.lines > MAX_LINES {
+            return Err(format!("a placement clears at most {} lines", MAX_LINES));
+        }
+        if clear.lines == 0 && (clear.t_spin || clear.perfect_clear) {
+            return Err("T-spins and perfect clears without lines send nothing".to_string());
+        }
+        if clear.t_spin && clear.lines == MAX_LINES {
+            return Err("a T-spin clears at most 3 lines".to_string());
+        }
+        let size = Position::from_code(board)
+            .map_err(|err| format!("invalid board: {}", err))?
+            .size();
+        if size != (self.width as i32, self.height as i32) {
+            return Err(format!(
+                "the board is {}x{}, this server plays on {}x{}",
+                size.0, size.1, self.width, self.height
+            ));
+        }
+        Ok(())
+    }
+
+    fn join(&mut self, id: ClientId, name: Option<String>) -> Outbox {
+        let waiting = |room: &Room| room.players.len() < 2 && room.game.is_none();
+        let (name, public) = match name {
+            Some(name) => match self.rooms.get(&name) {
+                Some(room) if !waiting(room) => {
+                    return vec![(id, ServerMessage::error(format!("room {} is full", name)))]
+                }
+                _ => (name, false),
+            },
+            None => {
+                let open = self
+                    .rooms
+                    .iter()
+                    .find(|(_, room)| room.public && waiting(room));
+                match open {
+                    Some((name, _)) => (name.clone(), true),
+                    None => loop {
+                        // (skipping names somebody picked for their own room)
+                        let name = format!("room-{}", self.next_room);
+                        self.next_room += 1;
+                        if !self.rooms.contains_key(&name) {
+                            break (name, true);
+                        }
+                    },
+                }
+            }
+        };
+        let room = self.rooms.entry(name.clone()).or_insert(Room {
+            players: vec![],
+            public,
+            game: None,
+        });
+        room.players.push(id);
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(name.clone());
+        }
+        if room.players.len() < 2 {
+            return vec![(id, ServerMessage::Waiting { room: name })];
+        }
+
+        // two players, the match starts:
+        room.game = Some(Match::default());
+        let seed = self.rng.next_u64();
+        let players = room.players.clone();
+        let names: Vec<String> = players
+            .iter()
+            .map(|player| {
+                self.clients
+                    .get(player)
+                    .and_then(|client| client.name.clone())
+                    .unwrap_or_default()
+            })
+            .collect();
+        players
+            .iter()
+            .enumerate()
+            .map(|(seat, player)| {
+                let start = ServerMessage::Start {
+                    room: name.clone(),
+                    seed,
+                    opponent: names[1 - seat].clone(),
+                    width: self.width,
+                    height: self.height,
+                };
+                (*player, start)
+            })
+            .collect()
+    }
+
+    fn placement(&mut self, id: ClientId, clear: Clear, board: String) -> Outbox {
+        let Some((name, seat)) = self.playing(id) else {
+            return vec![];
+        };
+        let room = self
+            .rooms
+            .get_mut(&name)
+            .expect("playing in an existing room");
+        let game = room.game.as_mut().expect("playing in a match");
+        let opponent = room.players[1 - seat];
+        let mut outbox = vec![];
+
+        let before = game.incoming[seat].total();
+        let (attack, rising) = garbage::exchange(
+            &mut game.attackers[seat],
+            &mut game.incoming[seat],
+            &self.settings.attack,
+            clear,
+            |attack| attack,
+        );
+        if attack > 0 {
+            game.incoming[1 - seat].push(attack);
+            let lines = game.incoming[1 - seat].total();
+            outbox.push((opponent, ServerMessage::Incoming { lines }));
+        }
+        if !rising.is_empty() {
+            let holes = self
+                .settings
+                .holes
+                .holes(&rising, self.width as i32, &mut self.rng);
+            outbox.push((id, ServerMessage::Garbage { holes }));
+        }
+        let lines = game.incoming[seat].total();
+        if lines != before {
+            outbox.push((id, ServerMessage::Incoming { lines }));
+        }
+        outbox.push((opponent, ServerMessage::OpponentBoard { board }));
+        outbox
+    }
+
+    // out of the room, a running match goes to the opponent
+    fn leave(&mut self, id: ClientId) -> Outbox {
+        let Some(name) = self
+            .clients
+            .get_mut(&id)
+            .and_then(|client| client.room.take())
+        else {
+            return vec![];
+        };
+        let Some(room) = self.rooms.get_mut(&name) else {
+            return vec![];
+        };
+        room.players.retain(|player| *player != id);
+        let mut outbox = vec![];
+        if room.game.is_some() {
+            outbox.push((id, ServerMessage::End { won: false }));
+            for player in room.players.drain(..) {
+                outbox.push((player, ServerMessage::End { won: true }));
+                if let Some(client) = self.clients.get_mut(&player) {
+                    client.room = None;
+                }
+            }
+        }
+        if room.players.is_empty() {
+            self.rooms.remove(&name);
+        }
+        outbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::garbage::HolePlacement;
+
+    fn hub() -> Hub {
+        Hub::new(
+            10,
+            20,
+            VersusSettings {
+                holes: HolePlacement::Column(4),
+                ..Default::default()
+            },
+            1,
+        )
+    }
+
+    // two clients that said hello and got matched
+    fn matched(hub: &mut Hub) -> (ClientId, ClientId) {
+        let (a, b) = (hub.connect(), hub.connect());
+        hub.handle(a, ClientMessage::hello("a"));
+        hub.handle(b, ClientMessage::hello("b"));
+        assert_eq!(
+            hub.handle(a, ClientMessage::Join { room: None }),
+            vec![(
+                a,
+                ServerMessage::Waiting {
+                    room: "room-1".into()
+                }
+            )]
+        );
+        let started = hub.handle(b, ClientMessage::Join { room: None });
+        assert!(matches!(
+            &started[..],
+            [(_, ServerMessage::Start { seed: x, opponent: b_name, width: 10, height: 20, .. }), (_, ServerMessage::Start { seed: y, opponent: a_name, .. })]
+                if x == y && b_name == "b" && a_name == "a"
+        ));
+        (a, b)
+    }
+
+    fn placement(lines: u32) -> ClientMessage {
+        ClientMessage::Placement {
+            lines,
+            t_spin: false,
+            perfect_clear: false,
+            board: "v1:10x20:200_::".into(),
+        }
+    }
+
+    #[test]
+    fn hello_first_and_same_version() {
+        let mut hub = hub();
+        let id = hub.connect();
+        assert!(matches!(
+            &hub.handle(id, ClientMessage::Join { room: None })[..],
+            [(_, ServerMessage::Error { .. })]
+        ));
+        let old = ClientMessage::Hello {
+            version: 0,
+            name: "old".into(),
+        };
+        assert!(matches!(
+            &hub.handle(id, old)[..],
+            [(_, ServerMessage::Error { .. })]
+        ));
+        assert_eq!(
+            hub.handle(id, ClientMessage::hello("new")),
+            vec![(
+                id,
+                ServerMessage::Welcome {
+                    version: PROTOCOL_VERSION,
+                    id
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn matchmaking_and_named_rooms() {
+        let mut hub = hub();
+        let (a, b) = matched(&mut hub);
+        // a third one waits in a new room, named rooms are only for who knows the name:
+        let c = hub.connect();
+        hub.handle(c, ClientMessage::hello("c"));
+        hub.handle(
+            c,
+            ClientMessage::Join {
+                room: Some("friends".into()),
+            },
+        );
+        let d = hub.connect();
+        hub.handle(d, ClientMessage::hello("d"));
+        assert_eq!(
+            hub.handle(d, ClientMessage::Join { room: None }),
+            vec![(
+                d,
+                ServerMessage::Waiting {
+                    room: "room-2".into()
+                }
+            )]
+        );
+        assert!(matches!(
+            &hub.handle(
+                d,
+                ClientMessage::Join {
+                    room: Some("friends".into())
+                }
+            )[..],
+            [(_, ServerMessage::Error { .. })]
+        ));
+        let rooms: Vec<_> = hub
+            .rooms()
+            .map(|(name, players)| (name.to_string(), players.to_vec()))
+            .collect();
+        assert_eq!(
+            rooms,
+            vec![
+                ("friends".to_string(), vec![c]),
+                ("room-1".to_string(), vec![a, b]),
+                ("room-2".to_string(), vec![d]),
+            ]
+        );
+        // leaving a waiting room closes it:
+        assert_eq!(hub.disconnect(d), vec![]);
+        assert_eq!(hub.rooms().count(), 2);
+    }
+
+    #[test]
+    fn garbage_exchange() {
+        let mut hub = hub();
+        let (a, b) = matched(&mut hub);
+        // a tetris attacks, b sees it coming:
+        let out = hub.handle(a, placement(4));
+        assert_eq!(out[0], (b, ServerMessage::Incoming { lines: 4 }));
+        assert!(matches!(out[1], (to, ServerMessage::OpponentBoard { .. }) if to == b));
+        // a double cancels 1 of it:
+        let out = hub.handle(b, placement(2));
+        assert_eq!(out[0], (b, ServerMessage::Incoming { lines: 3 }));
+        // nothing cleared, the rest rises:
+        let out = hub.handle(b, placement(0));
+        assert_eq!(out[0], (b, ServerMessage::Garbage { holes: vec![4; 3] }));
+        assert_eq!(out[1], (b, ServerMessage::Incoming { lines: 0 }));
+        // placements outside of a match are an error:
+        let c = hub.connect();
+        hub.handle(c, ClientMessage::hello("c"));
+        assert!(matches!(
+            &hub.handle(c, placement(1))[..],
+            [(_, ServerMessage::Error { .. })]
+        ));
+        // more than 4 lines at once are made up, and so are spins and perfect clears that can not happen:
+        let claims = [
+            (5, false, false),
+            (0, true, false),
+            (0, false, true),
+            (4, true, false),
+        ];
+        for (lines, t_spin, perfect_clear) in claims {
+            let claim = ClientMessage::Placement {
+                lines,
+                t_spin,
+                perfect_clear,
+                board: "v1:10x20:200_::".into(),
+            };
+            assert!(
+                matches!(
+                    &hub.handle(a, claim)[..],
+                    [(_, ServerMessage::Error { .. })]
+                ),
+                "{} {} {}",
+                lines,
+                t_spin,
+                perfect_clear
+            );
+        }
+        // a board of another size (or none at all) is not the board of this match:
+        for board in ["v1:4x4:_18446744073709551615_::", "garbage"] {
+            let out = hub.handle(
+                b,
+                ClientMessage::Placement {
+                    lines: 0,
+                    t_spin: false,
+                    perfect_clear: false,
+                    board: board.into(),
+                },
+            );
+            assert!(matches!(&out[..], [(to, ServerMessage::Error { .. })] if *to == b));
+        }
+        // the rest only gets passed on, whatever is in it:
+        hub.handle(a, placement(4));
+        let board = "v1:10x20:180_20I::".to_string();
+        let out = hub.handle(
+            b,
+            ClientMessage::Placement {
+                lines: 0,
+                t_spin: false,
+                perfect_clear: false,
+                board: board.clone(),
+            },
+        );
+        assert!(matches!(&out[0], (to, ServerMessage::Garbage { .. }) if *to == b));
+        assert_eq!(
+            out.last(),
+            Some(&(a, ServerMessage::OpponentBoard { board }))
+        );
+    }
+
+    #[test]
+    fn topping_out_or_leaving_ends_the_match() {
+        let mut hub = hub();
+        let (a, b) = matched(&mut hub);
+        assert_eq!(
+            hub.handle(b, ClientMessage::TopOut),
+            vec![
+                (b, ServerMessage::End { won: false }),
+                (a, ServerMessage::End { won: true })
+            ]
+        );
+        assert_eq!(hub.rooms().count(), 0);
+        // both can play again:
+        hub.handle(a, ClientMessage::Join { room: None });
+        let out = hub.handle(b, ClientMessage::Join { room: None });
+        assert_eq!(out.len(), 2);
+        assert_eq!(
+            hub.disconnect(a),
+            vec![
+                (a, ServerMessage::End { won: false }),
+                (b, ServerMessage::End { won: true })
+            ]
+        );
+    }
+}
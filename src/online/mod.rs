@@ -0,0 +1,20 @@
+/*
+*   Online versus: two players on two machines, a small relay server in between.
+*       - protocol: the typed (and versioned) messages between clients and server, json over websocket
+*       - hub: the state of the server (rooms, matchmaking, garbage exchange), no networking in it
+*       - session: the state of a client (its board, the opponent, the incoming garbage), no networking either
+*       - ws, server, client (feature "server", not in wasm): a minimal websocket on std::net, the server
+*         that runs a Hub on it (see src/bin/server) and a headless client for tests and bots
+*   The browser side lives in frontend::online.
+*/
+
+pub mod hub;
+pub mod protocol;
+pub mod session;
+
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod client;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod server;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod ws;
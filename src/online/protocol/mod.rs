@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tetris_game::garbage::Clear;
+
+/*
+    The messages of the online mode, sent as json text frames over a websocket:
+        { "type": "hello", "version": 2, "name": "vince" }
+    - the client starts with Hello, the server only talks to clients that speak its PROTOCOL_VERSION
+    - then Join puts the client into a room (a named one, or matchmaking into any room waiting for a player)
+    - once two players are in a room both get Start with the same seed (so the same pieces) and the size of the board,
+      the server picks the holes for that size and turns away boards of any other size
+    - every locked shape goes to the server as a Placement, the server runs the garbage exchange
+      (attack table, cancelling, holes) and tells the players what rises when
+    New message types or fields mean a new PROTOCOL_VERSION.
+
+    The server trusts what the clients report about their clears, it only turns away what can not happen
+    (more than 4 lines, T-spins or perfect clears without lines, a T-spin with 4 lines). A client that lies
+    about its clears looks just like one that plays well. Backing a result up takes the inputs of the game:
+    tetris_game::replay::verify() plays a recorded game again and checks its score, the protocol does not send them (yet).
+*/
+
+/// version of the messages below, clients with another version get turned away
+pub const PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum ProtocolError {
+    /// not a message of this protocol (or of this version of it)
+    Parse(String),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::Parse(err) => write!(f, "invalid message: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// What a client tells the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ClientMessage {
+    /// the first message: the protocol version the client speaks, and the name the opponent sees
+    Hello { version: u32, name: String },
+    /// into the named room, or (without a name) into any room waiting for a second player
+    Join { room: Option<String> },
+    /// a shape locked: what it cleared, and the board afterwards (a position code, see tetris_game::position)
+    Placement {
+        lines: u32,
+        t_spin: bool,
+        perfect_clear: bool,
+        board: String,
+    },
+    /// the board of the client topped out
+    TopOut,
+    /// out of the room (gives up a running match)
+    Leave,
+}
+
+/// What the server tells a client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ServerMessage {
+    /// the answer to Hello, with the id of the client
+    Welcome { version: u32, id: u64 },
+    /// in the room, waiting for an opponent
+    Waiting { room: String },
+    /// the match starts, both boards deal the pieces of the seed and are of the given size
+    Start {
+        room: String,
+        seed: u64,
+        opponent: String,
+        width: u32,
+        height: u32,
+    },
+    /// the garbage waiting to rise on the board of the client changed
+    Incoming { lines: u32 },
+    /// garbage rows to add right away (their holes, top row first, see Tetris::add_garbage())
+    Garbage { holes: Vec<i32> },
+    /// the board of the opponent after its last placement (a position code)
+    OpponentBoard { board: String },
+    /// the match is over
+    End { won: bool },
+    /// something the client sent could not be done
+    Error { message: String },
+}
+
+impl ClientMessage {
+    pub fn hello(name: &str) -> Self {
+        ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: name.to_string(),
+        }
+    }
+
+    /// the placement of a locked shape
+    pub fn placement(clear: Clear, board: String) -> Self {
+        ClientMessage::Placement {
+            lines: clear.lines,
+            t_spin: clear.t_spin,
+            perfect_clear: clear.perfect_clear,
+            board,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("messages always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ProtocolError> {
+        serde_json::from_str(json).map_err(|err| ProtocolError::Parse(err.to_string()))
+    }
+}
+
+impl ServerMessage {
+    pub fn error(message: impl Into<String>) -> Self {
+        ServerMessage::Error {
+            message: message.into(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("messages always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ProtocolError> {
+        serde_json::from_str(json).map_err(|err| ProtocolError::Parse(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let hello = ClientMessage::hello("vince");
+        assert_eq!(
+            hello.to_json(),
+            r#"{"type":"hello","version":2,"name":"vince"}"#
+        );
+        let messages = [
+            hello,
+            ClientMessage::Join { room: None },
+            ClientMessage::placement(Clear::default(), "v1:2x1:2_::".into()),
+            ClientMessage::TopOut,
+        ];
+        for message in messages {
+            assert_eq!(ClientMessage::from_json(&message.to_json()), Ok(message));
+        }
+        let start = ServerMessage::Start {
+            room: "room-1".into(),
+            seed: 7,
+            opponent: "other".into(),
+            width: 10,
+            height: 20,
+        };
+        assert_eq!(ServerMessage::from_json(&start.to_json()), Ok(start));
+        assert_eq!(
+            ServerMessage::from_json(r#"{"type":"garbage","holes":[3,3]}"#),
+            Ok(ServerMessage::Garbage { holes: vec![3, 3] })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_messages() {
+        assert!(ClientMessage::from_json(r#"{"type":"cheat","lines":40}"#).is_err());
+        assert!(ClientMessage::from_json("hello").is_err());
+        assert!(ServerMessage::from_json(r#"{"type":"end"}"#).is_err());
+    }
+}
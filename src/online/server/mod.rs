@@ -0,0 +1,257 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use super::{
+    hub::{ClientId, Hub, Outbox},
+    protocol::{ClientMessage, ServerMessage},
+    ws::WebSocket,
+};
+/*
+    The relay server: a websocket per client, all of them share one Hub.
+        - one thread per connection reads the messages of its client and hands them to the hub,
+          whatever the hub answers goes into the outbound queues of the receivers (peers)
+        - a second thread per connection writes its queue to the socket, so nothing waits on a slow client
+          while holding a lock. A client that does not take its messages within WRITE_TIMEOUT gets dropped
+        - the hub is locked while its answers get queued, so every client sees the messages in the order the hub made them
+          (always the hub first, then peers)
+    No async runtime, no dependencies: a few hundred players are a few hundred threads, that is fine here.
+*/
+
+/// a client that does not read what gets sent to it for this long gets disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the outbound queues of the clients
+type Peers = BTreeMap<ClientId, Sender<String>>;
+
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    hub: Arc<Mutex<Hub>>,
+    peers: Arc<Mutex<Peers>>,
+}
+
+impl Server {
+    /// listens on the address (port 0 picks a free one, see local_addr()), the clients play on the hub
+    pub fn bind(addr: impl ToSocketAddrs, hub: Hub) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            hub: Arc::new(Mutex::new(hub)),
+            peers: Arc::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// accepts clients until the listener fails
+    pub fn run(self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let (hub, peers) = (self.hub.clone(), self.peers.clone());
+            thread::spawn(move || serve(stream, hub, peers));
+        }
+        Ok(())
+    }
+
+    /// run() on a thread of its own
+    pub fn spawn(self) -> JoinHandle<io::Result<()>> {
+        thread::spawn(move || self.run())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    // a panic on another connection does not make the state unusable for the rest:
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// queues what the hub answered, clients that are gone already get skipped (and forgotten)
+fn deliver(peers: &mut Peers, outbox: Outbox) {
+    for (to, message) in outbox {
+        let sent = peers.get(&to).map(|queue| queue.send(message.to_json()));
+        if let Some(Err(_)) = sent {
+            peers.remove(&to);
+        }
+    }
+}
+
+// sends the queue of a client until it ends (the client is gone) or a send fails
+fn write(mut socket: WebSocket, queue: Receiver<String>) {
+    for text in queue {
+        if socket.send_text(&text).is_err() {
+            // the reading side notices and disconnects the client:
+            let _ = socket.shutdown();
+            return;
+        }
+    }
+}
+
+// one client, from the handshake until it is gone
+fn serve(stream: TcpStream, hub: Arc<Mutex<Hub>>, peers: Arc<Mutex<Peers>>) {
+    let Ok(mut socket) = WebSocket::accept(stream) else {
+        return;
+    };
+    let Ok(writer) = socket.try_clone() else {
+        return;
+    };
+    if writer.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+        return;
+    }
+    let (queue, outgoing) = mpsc::channel();
+    let writer = thread::spawn(move || write(writer, outgoing));
+    let id = lock(&hub).connect();
+    lock(&peers).insert(id, queue);
+
+    while let Ok(Some(text)) = socket.recv() {
+        let mut hub = lock(&hub);
+        let outbox = match ClientMessage::from_json(&text) {
+            Ok(message) => hub.handle(id, message),
+            Err(err) => vec![(id, ServerMessage::error(err.to_string()))],
+        };
+        deliver(&mut lock(&peers), outbox);
+    }
+
+    {
+        let mut hub = lock(&hub);
+        let outbox = hub.disconnect(id);
+        let mut peers = lock(&peers);
+        deliver(&mut peers, outbox);
+        // the end of the queue, the writer stops after what is left in it:
+        peers.remove(&id);
+    }
+    let _ = writer.join();
+    let _ = socket.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::online::{client::Client, protocol::PROTOCOL_VERSION};
+    use crate::tetris_game::{garbage::HolePlacement, versus::VersusSettings};
+
+    fn server() -> SocketAddr {
+        let settings = VersusSettings {
+            holes: HolePlacement::Column(4),
+            ..Default::default()
+        };
+        let server = Server::bind("127.0.0.1:0", Hub::new(10, 20, settings, 1)).unwrap();
+        let addr = server.local_addr().unwrap();
+        server.spawn();
+        addr
+    }
+
+    fn placement(lines: u32) -> ClientMessage {
+        ClientMessage::Placement {
+            lines,
+            t_spin: false,
+            perfect_clear: false,
+            board: "v1:10x20:200_::".into(),
+        }
+    }
+
+    #[test]
+    fn delivering_forgets_clients_that_are_gone() {
+        let (a, a_queue) = mpsc::channel();
+        let (b, b_queue) = mpsc::channel();
+        let mut peers: Peers = [(1, a), (2, b)].into_iter().collect();
+        drop(b_queue);
+        let message = ServerMessage::Incoming { lines: 1 };
+        deliver(
+            &mut peers,
+            vec![
+                (1, message.clone()),
+                (2, message.clone()),
+                (3, message.clone()),
+            ],
+        );
+        assert_eq!(peers.keys().collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(a_queue.try_recv(), Ok(message.to_json()));
+    }
+
+    #[test]
+    fn two_headless_clients_play_a_match() {
+        let addr = server();
+        let mut a = Client::connect(addr, "a").unwrap();
+        let mut b = Client::connect(addr, "b").unwrap();
+        assert_ne!(a.id(), b.id());
+
+        a.send(&ClientMessage::Join { room: None }).unwrap();
+        assert!(matches!(a.recv().unwrap(), ServerMessage::Waiting { .. }));
+        b.send(&ClientMessage::Join { room: None }).unwrap();
+        let (
+            ServerMessage::Start {
+                seed: x, opponent, ..
+            },
+            ServerMessage::Start { seed: y, .. },
+        ) = (a.recv().unwrap(), b.recv().unwrap())
+        else {
+            panic!("the match did not start");
+        };
+        assert_eq!((x, opponent.as_str()), (y, "b"));
+
+        // a clears a tetris, b gets 4 lines of garbage that rise on its next lock:
+        a.send(&placement(4)).unwrap();
+        assert_eq!(b.recv().unwrap(), ServerMessage::Incoming { lines: 4 });
+        assert!(matches!(
+            b.recv().unwrap(),
+            ServerMessage::OpponentBoard { .. }
+        ));
+        b.send(&placement(0)).unwrap();
+        assert_eq!(
+            b.recv().unwrap(),
+            ServerMessage::Garbage { holes: vec![4; 4] }
+        );
+        assert_eq!(b.recv().unwrap(), ServerMessage::Incoming { lines: 0 });
+        assert!(matches!(
+            a.recv().unwrap(),
+            ServerMessage::OpponentBoard { .. }
+        ));
+
+        a.send(&ClientMessage::TopOut).unwrap();
+        assert_eq!(a.recv().unwrap(), ServerMessage::End { won: false });
+        assert_eq!(b.recv().unwrap(), ServerMessage::End { won: true });
+    }
+
+    #[test]
+    fn leaving_gives_up_and_other_versions_get_turned_away() {
+        let addr = server();
+        let mut a = Client::connect(addr, "a").unwrap();
+        let mut b = Client::connect(addr, "b").unwrap();
+        a.send(&ClientMessage::Join {
+            room: Some("x".into()),
+        })
+        .unwrap();
+        a.recv().unwrap();
+        b.send(&ClientMessage::Join {
+            room: Some("x".into()),
+        })
+        .unwrap();
+        a.recv().unwrap();
+        b.recv().unwrap();
+        drop(a);
+        assert_eq!(b.recv().unwrap(), ServerMessage::End { won: true });
+
+        let mut old = WebSocket::connect(addr, "/").unwrap();
+        let hello = ClientMessage::Hello {
+            version: PROTOCOL_VERSION + 1,
+            name: "future".into(),
+        };
+        old.send_text(&hello.to_json()).unwrap();
+        let answer = ServerMessage::from_json(&old.recv().unwrap().unwrap()).unwrap();
+        assert!(matches!(answer, ServerMessage::Error { .. }));
+        old.send_text("not json").unwrap();
+        let answer = ServerMessage::from_json(&old.recv().unwrap().unwrap()).unwrap();
+        assert!(matches!(answer, ServerMessage::Error { .. }));
+    }
+}
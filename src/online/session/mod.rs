@@ -0,0 +1,237 @@
+use super::protocol::{ClientMessage, ServerMessage};
+use crate::tetris_game::{
+    buf::RingBuffer,
+    event::{Event, MAX_EVENTS},
+    garbage,
+    position::Position,
+    rng::SeededRng,
+    Settings, Tetris,
+};
+
+/*
+    The client side of an online match, without the connection itself (the browser uses a WebSocket,
+    tests and bots online::client::Client), so it runs the same everywhere:
+        - receive() everything the server sends, the match starts with the seed and the board size from Start
+        - play the board like any other (board_mut()), and after every update() send what outgoing() returns:
+          a Placement for every locked shape, TopOut once the board is lost
+        - the server decides what rises when (Garbage), the meter (incoming()) is just for showing
+*/
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// waiting for the Welcome of the server
+    Connecting,
+    /// said hello, not in a room
+    Lobby,
+    /// in the room, the opponent is not there yet
+    Waiting {
+        room: String,
+    },
+    Playing {
+        room: String,
+        opponent: String,
+    },
+    Over {
+        won: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct Session {
+    name: String,
+    settings: Settings,
+    status: Status,
+    /// the board of the running (or last) match
+    tetris: Option<Tetris>,
+    /// garbage waiting to rise, as the server last told us
+    incoming: u32,
+    /// the board of the opponent after its last placement
+    opponent: Option<Position>,
+    /// the last Error of the server
+    error: Option<String>,
+    topped_out: bool,
+    /// the events of the board, for the frontend (only the last MAX_EVENTS)
+    events: RingBuffer<Event>,
+}
+
+impl Session {
+    /// (the size of the board is up to the server, see ServerMessage::Start)
+    pub fn new(name: &str, settings: Settings) -> Self {
+        Self {
+            name: name.to_string(),
+            settings,
+            status: Status::Connecting,
+            tetris: None,
+            incoming: 0,
+            opponent: None,
+            error: None,
+            topped_out: false,
+            events: RingBuffer::with_capacity(MAX_EVENTS).overwriting(),
+        }
+    }
+
+    /// the first message to send once connected
+    pub fn hello(&self) -> ClientMessage {
+        ClientMessage::hello(&self.name)
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    pub fn board(&self) -> Option<&Tetris> {
+        self.tetris.as_ref()
+    }
+
+    /// for the inputs, only while playing
+    pub fn board_mut(&mut self) -> Option<&mut Tetris> {
+        match self.status {
+            Status::Playing { .. } => self.tetris.as_mut(),
+            _ => None,
+        }
+    }
+
+    pub fn incoming(&self) -> u32 {
+        self.incoming
+    }
+
+    pub fn opponent(&self) -> Option<&Position> {
+        self.opponent.as_ref()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// what the server sent
+    pub fn receive(&mut self, message: ServerMessage) {
+        match message {
+            ServerMessage::Welcome { .. } => self.status = Status::Lobby,
+            ServerMessage::Waiting { room } => self.status = Status::Waiting { room },
+            ServerMessage::Start {
+                room,
+                seed,
+                opponent,
+                width,
+                height,
+            } => {
+                self.tetris = Some(Tetris::with_rng(
+                    width,
+                    height,
+                    self.settings.clone(),
+                    Box::new(SeededRng::new(seed)),
+                ));
+                self.status = Status::Playing { room, opponent };
+                self.incoming = 0;
+                self.opponent = None;
+                self.topped_out = false;
+                self.error = None;
+            }
+            ServerMessage::Incoming { lines } => self.incoming = lines,
+            ServerMessage::Garbage { holes } => {
+                if let Some(tetris) = self.board_mut() {
+                    tetris.add_garbage(&holes);
+                }
+            }
+            ServerMessage::OpponentBoard { board } => {
+                self.opponent = Position::from_code(&board).ok();
+            }
+            ServerMessage::End { won } => self.status = Status::Over { won },
+            ServerMessage::Error { message } => self.error = Some(message),
+        }
+    }
+
+    /// Advances the board by the real time that passed
+    pub fn update(&mut self, elapsed_ms: f64) {
+        if let Some(tetris) = self.board_mut() {
+            tetris.update(elapsed_ms);
+        }
+    }
+
+    /// What to send about everything that happened on the board since the last call
+    pub fn outgoing(&mut self) -> Vec<ClientMessage> {
+        let Some(tetris) = self.board_mut() else {
+            return vec![];
+        };
+        let events = tetris.take_events();
+        let board = Position::of(tetris).to_code();
+        let mut messages: Vec<ClientMessage> = garbage::clears(&events)
+            .into_iter()
+            .map(|clear| ClientMessage::placement(clear, board.clone()))
+            .collect();
+        if tetris.is_game_over() && !self.topped_out {
+            self.topped_out = true;
+            messages.push(ClientMessage::TopOut);
+        }
+        for event in events {
+            self.events.push_overwrite(event);
+        }
+        messages
+    }
+
+    /// everything that happened on the board since the last call, oldest first (outgoing() collects them)
+    pub fn take_events(&mut self) -> Vec<Event> {
+        let events = &mut self.events;
+        std::iter::from_fn(|| events.try_pop().ok()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::shapes::XY;
+
+    fn playing() -> Session {
+        let mut session = Session::new("me", Settings::default());
+        assert!(matches!(session.hello(), ClientMessage::Hello { .. }));
+        session.receive(ServerMessage::Welcome { version: 1, id: 1 });
+        assert_eq!(session.status(), &Status::Lobby);
+        assert!(session.board_mut().is_none());
+        session.receive(ServerMessage::Start {
+            room: "room-1".into(),
+            seed: 5,
+            opponent: "you".into(),
+            width: 10,
+            height: 20,
+        });
+        session
+    }
+
+    #[test]
+    fn the_seed_deals_the_pieces() {
+        let (a, b) = (playing(), playing());
+        let pieces = |session: &Session| Position::of(session.board().unwrap()).to_code();
+        assert_eq!(pieces(&a), pieces(&b));
+    }
+
+    #[test]
+    fn placements_garbage_and_top_out() {
+        let mut session = playing();
+        session.board_mut().unwrap().hard_drop();
+        let messages = session.outgoing();
+        let [ClientMessage::Placement {
+            lines: 0, board, ..
+        }] = &messages[..]
+        else {
+            panic!("expected one placement, got {:?}", messages);
+        };
+        let position = Position::from_code(board).unwrap();
+        assert_eq!(position.cells().count(), 4);
+        assert!(matches!(session.take_events()[0], Event::Lock(_)));
+
+        session.receive(ServerMessage::Incoming { lines: 2 });
+        assert_eq!(session.incoming(), 2);
+        session.receive(ServerMessage::Garbage { holes: vec![0, 0] });
+        let tetris = session.board().unwrap();
+        assert_eq!(tetris.get_kind(XY(0, 19)), None);
+        assert!(tetris.get_kind(XY(1, 19)).is_some());
+        session.outgoing();
+
+        session.receive(ServerMessage::Garbage { holes: vec![0; 20] });
+        assert_eq!(session.outgoing(), vec![ClientMessage::TopOut]);
+        assert_eq!(session.outgoing(), vec![]);
+        session.receive(ServerMessage::End { won: false });
+        assert_eq!(session.status(), &Status::Over { won: false });
+        assert!(session.board_mut().is_none());
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::tetris_game::rng::SeededRng;
+
+/*
+    Just enough of a websocket (RFC 6455) for the online mode, on a plain std TcpStream:
+        - the opening handshake for both sides: accept() on the server, connect() on a (headless) client
+        - text messages (fragmented ones get put together), ping/pong and close
+        - no extensions, no tls (put a proxy in front of the server for wss://)
+    Browsers talk to accept() with their own WebSocket, connect() is for tests and bots.
+    The handshake needs sha1 and base64, both are small enough to live in here.
+*/
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// bigger messages close the connection, nothing in the protocol comes close
+pub const MAX_MESSAGE: usize = 1 << 20;
+
+// opcodes
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+#[derive(Debug)]
+pub struct WebSocket {
+    stream: TcpStream,
+    /// clients mask what they send, servers do not
+    masks: Option<SeededRng>,
+    closed: bool,
+}
+
+impl WebSocket {
+    /// the server side of the handshake, on a connection that was just accepted
+    pub fn accept(mut stream: TcpStream) -> io::Result<Self> {
+        let request = read_head(&mut stream)?;
+        let Some(key) = header(&request, "sec-websocket-key") else {
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+            return Err(invalid("not a websocket handshake"));
+        };
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(key)
+        );
+        stream.write_all(response.as_bytes())?;
+        Ok(Self {
+            stream,
+            masks: None,
+            closed: false,
+        })
+    }
+
+    /// the client side of the handshake, to ws://addr/path
+    pub fn connect(addr: impl ToSocketAddrs, path: &str) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        let host = stream.peer_addr()?;
+        // the key (and the masks) only have to differ between connections, not be secret:
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        let mut rng = SeededRng::new(nanos ^ u64::from(stream.local_addr()?.port()));
+        let nonce: Vec<u8> = [rng.next_u64(), rng.next_u64()]
+            .iter()
+            .flat_map(|n| n.to_be_bytes())
+            .collect();
+        let key = base64(&nonce);
+        let request = format!(
+            "GET {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n",
+            path, host, key
+        );
+        stream.write_all(request.as_bytes())?;
+        let response = read_head(&mut stream)?;
+        if !response.starts_with("HTTP/1.1 101") {
+            return Err(invalid("the server did not switch to websocket"));
+        }
+        if header(&response, "sec-websocket-accept") != Some(accept_key(&key).as_str()) {
+            return Err(invalid("wrong Sec-WebSocket-Accept"));
+        }
+        Ok(Self {
+            stream,
+            masks: Some(rng),
+            closed: false,
+        })
+    }
+
+    /// a second handle to the same connection (to send from one thread while another one reads)
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            stream: self.stream.try_clone()?,
+            masks: self.masks.clone(),
+            closed: self.closed,
+        })
+    }
+
+    /// recv() gives up with WouldBlock/TimedOut after this (None waits forever)
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    /// sending gives up with WouldBlock/TimedOut after this, for every handle of the connection (None waits forever)
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_write_timeout(timeout)
+    }
+
+    pub fn send_text(&mut self, text: &str) -> io::Result<()> {
+        self.send_frame(TEXT, text.as_bytes())
+    }
+
+    /// The next text message, None once the connection got closed.
+    /// Answers pings and closes on the way.
+    pub fn recv(&mut self) -> io::Result<Option<String>> {
+        let mut message = Vec::new();
+        loop {
+            if self.closed {
+                return Ok(None);
+            }
+            let (fin, opcode, payload) = match self.read_frame() {
+                Ok(frame) => frame,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.closed = true;
+                    return Ok(None);
+                }
+                Err(err) => return Err(err),
+            };
+            match opcode {
+                TEXT | CONTINUATION => {
+                    if message.len() + payload.len() > MAX_MESSAGE {
+                        self.close()?;
+                        return Err(invalid("message too big"));
+                    }
+                    message.extend_from_slice(&payload);
+                    if fin {
+                        let text = String::from_utf8(message)
+                            .map_err(|_| invalid("text message is not utf-8"))?;
+                        return Ok(Some(text));
+                    }
+                }
+                PING => self.send_frame(PONG, &payload)?,
+                PONG => {}
+                CLOSE => {
+                    // echo the close (with its status code), then the connection is done:
+                    let _ = self.send_frame(CLOSE, &payload[..payload.len().min(2)]);
+                    self.closed = true;
+                }
+                BINARY => {
+                    self.close()?;
+                    return Err(invalid("binary messages are not supported"));
+                }
+                _ => {
+                    self.close()?;
+                    return Err(invalid("unknown opcode"));
+                }
+            }
+        }
+    }
+
+    /// tells the other side we are done (recv() returns None from now on)
+    pub fn close(&mut self) -> io::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        self.send_frame(CLOSE, &1000u16.to_be_bytes())
+    }
+
+    /// drops the connection without a close handshake, recv() on every handle ends
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        self.closed = true;
+        self.stream.shutdown(Shutdown::Both)
+    }
+
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        let mask_bit = if self.masks.is_some() { 0x80 } else { 0 };
+        match payload.len() {
+            len @ 0..=125 => frame.push(mask_bit | len as u8),
+            len @ 126..=0xffff => {
+                frame.push(mask_bit | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(mask_bit | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        match &mut self.masks {
+            Some(rng) => {
+                let mask = (rng.next_u64() as u32).to_be_bytes();
+                frame.extend_from_slice(&mask);
+                frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+            }
+            None => frame.extend_from_slice(payload),
+        }
+        // one write per frame, so frames from two handles never interleave:
+        self.stream.write_all(&frame)
+    }
+
+    // fin, opcode and the (unmasked) payload
+    fn read_frame(&mut self) -> io::Result<(bool, u8, Vec<u8>)> {
+        let mut head = [0u8; 2];
+        self.stream.read_exact(&mut head)?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0f;
+        let masked = head[1] & 0x80 != 0;
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                self.stream.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                self.stream.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => len as u64,
+        };
+        if len > MAX_MESSAGE as u64 {
+            return Err(invalid("frame too big"));
+        }
+        let mut mask = [0u8; 4];
+        if masked {
+            self.stream.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0u8; len as usize];
+        self.stream.read_exact(&mut payload)?;
+        if masked {
+            for (b, m) in payload.iter_mut().zip(mask.iter().cycle()) {
+                *b ^= m;
+            }
+        }
+        Ok((fin, opcode, payload))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// the http head up to the empty line (byte by byte, so none of the frames after it get read)
+fn read_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > 8 * 1024 {
+            return Err(invalid("http head too long"));
+        }
+        stream.read_exact(&mut byte)?;
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).map_err(|_| invalid("http head is not utf-8"))
+}
+
+// value of a header (names are case insensitive)
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+/// what the server answers to a Sec-WebSocket-Key
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, x) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+    let mut digest = [0u8; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn handshake_helpers() {
+        // the example of RFC 6455:
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(
+            base64(&sha1(b"abc")),
+            base64(&[
+                0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71, 0x78, 0x50,
+                0xc2, 0x6c, 0x9c, 0xd0, 0xd8, 0x9d
+            ])
+        );
+        let head = "GET / HTTP/1.1\r\nSEC-WebSocket-Key:  abc \r\n\r\n";
+        assert_eq!(header(head, "sec-websocket-key"), Some("abc"));
+        assert_eq!(header(head, "host"), None);
+    }
+
+    #[test]
+    fn messages_over_localhost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut socket = WebSocket::accept(listener.accept().unwrap().0).unwrap();
+            // echo until the client closes:
+            while let Some(text) = socket.recv().unwrap() {
+                socket.send_text(&text).unwrap();
+            }
+        });
+        let mut client = WebSocket::connect(addr, "/").unwrap();
+        let long = "x".repeat(70_000);
+        for text in ["hello", "", long.as_str()] {
+            client.send_text(text).unwrap();
+            assert_eq!(client.recv().unwrap().as_deref(), Some(text));
+        }
+        client.close().unwrap();
+        assert_eq!(client.recv().unwrap(), None);
+        server.join().unwrap();
+    }
+}
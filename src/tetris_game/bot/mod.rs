@@ -0,0 +1,276 @@
+use super::movegen;
+use super::shapes::{PieceKind, Shape, XY};
+use super::Tetris;
+
+pub use super::movegen::Move;
+
+/*
+    A bot that plays the game:
+        - placements(): every spot the current shape (and the hold shape) can get to (see movegen,
+          that includes tucks and spins)
+        - every placement gets scored with a heuristic (Weights), best() picks the highest one
+        - the Moves of a placement replay it on the real game, one call per move
+    Works without any frontend: Bot::play() places one shape right away, Autoplay does one move per step().
+*/
+
+/// How much each property of the board counts, positive is good.
+/// The defaults are the well known weights from Yiyuan Lee's genetic tuning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    /// sum of the heights of all columns
+    pub aggregate_height: f64,
+    /// lines the placement clears
+    pub lines: f64,
+    /// empty cells with a filled cell somewhere above them
+    pub holes: f64,
+    /// sum of the height differences of neighbouring columns
+    pub bumpiness: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            aggregate_height: -0.510066,
+            lines: 0.760666,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+        }
+    }
+}
+
+/// A spot the current (or hold) shape can end up in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub kind: PieceKind,
+    /// the cells the shape locks into
+    pub cells: Vec<XY>,
+    /// the shape gets swapped with the hold shape first
+    pub hold: bool,
+    /// the inputs that get it there, ends with Move::HardDrop
+    pub moves: Vec<Move>,
+    /// what the heuristic thinks of the board after the placement
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bot {
+    pub weights: Weights,
+    /// also try the shape from the hold box (or the next one, if hold is empty)
+    pub use_hold: bool,
+}
+
+impl Bot {
+    pub fn new(weights: Weights) -> Self {
+        Self {
+            weights,
+            use_hold: true,
+        }
+    }
+
+    /// every placement of the current shape (and the hold shape), best first
+    pub fn placements(&self, tetris: &Tetris) -> Vec<Placement> {
+        if !tetris.is_falling() || tetris.is_game_over() {
+            return vec![];
+        }
+        let mut placements = self.placements_of(tetris, &tetris.current_shape, false);
+        if self.use_hold && !tetris.hold_used {
+            let held = match tetris.hold {
+                Some(idx) => Some(Tetris::spawn_shape(
+                    &tetris.settings.piece_set,
+                    idx,
+                    tetris.width,
+                )),
+                None => tetris.next_shapes.iter().next().cloned(),
+            };
+            if let Some(held) = held.filter(|held| tetris.fits(held)) {
+                placements.extend(self.placements_of(tetris, &held, true));
+            }
+        }
+        placements.sort_by(|a, b| b.score.total_cmp(&a.score));
+        placements
+    }
+
+    /// the best placement, None if the game is over (or waits in a delay)
+    pub fn best(&self, tetris: &Tetris) -> Option<Placement> {
+        self.placements(tetris).into_iter().next()
+    }
+
+    /// Places the current shape where best() says, right away. False if there was nothing to place.
+    pub fn play(&self, tetris: &mut Tetris) -> bool {
+        match self.best(tetris) {
+            Some(placement) => {
+                for step in placement.moves {
+                    step.apply(tetris);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    // every spot of the shape, scored
+    fn placements_of(&self, tetris: &Tetris, shape: &Shape, hold: bool) -> Vec<Placement> {
+        movegen::paths(tetris, shape)
+            .into_iter()
+            .map(|path| Placement {
+                kind: shape.get_kind(),
+                score: self.score(tetris, &path.cells),
+                hold,
+                moves: if hold {
+                    [&[Move::Hold], &path.moves[..]].concat()
+                } else {
+                    path.moves
+                },
+                cells: path.cells,
+            })
+            .collect()
+    }
+
+    /// what the heuristic thinks of the board after the cells got locked
+    pub fn score(&self, tetris: &Tetris, cells: &[XY]) -> f64 {
+        let (width, height) = (tetris.width, tetris.height);
+        let mut filled = vec![vec![false; width as usize]; height as usize];
+        for XY(x, y) in tetris
+            .sticky_bottom_shapes
+            .iter()
+            .flat_map(|shape| shape.get_pixels())
+            .chain(cells.iter().copied())
+        {
+            if x >= 0 && x < width && y >= 0 && y < height {
+                filled[y as usize][x as usize] = true;
+            }
+        }
+        // full rows go away before the board gets judged:
+        let rows = filled.len();
+        filled.retain(|row| !row.iter().all(|cell| *cell));
+        let lines = rows - filled.len();
+
+        let heights: Vec<usize> = (0..width as usize)
+            .map(|x| {
+                let top = filled.iter().position(|row| row[x]);
+                top.map(|top| filled.len() - top).unwrap_or(0)
+            })
+            .collect();
+        let holes: usize = (0..width as usize)
+            .map(|x| {
+                let top = filled.len() - heights[x];
+                filled[top..].iter().filter(|row| !row[x]).count()
+            })
+            .sum();
+        let bumpiness: usize = heights
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum();
+
+        let w = &self.weights;
+        w.aggregate_height * heights.iter().sum::<usize>() as f64
+            + w.lines * lines as f64
+            + w.holes * holes as f64
+            + w.bumpiness * bumpiness as f64
+    }
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self::new(Weights::default())
+    }
+}
+
+/// The bot playing a game move by move (so it can be watched), one step() per move
+#[derive(Debug, Clone, Default)]
+pub struct Autoplay {
+    pub bot: Bot,
+    /// moves of the current placement that are still to come
+    plan: Vec<Move>,
+}
+
+impl Autoplay {
+    pub fn new(bot: Bot) -> Self {
+        Self { bot, plan: vec![] }
+    }
+
+    /// Sends the next move of the plan to the game, plans the next placement when there is none.
+    /// Returns the move (None while there is nothing to do, like during a delay).
+    pub fn step(&mut self, tetris: &mut Tetris) -> Option<Move> {
+        if !tetris.is_falling() || tetris.is_paused() || tetris.is_game_over() {
+            return None;
+        }
+        if self.plan.is_empty() {
+            let mut moves = self.bot.best(tetris)?.moves;
+            moves.reverse();
+            self.plan = moves;
+        }
+        let step = self.plan.pop()?;
+        step.apply(tetris);
+        Some(step)
+    }
+
+    /// forgets the current plan (after someone else moved the shape)
+    pub fn reset(&mut self) {
+        self.plan.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::{rng::SeededRng, Settings};
+
+    fn seeded(seed: u64) -> Tetris {
+        Tetris::with_rng(10, 20, Settings::default(), Box::new(SeededRng::new(seed)))
+    }
+
+    #[test]
+    fn moves_reach_the_placement() {
+        let tetris = seeded(3);
+        let bot = Bot::default();
+        let placements = bot.placements(&tetris);
+        // an I/J/L/T/S/Z/O piece has somewhere between 9 and 34 spots on an empty board:
+        assert!(placements.len() >= 9, "{}", placements.len());
+        for placement in placements {
+            let mut tetris = seeded(3);
+            for step in &placement.moves {
+                step.apply(&mut tetris);
+            }
+            let mut locked: Vec<XY> = tetris.sticky_bottom_shapes[0].get_pixels().collect();
+            locked.sort_by_key(|xy| (xy.1, xy.0));
+            assert_eq!(locked, placement.cells, "{:?}", placement.moves);
+        }
+    }
+
+    #[test]
+    fn heuristic_prefers_flat_boards_without_holes() {
+        let tetris = Tetris::new(4, 4);
+        let bot = Bot::default();
+        let flat = [XY(0, 3), XY(1, 3), XY(2, 3)];
+        let tower = [XY(0, 1), XY(0, 2), XY(0, 3)];
+        let hole = [XY(0, 2), XY(1, 2), XY(2, 2)];
+        assert!(bot.score(&tetris, &flat) > bot.score(&tetris, &tower));
+        assert!(bot.score(&tetris, &flat) > bot.score(&tetris, &hole));
+    }
+
+    #[test]
+    fn plays_headless() {
+        let mut tetris = seeded(11);
+        let bot = Bot::default();
+        for _ in 0..60 {
+            assert!(bot.play(&mut tetris));
+        }
+        // a random player would have topped out long ago:
+        assert!(!tetris.is_game_over());
+        assert!(tetris.get_lines() >= 18, "{}", tetris.get_lines());
+    }
+
+    #[test]
+    fn autoplay_moves_one_step_at_a_time() {
+        let mut tetris = seeded(5);
+        let mut autoplay = Autoplay::default();
+        let mut steps = 0;
+        while tetris.sticky_bottom_shapes.is_empty() {
+            assert!(autoplay.step(&mut tetris).is_some());
+            steps += 1;
+        }
+        assert!(steps >= 1);
+        assert!(autoplay.plan.is_empty());
+    }
+}
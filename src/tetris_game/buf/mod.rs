@@ -1,68 +1,167 @@
+use std::ops::Index;
+
 #[derive(Debug, PartialEq)]
 pub enum RingBufferError {
     AlreadyEmpty,
     AlreadyFull,
 }
 
+impl std::fmt::Display for RingBufferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RingBufferError::AlreadyEmpty => write!(f, "ring buffer is already empty"),
+            RingBufferError::AlreadyFull => write!(f, "ring buffer is already full"),
+        }
+    }
+}
+
+impl std::error::Error for RingBufferError {}
+
 /// ### Ring Buffer
-/// Implementation of a RingBuffer:
-/// - pop() from front,
-/// - push() to end,
-/// - of static length
-/// Used to
-#[derive(Debug, PartialEq)]
+/// Implementation of a RingBuffer (a bounded queue):
+/// - pop from front,
+/// - push to end,
+/// - of static capacity
+///
+/// By default pushing into a full buffer fails, in overwrite-mode the oldest element gets dropped instead.
+///
+/// Used to queue up the next shapes in the game, but general enough for input buffers, replay windows etc.
+#[derive(Debug, Clone, PartialEq)]
 pub struct RingBuffer<T> {
-    all: Vec<T>,
+    all: Vec<Option<T>>,
     first: usize,
     size: usize,
+    overwrite: bool,
 }
-impl<T: Clone> RingBuffer<T> {
+
+impl<T> RingBuffer<T> {
+    /// creates a full buffer, with the capacity of the starting_vec
     pub fn new(starting_vec: Vec<T>) -> Self {
         Self {
             first: 0,
-            size: starting_vec.len() as usize,
-            all: starting_vec,
+            size: starting_vec.len(),
+            all: starting_vec.into_iter().map(Some).collect(),
+            overwrite: false,
         }
     }
 
+    /// creates an empty buffer that can hold up to capacity elements
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            first: 0,
+            size: 0,
+            all: (0..capacity).map(|_| None).collect(),
+            overwrite: false,
+        }
+    }
+
+    /// switches the buffer to overwrite-mode: pushing into a full buffer drops the oldest element
+    pub fn overwriting(mut self) -> Self {
+        self.overwrite = true;
+        self
+    }
+
+    pub fn is_overwriting(&self) -> bool {
+        self.overwrite
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.all.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.size == self.all.len()
+    }
+
     // pops FIRST element in the Ring and appends new Element to END after.
+    // if the buffer is empty the new element just passes through
     pub fn pop_and_push(&mut self, t: T) -> T {
-        let out = self.pop().unwrap();
-        self.push(t).unwrap();
-        out
+        match self.try_pop() {
+            Ok(out) => {
+                // we just made space, so this can not fail:
+                let _ = self.try_push(t);
+                out
+            }
+            Err(_) => t,
+        }
     }
 
     // returns reference to next in queue
-    pub fn peek(&self) -> &T{
-        &self.all[self.first]
+    pub fn peek(&self) -> Option<&T> {
+        self.get(0)
     }
-    
-    fn pop(&mut self) -> Result<T, RingBufferError> {
+
+    /// returns reference to the idx-th element, counting from the first (next to pop)
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        if idx >= self.size {
+            return None;
+        }
+        self.all[(self.first + idx) % self.all.len()].as_ref()
+    }
+
+    /// iterates over the queued elements, starting with the first (next to pop) to the last
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            ring: self,
+            front: 0,
+            back: self.size,
+        }
+    }
+
+    /// removes the FIRST element of the queue
+    pub fn try_pop(&mut self) -> Result<T, RingBufferError> {
         if self.size == 0 {
             return Err(RingBufferError::AlreadyEmpty);
         }
-        let out = &self.all[self.first];
+        let out = self.all[self.first].take();
         self.first = self.add_one(self.first);
         self.size -= 1;
-        Ok(out.clone())
+        Ok(out.expect("ring buffer slot inside of size must be filled"))
     }
 
-    fn push(&mut self, val: T) -> Result<(), RingBufferError> {
-        if self.size >= self.all.len() {
-            return Err(RingBufferError::AlreadyFull);
+    /// appends to the END of the queue, in overwrite-mode this only fails with a capacity of 0
+    pub fn try_push(&mut self, val: T) -> Result<(), RingBufferError> {
+        if self.overwrite && !self.all.is_empty() {
+            self.push_overwrite(val);
+            return Ok(());
         }
-        let mut size = self.size as i32 - 1;
-        if self.size as i32 - 1 < 0 {
-            // wrap to the last element of the vec:
-            size += self.all.len() as i32;
+        if self.is_full() {
+            return Err(RingBufferError::AlreadyFull);
         }
-        let new_last_idx = self.add_one(self.first + size as usize);
+        let new_last_idx = (self.first + self.size) % self.all.len();
         self.size += 1;
-        self.all[new_last_idx] = val;
-        dbg!(new_last_idx);
+        self.all[new_last_idx] = Some(val);
         Ok(())
     }
 
+    /// appends to the END of the queue, if full the FIRST element gets removed and returned
+    /// (with a capacity of 0 the element itself is returned)
+    pub fn push_overwrite(&mut self, val: T) -> Option<T> {
+        if self.all.is_empty() {
+            return Some(val);
+        }
+        let dropped = if self.is_full() { self.try_pop().ok() } else { None };
+        let new_last_idx = (self.first + self.size) % self.all.len();
+        self.size += 1;
+        self.all[new_last_idx] = Some(val);
+        dropped
+    }
+
+    /// removes all elements (capacity stays the same)
+    pub fn clear(&mut self) {
+        self.all.iter_mut().for_each(|slot| *slot = None);
+        self.first = 0;
+        self.size = 0;
+    }
+
     // helper functions:
     /// adds one and wraps to beginning if it would be out of bounds of array length
     fn add_one(&self, idx: usize) -> usize {
@@ -74,6 +173,96 @@ impl<T: Clone> RingBuffer<T> {
     }
 }
 
+impl<T> Index<usize> for RingBuffer<T> {
+    type Output = T;
+
+    /// indexes from the first (next to pop) element, panics if idx >= len()
+    fn index(&self, idx: usize) -> &T {
+        match self.get(idx) {
+            Some(val) => val,
+            None => panic!("index {} out of bounds for RingBuffer of len {}", idx, self.size),
+        }
+    }
+}
+
+/// collects into a full buffer, with the capacity of the number of collected elements
+impl<T> FromIterator<T> for RingBuffer<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+/// borrowing iterator, from first to last
+pub struct Iter<'a, T> {
+    ring: &'a RingBuffer<T>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.front += 1;
+        self.ring.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.ring.get(self.back)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a RingBuffer<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// consuming iterator, pops from first to last
+pub struct IntoIter<T>(RingBuffer<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.try_pop().ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len(), Some(self.0.len()))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for RingBuffer<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,46 +272,46 @@ mod tests {
     fn cant_pop_to_much() {
         // trying to pop while its empty must fail. (for every possible first element) for size 4
         let mut ring = RingBuffer::new(vec![0, 1, 2, 3]);
-        ring.pop().unwrap();
-        ring.pop().unwrap();
-        ring.pop().unwrap();
-        ring.pop().unwrap();
-        assert_eq!(ring.pop().unwrap_err(), AlreadyEmpty);
-        ring.push(9).unwrap();
-        ring.pop().unwrap();
-        assert_eq!(ring.pop().unwrap_err(), AlreadyEmpty);
-        ring.push(8).unwrap();
-        ring.pop().unwrap();
-        assert_eq!(ring.pop().unwrap_err(), AlreadyEmpty);
-        ring.push(7).unwrap();
-        ring.pop().unwrap();
-        assert_eq!(ring.pop().unwrap_err(), AlreadyEmpty);
-        ring.push(6).unwrap();
-        ring.pop().unwrap();
-        assert_eq!(ring.pop().unwrap_err(), AlreadyEmpty);
-        ring.push(5).unwrap();
-        ring.pop().unwrap();
-        assert_eq!(ring.pop().unwrap_err(), AlreadyEmpty);
+        ring.try_pop().unwrap();
+        ring.try_pop().unwrap();
+        ring.try_pop().unwrap();
+        ring.try_pop().unwrap();
+        assert_eq!(ring.try_pop().unwrap_err(), AlreadyEmpty);
+        ring.try_push(9).unwrap();
+        ring.try_pop().unwrap();
+        assert_eq!(ring.try_pop().unwrap_err(), AlreadyEmpty);
+        ring.try_push(8).unwrap();
+        ring.try_pop().unwrap();
+        assert_eq!(ring.try_pop().unwrap_err(), AlreadyEmpty);
+        ring.try_push(7).unwrap();
+        ring.try_pop().unwrap();
+        assert_eq!(ring.try_pop().unwrap_err(), AlreadyEmpty);
+        ring.try_push(6).unwrap();
+        ring.try_pop().unwrap();
+        assert_eq!(ring.try_pop().unwrap_err(), AlreadyEmpty);
+        ring.try_push(5).unwrap();
+        ring.try_pop().unwrap();
+        assert_eq!(ring.try_pop().unwrap_err(), AlreadyEmpty);
     }
 
     #[test]
     fn cant_push_to_much() {
         // pushing into already full must fail. Testing for every possible "first-pointer" for size 3
         let mut ring = RingBuffer::new(vec![0, 1, 2]);
-        assert_eq!(ring.push(9).unwrap_err(), AlreadyFull);
-        ring.pop().unwrap();
-        ring.push(11).unwrap();
-        assert_eq!(ring.push(9).unwrap_err(), AlreadyFull);
-        ring.pop().unwrap();
+        assert_eq!(ring.try_push(9).unwrap_err(), AlreadyFull);
+        ring.try_pop().unwrap();
+        ring.try_push(11).unwrap();
+        assert_eq!(ring.try_push(9).unwrap_err(), AlreadyFull);
+        ring.try_pop().unwrap();
         println!("{:?}", ring);
-        ring.push(22).unwrap();
-        assert_eq!(ring.push(9).unwrap_err(), AlreadyFull);
-        ring.pop().unwrap();
-        ring.push(33).unwrap();
-        assert_eq!(ring.push(9).unwrap_err(), AlreadyFull);
-        ring.pop().unwrap();
-        ring.push(44).unwrap();
-        assert_eq!(ring.push(9).unwrap_err(), AlreadyFull);
+        ring.try_push(22).unwrap();
+        assert_eq!(ring.try_push(9).unwrap_err(), AlreadyFull);
+        ring.try_pop().unwrap();
+        ring.try_push(33).unwrap();
+        assert_eq!(ring.try_push(9).unwrap_err(), AlreadyFull);
+        ring.try_pop().unwrap();
+        ring.try_push(44).unwrap();
+        assert_eq!(ring.try_push(9).unwrap_err(), AlreadyFull);
     }
 
     #[test]
@@ -153,81 +342,250 @@ mod tests {
         assert_eq!(ring3.add_one(5), 0);
     }
 
+    #[test]
+    fn iter_first_to_last() {
+        // iter() must start at the first-pointer and wrap arround the end of the vec
+        let mut ring = RingBuffer::new(vec![0, 1, 2, 3]);
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        ring.pop_and_push(4);
+        ring.pop_and_push(5);
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4, 5]);
+        ring.try_pop().unwrap();
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(ring.iter().rev().copied().collect::<Vec<_>>(), vec![5, 4, 3]);
+        assert_eq!(ring[0], 3);
+        assert_eq!(ring[2], 5);
+        assert_eq!(ring.into_iter().collect::<Vec<_>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn pop_and_push_on_empty_passes_through() {
+        let mut ring: RingBuffer<i32> = RingBuffer::with_capacity(2);
+        assert_eq!(ring.pop_and_push(1), 1);
+        assert!(ring.is_empty());
+        let mut ring: RingBuffer<i32> = RingBuffer::with_capacity(0);
+        assert_eq!(ring.pop_and_push(1), 1);
+        assert_eq!(ring.try_push(1).unwrap_err(), AlreadyFull);
+        // nowhere to put it, even when overwriting:
+        let mut ring = ring.overwriting();
+        assert_eq!(ring.try_push(1).unwrap_err(), AlreadyFull);
+    }
+
+    #[test]
+    fn overwrite_drops_oldest() {
+        let mut ring = (0..3).collect::<RingBuffer<_>>().overwriting();
+        assert_eq!(ring.capacity(), 3);
+        ring.try_push(3).unwrap();
+        assert_eq!(ring.push_overwrite(4), Some(1));
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+        ring.try_pop().unwrap();
+        assert_eq!(ring.push_overwrite(5), None);
+        assert_eq!(ring.len(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_out_of_bounds() {
+        let mut ring = RingBuffer::new(vec![0, 1]);
+        ring.try_pop().unwrap();
+        let _ = ring[1];
+    }
+
     #[test]
     fn push_into_pop_out() {
         // adding and removing with push and pop
         let mut ring1 = RingBuffer::new(vec![0, 1, 2]);
 
         // first pop n push
-        let out = ring1.pop().unwrap();
+        let out = ring1.try_pop().unwrap();
         assert_eq!(out, 0);
         let ring2 = RingBuffer {
-            all: vec![0, 1, 2],
+            all: vec![None, Some(1), Some(2)],
             first: 1,
             size: 2,
+            overwrite: false,
         };
         assert_eq!(ring1, ring2);
 
-        ring1.push(99).unwrap();
+        ring1.try_push(99).unwrap();
         let ring2 = RingBuffer {
-            all: vec![99, 1, 2],
+            all: vec![Some(99), Some(1), Some(2)],
             first: 1,
             size: 3,
+            overwrite: false,
         };
         assert_eq!(ring1, ring2);
 
         // second pop n push
-        let out = ring1.pop().unwrap();
+        let out = ring1.try_pop().unwrap();
         assert_eq!(out, 1);
         let ring2 = RingBuffer {
-            all: vec![99, 1, 2],
+            all: vec![Some(99), None, Some(2)],
             first: 2,
             size: 2,
+            overwrite: false,
         };
 
         assert_eq!(ring1, ring2);
-        ring1.push(88).unwrap();
+        ring1.try_push(88).unwrap();
         let ring2 = RingBuffer {
-            all: vec![99, 88, 2],
+            all: vec![Some(99), Some(88), Some(2)],
             first: 2,
             size: 3,
+            overwrite: false,
         };
         assert_eq!(ring1, ring2);
 
         // 3rd pop n push
-        let out = ring1.pop().unwrap();
+        let out = ring1.try_pop().unwrap();
         assert_eq!(out, 2);
         let ring2 = RingBuffer {
-            all: vec![99, 88, 2],
+            all: vec![Some(99), Some(88), None],
             first: 0,
             size: 2,
+            overwrite: false,
         };
 
         assert_eq!(ring1, ring2);
-        ring1.push(77).unwrap();
+        ring1.try_push(77).unwrap();
         let ring2 = RingBuffer {
-            all: vec![99, 88, 77],
+            all: vec![Some(99), Some(88), Some(77)],
             first: 0,
             size: 3,
+            overwrite: false,
         };
         assert_eq!(ring1, ring2);
 
         // 4th pop n push
-        let out = ring1.pop().unwrap();
+        let out = ring1.try_pop().unwrap();
         assert_eq!(out, 99);
         let ring2 = RingBuffer {
-            all: vec![99, 88, 77],
+            all: vec![None, Some(88), Some(77)],
             first: 1,
             size: 2,
+            overwrite: false,
         };
 
         assert_eq!(ring1, ring2);
-        ring1.push(66).unwrap();
+        ring1.try_push(66).unwrap();
         let ring2 = RingBuffer {
-            all: vec![66, 88, 77],
+            all: vec![Some(66), Some(88), Some(77)],
             first: 1,
             size: 3,
+            overwrite: false,
         };
         assert_eq!(ring1, ring2);
     }
+
+    mod against_vecdeque {
+        use super::*;
+        use proptest::prelude::*;
+        use std::collections::VecDeque;
+
+        #[derive(Debug, Clone)]
+        enum Op {
+            Push(i32),
+            Pop,
+            PushOverwrite(i32),
+            PopAndPush(i32),
+            Clear,
+        }
+
+        fn op() -> impl Strategy<Value = Op> {
+            prop_oneof![
+                4 => any::<i32>().prop_map(Op::Push),
+                4 => Just(Op::Pop),
+                2 => any::<i32>().prop_map(Op::PushOverwrite),
+                2 => any::<i32>().prop_map(Op::PopAndPush),
+                1 => Just(Op::Clear),
+            ]
+        }
+
+        // the reference model: a VecDeque that never grows past capacity
+        fn apply(model: &mut VecDeque<i32>, capacity: usize, overwrite: bool, op: &Op) {
+            match *op {
+                Op::Push(v) if overwrite => apply(model, capacity, overwrite, &Op::PushOverwrite(v)),
+                Op::Push(v) => {
+                    if model.len() < capacity {
+                        model.push_back(v)
+                    }
+                }
+                Op::Pop => {
+                    model.pop_front();
+                }
+                Op::PushOverwrite(v) => {
+                    if capacity > 0 {
+                        if model.len() == capacity {
+                            model.pop_front();
+                        }
+                        model.push_back(v);
+                    }
+                }
+                Op::PopAndPush(v) => {
+                    if model.pop_front().is_some() {
+                        model.push_back(v);
+                    }
+                }
+                Op::Clear => model.clear(),
+            }
+        }
+
+        proptest! {
+            #[test]
+            fn behaves_like_bounded_vecdeque(
+                capacity in 0usize..8,
+                overwrite in any::<bool>(),
+                ops in proptest::collection::vec(op(), 0..64),
+            ) {
+                let mut ring = RingBuffer::with_capacity(capacity);
+                if overwrite {
+                    ring = ring.overwriting();
+                }
+                let mut model = VecDeque::new();
+
+                for op in &ops {
+                    let expected_front = model.front().copied();
+                    let was_full = model.len() == capacity;
+                    match *op {
+                        Op::Push(v) => {
+                            let res = ring.try_push(v);
+                            prop_assert_eq!(res.is_err(), was_full && (!overwrite || capacity == 0));
+                        }
+                        Op::Pop => {
+                            prop_assert_eq!(ring.try_pop().ok(), expected_front);
+                        }
+                        Op::PushOverwrite(v) => {
+                            let dropped = ring.push_overwrite(v);
+                            let expected = if capacity == 0 { Some(v) } else if was_full { expected_front } else { None };
+                            prop_assert_eq!(dropped, expected);
+                        }
+                        Op::PopAndPush(v) => {
+                            prop_assert_eq!(ring.pop_and_push(v), expected_front.unwrap_or(v));
+                        }
+                        Op::Clear => ring.clear(),
+                    }
+                    apply(&mut model, capacity, overwrite, op);
+
+                    prop_assert_eq!(ring.len(), model.len());
+                    prop_assert_eq!(ring.is_empty(), model.is_empty());
+                    prop_assert_eq!(ring.capacity(), capacity);
+                    prop_assert_eq!(ring.peek(), model.front());
+                    prop_assert!(ring.iter().eq(model.iter()));
+                    prop_assert!(ring.iter().rev().eq(model.iter().rev()));
+                    for i in 0..model.len() {
+                        prop_assert_eq!(ring[i], model[i]);
+                    }
+                }
+                prop_assert_eq!(ring.into_iter().collect::<Vec<_>>(), model.into_iter().collect::<Vec<_>>());
+            }
+
+            #[test]
+            fn collects_like_vecdeque(items in proptest::collection::vec(any::<i32>(), 0..16)) {
+                let ring: RingBuffer<i32> = items.iter().copied().collect();
+                let model: VecDeque<i32> = items.iter().copied().collect();
+                prop_assert_eq!(ring.capacity(), items.len());
+                prop_assert!(ring.iter().eq(model.iter()));
+            }
+        }
+    }
 }
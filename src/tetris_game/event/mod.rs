@@ -0,0 +1,66 @@
+use super::shapes::PieceKind;
+
+/*
+    Things that happened in the game, for frontends that want to react to them (sounds, effects, js callbacks).
+        - the game queues them up, Tetris::take_events() hands them out oldest first
+        - nobody has to take them: only the last MAX_EVENTS are kept
+*/
+
+/// how many events are kept if nobody takes them
+pub const MAX_EVENTS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// a new current shape appeared at the top
+    Spawn(PieceKind),
+    /// the current shape locked into the board
+    Lock(PieceKind),
+    /// full lines got removed, and the score that got for it
+    LineClear {
+        lines: u32,
+        score: i32,
+    },
+    /// the shape that just locked was a T-spin, clearing this many lines (0 to 3)
+    TSpin {
+        lines: u32,
+    },
+    /// the lines that just got cleared left the board empty
+    PerfectClear,
+    /// this many rows of garbage pushed the board up (see garbage)
+    Garbage {
+        lines: u32,
+    },
+    /// the level went up (to the new level)
+    LevelUp(u32),
+    /// the shape of this kind got put aside
+    Hold(PieceKind),
+    /// the game got paused (true) or resumed (false)
+    Pause(bool),
+    /// how many inputs the shape that just locked took, and how few it could have taken
+    /// (only with finesse checking on, see Settings::finesse)
+    Finesse {
+        kind: PieceKind,
+        inputs: u32,
+        minimum: u32,
+    },
+    GameOver,
+}
+
+impl Event {
+    /// short name of the event, like the js api calls it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Spawn(_) => "spawn",
+            Event::Lock(_) => "lock",
+            Event::LineClear { .. } => "lineClear",
+            Event::TSpin { .. } => "tSpin",
+            Event::PerfectClear => "perfectClear",
+            Event::Garbage { .. } => "garbage",
+            Event::LevelUp(_) => "levelUp",
+            Event::Hold(_) => "hold",
+            Event::Pause(_) => "pause",
+            Event::Finesse { .. } => "finesse",
+            Event::GameOver => "gameOver",
+        }
+    }
+}
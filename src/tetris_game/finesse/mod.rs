@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+
+use super::event::Event;
+use super::movegen::{self, Move};
+use super::shapes::{Shape, XY};
+use super::Tetris;
+
+/*
+    Finesse: placing every shape with as few inputs as possible.
+        - the game counts the inputs of the current shape: taps, DAS presses (the auto repeat is free),
+          rotations and soft drop presses
+        - when it locks, movegen looks for the fewest inputs that get it from where it spawned to the same spot
+          (gravity pulling it down is free)
+        - both go out as Event::Finesse, more inputs than needed is a finesse fault
+    FinesseMode::Strict does not let a fault lock, the shape starts over from the top instead.
+*/
+
+/// How the game checks finesse (see Settings::finesse)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FinesseMode {
+    #[default]
+    Off,
+    /// reports every shape, counts the faults
+    Report,
+    /// like Report, but a fault sends the shape back to where it spawned
+    Strict,
+}
+
+// what gets counted for the current shape
+#[derive(Debug, Clone)]
+pub(super) struct Finesse {
+    /// where the current shape spawned (the inputs count from there)
+    spawn: Shape,
+    inputs: u32,
+    faults: u32,
+}
+
+impl Finesse {
+    pub(super) fn new(spawn: &Shape) -> Self {
+        Self {
+            spawn: spawn.clone(),
+            inputs: 0,
+            faults: 0,
+        }
+    }
+
+    pub(super) fn spawned(&mut self, shape: &Shape) {
+        self.spawn = shape.clone();
+        self.inputs = 0;
+    }
+}
+
+/// The fewest inputs that get the shape into the cells, None if it can not get there.
+/// (Move::Down is what gravity does, so it does not count, neither does the hard drop at the end)
+pub fn minimum_inputs(tetris: &Tetris, from: &Shape, cells: &[XY]) -> Option<u32> {
+    let mut cells = cells.to_vec();
+    cells.sort_by_key(|xy| (xy.1, xy.0));
+    let free = |step: Move| matches!(step, Move::Down | Move::HardDrop);
+    let cost = |step: Move| u32::from(!free(step));
+    movegen::paths_with(tetris, from, &Move::PLAYER_MOVES, cost)
+        .into_iter()
+        .find(|path| path.cells == cells)
+        .map(|path| path.moves.iter().filter(|step| !free(**step)).count() as u32)
+}
+
+impl Tetris {
+    /// finesse faults so far (0 with finesse checking off)
+    pub fn finesse_faults(&self) -> u32 {
+        self.finesse.faults
+    }
+
+    pub fn finesse_mode(&self) -> FinesseMode {
+        self.settings.finesse
+    }
+
+    /// turns finesse checking on/off (also in the middle of a game)
+    pub fn set_finesse_mode(&mut self, mode: FinesseMode) {
+        self.settings.finesse = mode;
+    }
+
+    // one more input for the current shape (pressed keys, not the auto repeat)
+    pub(super) fn count_input(&mut self) {
+        if !self.paused && !self.game_over && self.is_falling() {
+            self.finesse.inputs += 1;
+        }
+    }
+
+    // garbage pushed the board up: the spawn moves up with the current shape if it is in the way now
+    pub(super) fn finesse_spawn_pushed_up(&mut self) {
+        self.finesse.spawn = self.pushed_up(&self.finesse.spawn);
+    }
+
+    // Checks the current shape right before it locks.
+    // False if it should not lock: strict mode sent it back to the top.
+    pub(super) fn check_finesse(&mut self) -> bool {
+        if self.settings.finesse == FinesseMode::Off {
+            return true;
+        }
+        let cells: Vec<XY> = self.current_shape.get_pixels().collect();
+        let inputs = self.finesse.inputs;
+        // (the shape can always get where it is, but better not fault anyone if the search says otherwise)
+        let minimum = minimum_inputs(self, &self.finesse.spawn, &cells).unwrap_or(inputs);
+        self.emit(Event::Finesse {
+            kind: self.current_shape.get_kind(),
+            inputs,
+            minimum,
+        });
+        if inputs <= minimum {
+            return true;
+        }
+        self.finesse.faults += 1;
+        // (garbage can fill the spawn, then the shape locks where it is)
+        if self.settings.finesse != FinesseMode::Strict || !self.fits(&self.finesse.spawn) {
+            return true;
+        }
+        self.current_shape = self.finesse.spawn.clone();
+        self.finesse.inputs = 0;
+        self.clock.on_spawn();
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::{piece_set::PieceSet, shapes::PieceKind, Direction, Settings};
+    use std::collections::HashSet;
+
+    // an O (needs no rotations) on an empty board
+    fn game(mode: FinesseMode) -> Tetris {
+        let standard = PieceSet::standard();
+        let idx = (0..standard.len())
+            .find(|idx| standard.shape(*idx).get_kind() == PieceKind::O)
+            .unwrap();
+        let settings = Settings {
+            finesse: mode,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::with_settings(10, 20, settings);
+        tetris.current_shape = Tetris::spawn_shape(&standard, idx, 10);
+        tetris.finesse.spawned(&tetris.current_shape);
+        tetris
+    }
+
+    fn finesse_events(tetris: &mut Tetris) -> Vec<(u32, u32)> {
+        tetris
+            .take_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Finesse {
+                    inputs, minimum, ..
+                } => Some((inputs, minimum)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn das_to_the_wall_is_one_input() {
+        let mut tetris = game(FinesseMode::Report);
+        tetris.take_events();
+        Move::DasLeft.apply(&mut tetris);
+        tetris.hard_drop();
+        assert_eq!(finesse_events(&mut tetris), vec![(1, 1)]);
+        assert_eq!(tetris.finesse_faults(), 0);
+    }
+
+    #[test]
+    fn tapping_to_the_wall_is_a_fault() {
+        let mut tetris = game(FinesseMode::Report);
+        tetris.take_events();
+        while tetris.move_current_shape(Direction::Left) {}
+        tetris.hard_drop();
+        let events = finesse_events(&mut tetris);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].0 > events[0].1, "{:?}", events);
+        assert_eq!(tetris.finesse_faults(), 1);
+        // the shape locked anyways:
+        assert_eq!(tetris.sticky_bottom_shapes.len(), 1);
+    }
+
+    #[test]
+    fn strict_mode_restarts_the_shape() {
+        let mut tetris = game(FinesseMode::Strict);
+        let spawn: HashSet<XY> = tetris.current_shape.get_pixels().collect();
+        // one step left and back again is two inputs too many:
+        tetris.move_current_shape(Direction::Left);
+        tetris.move_current_shape(Direction::Right);
+        tetris.hard_drop();
+        assert_eq!(tetris.finesse_faults(), 1);
+        assert!(tetris.sticky_bottom_shapes.is_empty());
+        assert_eq!(
+            tetris.current_shape.get_pixels().collect::<HashSet<_>>(),
+            spawn
+        );
+        // the second try is fine:
+        tetris.hard_drop();
+        assert_eq!(tetris.finesse_faults(), 1);
+        assert_eq!(tetris.sticky_bottom_shapes.len(), 1);
+    }
+
+    #[test]
+    fn strict_mode_restarts_clear_of_garbage() {
+        let mut tetris = game(FinesseMode::Strict);
+        let spawn: Vec<XY> = tetris.current_shape.get_pixels().collect();
+        // garbage that does not reach the spawn, then a fault: back to the (free) spawn
+        tetris.move_current_shape(Direction::Left);
+        tetris.move_current_shape(Direction::Right);
+        tetris.add_garbage(&[0]);
+        tetris.hard_drop();
+        assert_eq!(tetris.finesse_faults(), 1);
+        assert_eq!(tetris.current_shape.get_pixels().collect::<Vec<_>>(), spawn);
+        assert!(tetris.fits(&tetris.current_shape));
+
+        // a column right under the spawn, that the garbage pushes into it:
+        let column = spawn
+            .iter()
+            .flat_map(|xy| (2..19).map(move |y| XY(xy.0, y)))
+            .collect::<HashSet<_>>();
+        tetris
+            .sticky_bottom_shapes
+            .push(Shape::new(0, PieceKind::Garbage, column, XY(0, 0)));
+        while tetris.move_current_shape(Direction::Left) {}
+        tetris.add_garbage(&[9, 9]);
+        assert!(!tetris.game_over);
+        let locked = tetris.sticky_bottom_shapes.len();
+        tetris.hard_drop();
+        // the shape can not start over inside the board, it locks where it got to:
+        assert_eq!(tetris.sticky_bottom_shapes.len(), locked + 1);
+        assert_eq!(tetris.finesse_faults(), 1);
+        assert!(tetris.fits(&tetris.current_shape) || tetris.game_over);
+    }
+
+    #[test]
+    fn off_does_not_count() {
+        let mut tetris = game(FinesseMode::Off);
+        tetris.move_current_shape(Direction::Left);
+        tetris.move_current_shape(Direction::Right);
+        tetris.hard_drop();
+        assert!(finesse_events(&mut tetris).is_empty());
+        assert_eq!(tetris.finesse_faults(), 0);
+    }
+}
@@ -0,0 +1,893 @@
+use super::position::Position;
+use super::shapes::{PieceKind, Shape, XY};
+use super::{Settings, Tetris};
+
+/*
+    Fumen (v115), the format the community shares positions in: "v115@vhAAgH" is one empty page.
+        - after the prefix every character is a number 0..63 (TABLE), bigger numbers take a few of them (lowest first)
+        - every page is: the field, the piece of the page, and (if it changed) the comment
+        - the field is 23 rows of 10 plus a garbage row below, stored as the difference to the field of the page before
+          (after that pages piece locked and full lines cleared), run length encoded
+        - a page with the same field as the one before only stores a repeat count, following ones count up that one
+        - comments are escape()d like in javascript and packed 4 characters into 5 numbers (COMMENT_TABLE)
+        - quiz comments like "#Q=[L](T)IOJ" give the hold, the current piece and the queue (see Quiz)
+    Fumen fields are always 10 wide, y counts from the bottom here (y = -1 is the garbage row).
+    Multi page fumens turn into one Position per page, like a sequence of puzzles.
+*/
+
+/// width of a fumen field
+pub const WIDTH: i32 = 10;
+/// rows above the garbage row
+const TOP: i32 = 23;
+/// cells of a field, including the garbage row
+const BLOCKS: usize = ((TOP + 1) * WIDTH) as usize;
+
+const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+/// longest comment (after escaping) a page can have
+const MAX_COMMENT: usize = 4095;
+
+#[derive(Debug, PartialEq)]
+pub enum FumenError {
+    /// not a v115 fumen
+    Version,
+    /// a character that is not part of the encoding
+    Character(char),
+    /// the data ended in the middle of a page
+    Truncated,
+    /// the data does not describe a valid field or piece
+    Invalid(String),
+    /// fumen fields are always 10 wide
+    Width(i32),
+}
+
+impl std::fmt::Display for FumenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FumenError::Version => write!(f, "not a v115 fumen"),
+            FumenError::Character(c) => write!(f, "invalid fumen character '{}'", c),
+            FumenError::Truncated => write!(f, "fumen data ends too early"),
+            FumenError::Invalid(err) => write!(f, "invalid fumen: {}", err),
+            FumenError::Width(width) => write!(f, "fumen boards are 10 wide, not {}", width),
+        }
+    }
+}
+
+impl std::error::Error for FumenError {}
+
+/// How a piece is turned, from where it spawns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::Spawn,
+        Rotation::Right,
+        Rotation::Reverse,
+        Rotation::Left,
+    ];
+
+    /// how often the spawn orientation got turned clockwise
+    pub fn turns(self) -> usize {
+        self as usize
+    }
+
+    fn code(self) -> u32 {
+        match self {
+            Rotation::Reverse => 0,
+            Rotation::Right => 1,
+            Rotation::Spawn => 2,
+            Rotation::Left => 3,
+        }
+    }
+
+    fn from_code(code: u32) -> Self {
+        match code {
+            0 => Rotation::Reverse,
+            1 => Rotation::Right,
+            2 => Rotation::Spawn,
+            _ => Rotation::Left,
+        }
+    }
+}
+
+// the number a kind has in fumen (0 is empty)
+fn kind_code(kind: Option<PieceKind>) -> u32 {
+    match kind {
+        None => 0,
+        Some(PieceKind::I) => 1,
+        Some(PieceKind::L) => 2,
+        Some(PieceKind::O) => 3,
+        Some(PieceKind::Z) => 4,
+        Some(PieceKind::T) => 5,
+        Some(PieceKind::J) => 6,
+        Some(PieceKind::S) => 7,
+        Some(PieceKind::Garbage) => 8,
+    }
+}
+
+fn kind_of_code(code: u32) -> Option<PieceKind> {
+    [
+        PieceKind::I,
+        PieceKind::L,
+        PieceKind::O,
+        PieceKind::Z,
+        PieceKind::T,
+        PieceKind::J,
+        PieceKind::S,
+        PieceKind::Garbage,
+    ]
+    .get((code as usize).wrapping_sub(1))
+    .copied()
+}
+
+/// The piece of a page: its kind, how it is turned, and where its rotation center is (SRS, y from the bottom)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Piece {
+    pub kind: PieceKind,
+    pub rotation: Rotation,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Piece {
+    // the cells around the center (y up) when it spawns
+    fn spawn_blocks(kind: PieceKind) -> Option<[XY; 4]> {
+        let blocks = match kind {
+            PieceKind::I => [XY(0, 0), XY(-1, 0), XY(1, 0), XY(2, 0)],
+            PieceKind::L => [XY(0, 0), XY(-1, 0), XY(1, 0), XY(1, 1)],
+            PieceKind::O => [XY(0, 0), XY(1, 0), XY(0, 1), XY(1, 1)],
+            PieceKind::Z => [XY(0, 0), XY(1, 0), XY(0, 1), XY(-1, 1)],
+            PieceKind::T => [XY(0, 0), XY(-1, 0), XY(1, 0), XY(0, 1)],
+            PieceKind::J => [XY(0, 0), XY(-1, 0), XY(1, 0), XY(-1, 1)],
+            PieceKind::S => [XY(0, 0), XY(-1, 0), XY(0, 1), XY(1, 1)],
+            PieceKind::Garbage => return None,
+        };
+        Some(blocks)
+    }
+
+    fn blocks(kind: PieceKind, rotation: Rotation) -> Option<[XY; 4]> {
+        let blocks = Self::spawn_blocks(kind)?;
+        Some(blocks.map(|XY(x, y)| match rotation {
+            Rotation::Spawn => XY(x, y),
+            Rotation::Right => XY(y, -x),
+            Rotation::Reverse => XY(-x, -y),
+            Rotation::Left => XY(-y, x),
+        }))
+    }
+
+    /// the cells of the piece (x to the right, y from the bottom)
+    pub fn cells(&self) -> [XY; 4] {
+        Self::blocks(self.kind, self.rotation)
+            .expect("pieces are never garbage")
+            .map(|XY(x, y)| XY(self.x + x, self.y + y))
+    }
+
+    /// the piece covering exactly these cells (y from the bottom), None if no tetromino does
+    pub fn covering(kind: PieceKind, cells: &[XY]) -> Option<Self> {
+        let first = cells.iter().min_by_key(|XY(x, y)| (*y, *x))?;
+        Rotation::ALL.into_iter().find_map(|rotation| {
+            let blocks = Self::blocks(kind, rotation)?;
+            let lowest = blocks.iter().min_by_key(|XY(x, y)| (*y, *x))?;
+            let piece = Piece {
+                kind,
+                rotation,
+                x: first.0 - lowest.0,
+                y: first.1 - lowest.1,
+            };
+            let covered = piece.cells();
+            (cells.len() == 4 && cells.iter().all(|xy| covered.contains(xy))).then_some(piece)
+        })
+    }
+
+    // fumen stores some pieces around a different center than SRS
+    fn offset(&self) -> XY {
+        match (self.kind, self.rotation) {
+            (PieceKind::O, Rotation::Left) => XY(1, -1),
+            (PieceKind::O, Rotation::Reverse) => XY(1, 0),
+            (PieceKind::O, Rotation::Spawn) => XY(0, -1),
+            (PieceKind::I, Rotation::Reverse) => XY(1, 0),
+            (PieceKind::I, Rotation::Left) => XY(0, -1),
+            (PieceKind::S, Rotation::Spawn) => XY(0, -1),
+            (PieceKind::S, Rotation::Right) => XY(-1, 0),
+            (PieceKind::Z, Rotation::Spawn) => XY(0, -1),
+            (PieceKind::Z, Rotation::Left) => XY(1, 0),
+            _ => XY(0, 0),
+        }
+    }
+}
+
+/// What a quiz comment ("#Q=[hold](current)queue") says about the pieces
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Quiz {
+    pub hold: Option<PieceKind>,
+    pub current: Option<PieceKind>,
+    pub queue: Vec<PieceKind>,
+}
+
+impl Quiz {
+    /// None if the comment is no quiz
+    pub fn parse(comment: &str) -> Option<Self> {
+        let rest = comment.strip_prefix("#Q=")?;
+        let piece = |c: char| PieceKind::from_letter(c).filter(|kind| *kind != PieceKind::Garbage);
+        let (hold, rest) = rest.strip_prefix('[')?.split_once(']')?;
+        let (current, rest) = rest.strip_prefix('(')?.split_once(')')?;
+        let queue = rest.split(';').next().unwrap_or_default();
+        Some(Self {
+            hold: hold.chars().next().and_then(piece),
+            current: current.chars().next().and_then(piece),
+            queue: queue.chars().map_while(piece).collect(),
+        })
+    }
+
+    pub fn comment(&self) -> String {
+        let letter = |kind: Option<PieceKind>| {
+            kind.map(|kind| kind.letter().to_string())
+                .unwrap_or_default()
+        };
+        let queue: String = self.queue.iter().map(|kind| kind.letter()).collect();
+        format!(
+            "#Q=[{}]({}){}",
+            letter(self.hold),
+            letter(self.current),
+            queue
+        )
+    }
+}
+
+/// One page of a fumen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    /// row by row from the top, the last row is the garbage row
+    field: Vec<Option<PieceKind>>,
+    pub piece: Option<Piece>,
+    pub comment: String,
+    /// the piece locks before the next page (and full lines get cleared)
+    pub lock: bool,
+    /// the garbage row rises into the field before the next page
+    pub rise: bool,
+    /// the field gets mirrored before the next page
+    pub mirror: bool,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            field: vec![None; BLOCKS],
+            piece: None,
+            comment: String::new(),
+            lock: true,
+            rise: false,
+            mirror: false,
+        }
+    }
+}
+
+impl Page {
+    fn index(XY(x, y): XY) -> Option<usize> {
+        ((0..WIDTH).contains(&x) && (-1..TOP).contains(&y))
+            .then(|| ((TOP - 1 - y) * WIDTH + x) as usize)
+    }
+
+    /// the cell at x, y (from the bottom, -1 is the garbage row)
+    pub fn get(&self, xy: XY) -> Option<PieceKind> {
+        Self::index(xy).and_then(|idx| self.field[idx])
+    }
+
+    pub fn set(&mut self, xy: XY, kind: Option<PieceKind>) {
+        if let Some(idx) = Self::index(xy) {
+            self.field[idx] = kind;
+        }
+    }
+
+    /// The page of a game: its board (the top rows of boards higher than fumen fields get cut),
+    /// the current shape as the piece, and the comment
+    pub fn of(tetris: &Tetris, comment: &str) -> Result<Self, FumenError> {
+        let (width, height) = tetris.get_size();
+        if width != WIDTH {
+            return Err(FumenError::Width(width));
+        }
+        let to_fumen = |XY(x, y): XY| XY(x, height - 1 - y);
+        let mut page = Page {
+            comment: comment.to_string(),
+            ..Default::default()
+        };
+        for shape in &tetris.sticky_bottom_shapes {
+            for xy in shape.get_pixels() {
+                page.set(to_fumen(xy), Some(shape.get_kind()));
+            }
+        }
+        // (on boards higher than the field the piece can spawn above it, then there is no piece)
+        let cells: Vec<XY> = tetris.current_shape.get_pixels().map(to_fumen).collect();
+        let on_field = cells
+            .iter()
+            .all(|XY(x, y)| (0..WIDTH).contains(x) && (0..TOP).contains(y));
+        if tetris.is_falling() && !tetris.is_game_over() && on_field {
+            page.piece = Piece::covering(tetris.current_shape.get_kind(), &cells);
+        }
+        Ok(page)
+    }
+
+    /// the quiz of the comment, if it is one
+    pub fn quiz(&self) -> Option<Quiz> {
+        Quiz::parse(&self.comment)
+    }
+
+    /// The page as a position on a board of that height: the field, the current piece and the queue of the quiz
+    pub fn position(&self, height: u32) -> Position {
+        let mut position = Position::empty(WIDTH as u32, height);
+        for y in 0..TOP {
+            for x in 0..WIDTH {
+                position.set(XY(x, height as i32 - 1 - y), self.get(XY(x, y)));
+            }
+        }
+        let quiz = self.quiz().unwrap_or_default();
+        position.current = self.piece.map(|piece| piece.kind).or(quiz.current);
+        position.queue = quiz.queue;
+        position.hold = quiz.hold;
+        position
+    }
+
+    /// A game starting from the page, with the current shape where the piece of the page is (if it fits there)
+    pub fn to_tetris(&self, height: u32, settings: Settings) -> Tetris {
+        let mut tetris = self.position(height).start(settings);
+        if let Some(piece) = self.piece {
+            let cells: Vec<XY> = piece
+                .cells()
+                .iter()
+                .map(|XY(x, y)| XY(*x, height as i32 - 1 - y))
+                .collect();
+            let mut shape = tetris.current_shape.clone();
+            for _ in 0..piece.rotation.turns() {
+                shape = shape.rotated_shape();
+            }
+            let placed = move_onto(&shape, &cells);
+            if shape.get_kind() == piece.kind && tetris.fits(&placed) {
+                tetris.finesse.spawned(&placed);
+                tetris.current_shape = placed;
+            }
+        }
+        tetris
+    }
+
+    // the field of the next page: piece locked, full lines cleared, risen and mirrored
+    fn next_field(&self) -> Vec<Option<PieceKind>> {
+        let mut page = self.clone();
+        if let (true, Some(piece)) = (self.lock, self.piece) {
+            for xy in piece.cells() {
+                page.set(xy, Some(piece.kind));
+            }
+        }
+        let full = |page: &Page, y: i32| (0..WIDTH).all(|x| page.get(XY(x, y)).is_some());
+        let rows: Vec<Vec<Option<PieceKind>>> = (0..TOP)
+            .filter(|y| !full(&page, *y))
+            .map(|y| (0..WIDTH).map(|x| page.get(XY(x, y))).collect())
+            .collect();
+        let garbage: Vec<Option<PieceKind>> = (0..WIDTH).map(|x| page.get(XY(x, -1))).collect();
+        let mut rows: Vec<_> = if self.rise {
+            std::iter::once(garbage.clone()).chain(rows).collect()
+        } else {
+            rows
+        };
+        rows.resize(TOP as usize, vec![None; WIDTH as usize]);
+        if self.mirror {
+            rows.iter_mut().for_each(|row| row.reverse());
+        }
+        let mut next = Page::default();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, kind) in row.iter().enumerate() {
+                next.set(XY(x as i32, y as i32), *kind);
+            }
+        }
+        for (x, kind) in garbage.into_iter().enumerate() {
+            next.set(XY(x as i32, -1), kind);
+        }
+        next.field
+    }
+}
+
+// the shape moved so it covers the cells (the same cells, just somewhere else)
+fn move_onto(shape: &Shape, cells: &[XY]) -> Shape {
+    let lowest = |cells: &mut dyn Iterator<Item = XY>| {
+        cells.min_by_key(|XY(x, y)| (*y, *x)).unwrap_or(XY(0, 0))
+    };
+    let XY(x, y) = lowest(&mut cells.iter().copied());
+    let XY(sx, sy) = lowest(&mut shape.get_pixels());
+    shape + XY(x - sx, y - sy)
+}
+
+// javascript escape(): letters, digits and @*_+-./ stay, everything else becomes %XX or %uXXXX
+fn escape(text: &str) -> String {
+    text.encode_utf16()
+        .map(|unit| match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => c.to_string(),
+            _ if unit < 256 => format!("%{:02X}", unit),
+            _ => format!("%u{:04X}", unit),
+        })
+        .collect()
+}
+
+fn unescape(text: &str) -> String {
+    let mut units: Vec<u16> = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let hex = |digits: &str| u16::from_str_radix(digits, 16).ok();
+        let escaped = match rest.strip_prefix("%u") {
+            Some(tail) => tail.get(..4).and_then(hex).map(|unit| (unit, 6)),
+            None => rest
+                .strip_prefix('%')
+                .and_then(|tail| tail.get(..2))
+                .and_then(hex)
+                .map(|unit| (unit, 3)),
+        };
+        match escaped {
+            Some((unit, len)) => {
+                units.push(unit);
+                rest = &rest[len..];
+            }
+            None => {
+                units.extend(c.encode_utf16(&mut [0; 2]).iter());
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+// the numbers of the encoding, written as characters at the end
+#[derive(Default)]
+struct Writer {
+    values: Vec<u32>,
+}
+
+impl Writer {
+    fn push(&mut self, mut value: u32, digits: usize) {
+        for _ in 0..digits {
+            self.values.push(value % 64);
+            value /= 64;
+        }
+    }
+
+    fn finish(self) -> String {
+        let data: Vec<char> = self
+            .values
+            .iter()
+            .map(|value| TABLE[*value as usize] as char)
+            .collect();
+        // like the fumen site: a '?' after the first 42 characters, then after every 47
+        let mut out = String::from("v115@");
+        for (idx, c) in data.iter().enumerate() {
+            if idx >= 42 && (idx - 42) % 47 == 0 {
+                out.push('?');
+            }
+            out.push(*c);
+        }
+        out
+    }
+}
+
+struct Reader {
+    values: Vec<u32>,
+    next: usize,
+}
+
+impl Reader {
+    fn is_empty(&self) -> bool {
+        self.next >= self.values.len()
+    }
+
+    fn poll(&mut self, digits: usize) -> Result<u32, FumenError> {
+        let values = self
+            .values
+            .get(self.next..self.next + digits)
+            .ok_or(FumenError::Truncated)?;
+        self.next += digits;
+        Ok(values
+            .iter()
+            .rev()
+            .fold(0, |value, digit| value * 64 + digit))
+    }
+}
+
+/// Encodes the pages as a v115 fumen
+pub fn encode(pages: &[Page]) -> String {
+    let mut writer = Writer::default();
+    let mut prev_field = vec![None; BLOCKS];
+    let mut prev_comment = String::new();
+    // where the repeat count of unchanged fields is, while it can still count up:
+    let mut repeat: Option<usize> = None;
+
+    for (idx, page) in pages.iter().enumerate() {
+        // the field, as runs of the same difference:
+        let diffs: Vec<u32> = page
+            .field
+            .iter()
+            .zip(&prev_field)
+            .map(|(kind, prev)| kind_code(*kind) + 8 - kind_code(*prev))
+            .collect();
+        let changed = diffs.iter().any(|diff| *diff != 8);
+        match repeat {
+            Some(at) if !changed && writer.values[at] < 63 => writer.values[at] += 1,
+            _ => {
+                let mut start = 0;
+                while start < BLOCKS {
+                    let run = diffs[start..]
+                        .iter()
+                        .take_while(|diff| **diff == diffs[start])
+                        .count();
+                    writer.push(diffs[start] * BLOCKS as u32 + run as u32 - 1, 2);
+                    start += run;
+                }
+                repeat = None;
+                if !changed {
+                    writer.push(0, 1);
+                    repeat = Some(writer.values.len() - 1);
+                }
+            }
+        }
+
+        // the piece and the flags:
+        let comment = escape(&page.comment);
+        let comment = &comment[..comment.len().min(MAX_COMMENT)];
+        let has_comment = page.comment != prev_comment;
+        // (a piece off the field gets left out)
+        let piece = page.piece.and_then(|piece| {
+            let XY(dx, dy) = piece.offset();
+            let location = (TOP - (piece.y + dy) - 1) * WIDTH + piece.x + dx;
+            (0..BLOCKS as i32)
+                .contains(&location)
+                .then_some((piece, location as u32))
+        });
+        let (kind, rotation, location) = match piece {
+            Some((piece, location)) => {
+                (kind_code(Some(piece.kind)), piece.rotation.code(), location)
+            }
+            None => (0, 0, 0),
+        };
+        let flags = [!page.lock, has_comment, idx == 0, page.mirror, page.rise]
+            .into_iter()
+            .fold(0, |flags, flag| flags * 2 + flag as u32);
+        writer.push(
+            ((flags * BLOCKS as u32 + location) * 4 + rotation) * 8 + kind,
+            3,
+        );
+
+        if has_comment {
+            writer.push(comment.len() as u32, 2);
+            for chunk in comment.as_bytes().chunks(4) {
+                let value = chunk.iter().rev().fold(0, |value, c| {
+                    let code = COMMENT_TABLE.iter().position(|t| t == c).unwrap_or(0) as u32;
+                    value * (COMMENT_TABLE.len() as u32 + 1) + code
+                });
+                writer.push(value, 5);
+            }
+            prev_comment = page.comment.clone();
+        }
+        prev_field = page.next_field();
+    }
+    writer.finish()
+}
+
+/// Decodes a v115 fumen (also "m115@"/"d115@", or a whole url with one in it) into its pages
+pub fn decode(fumen: &str) -> Result<Vec<Page>, FumenError> {
+    let data = ["v115@", "m115@", "d115@"]
+        .iter()
+        .find_map(|prefix| fumen.find(prefix).map(|at| &fumen[at + prefix.len()..]))
+        .ok_or(FumenError::Version)?;
+    let values = data
+        .trim()
+        .chars()
+        .filter(|c| *c != '?')
+        .map(|c| {
+            TABLE
+                .iter()
+                .position(|t| *t as char == c)
+                .map(|value| value as u32)
+                .ok_or(FumenError::Character(c))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut reader = Reader { values, next: 0 };
+
+    let mut pages = vec![];
+    let mut prev_field = vec![None; BLOCKS];
+    let mut prev_comment = String::new();
+    let mut repeat = 0;
+    while !reader.is_empty() {
+        let mut page = Page::default();
+        if repeat > 0 {
+            page.field = prev_field.clone();
+            repeat -= 1;
+        } else {
+            let mut cell = 0;
+            let mut changed = false;
+            while cell < BLOCKS {
+                let value = reader.poll(2)?;
+                let (diff, run) = (value / BLOCKS as u32, (value % BLOCKS as u32) as usize + 1);
+                if cell + run > BLOCKS || diff > 16 {
+                    return Err(FumenError::Invalid("field does not add up".into()));
+                }
+                changed |= diff != 8;
+                let cells = page.field[cell..cell + run]
+                    .iter_mut()
+                    .zip(&prev_field[cell..]);
+                for (kind, prev) in cells {
+                    let code = kind_code(*prev) as i32 + diff as i32 - 8;
+                    if !(0..=8).contains(&code) {
+                        return Err(FumenError::Invalid("cell out of range".into()));
+                    }
+                    *kind = kind_of_code(code as u32);
+                }
+                cell += run;
+            }
+            if !changed {
+                repeat = reader.poll(1)?;
+            }
+        }
+
+        let action = reader.poll(3)?;
+        let kind = action % 8;
+        let rotation = Rotation::from_code(action / 8 % 4);
+        let location = (action / 32 % BLOCKS as u32) as i32;
+        let flags = action / 32 / BLOCKS as u32;
+        page.rise = flags & 1 != 0;
+        page.mirror = flags & 2 != 0;
+        let has_comment = flags & 8 != 0;
+        page.lock = flags & 16 == 0;
+        page.piece = match kind_of_code(kind) {
+            None => None,
+            Some(PieceKind::Garbage) => return Err(FumenError::Invalid("gray piece".into())),
+            Some(kind) => {
+                let mut piece = Piece {
+                    kind,
+                    rotation,
+                    x: location % WIDTH,
+                    y: TOP - 1 - location / WIDTH,
+                };
+                let XY(dx, dy) = piece.offset();
+                piece.x -= dx;
+                piece.y -= dy;
+                Some(piece)
+            }
+        };
+
+        if has_comment {
+            let len = reader.poll(2)? as usize;
+            let mut escaped = String::new();
+            for _ in 0..len.div_ceil(4) {
+                let mut value = reader.poll(5)?;
+                for _ in 0..4 {
+                    let code = value % (COMMENT_TABLE.len() as u32 + 1);
+                    escaped.push(*COMMENT_TABLE.get(code as usize).unwrap_or(&b' ') as char);
+                    value /= COMMENT_TABLE.len() as u32 + 1;
+                }
+            }
+            escaped.truncate(len);
+            prev_comment = unescape(&escaped);
+        }
+        page.comment = prev_comment.clone();
+        prev_field = page.next_field();
+        pages.push(page);
+    }
+    Ok(pages)
+}
+
+/// the game as a one page fumen, with the comment on it
+pub fn encode_tetris(tetris: &Tetris, comment: &str) -> Result<String, FumenError> {
+    Ok(encode(&[Page::of(tetris, comment)?]))
+}
+
+/// a game from the first page of the fumen (see Page::to_tetris())
+pub fn decode_tetris(fumen: &str, height: u32, settings: Settings) -> Result<Tetris, FumenError> {
+    let pages = decode(fumen)?;
+    let page = pages.first().ok_or(FumenError::Truncated)?;
+    Ok(page.to_tetris(height, settings))
+}
+
+/// every page of the fumen as a position, like a sequence of puzzles
+pub fn decode_positions(fumen: &str, height: u32) -> Result<Vec<Position>, FumenError> {
+    Ok(decode(fumen)?
+        .iter()
+        .map(|page| page.position(height))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_pages() {
+        let pages = decode("v115@vhAAgH").unwrap();
+        assert_eq!(pages, vec![Page::default()]);
+        assert_eq!(encode(&pages), "v115@vhAAgH");
+        // the second page only counts up the repeat of the first one:
+        let two = vec![Page::default(), Page::default()];
+        assert_eq!(encode(&two), "v115@vhBAgHAAA");
+        assert_eq!(decode("v115@vhBAgHAAA").unwrap(), two);
+    }
+
+    #[test]
+    fn known_field() {
+        // 4 rows of 6 garbage cells at the bottom left:
+        let fumen = "v115@9gF8DeF8DeF8DeF8NeAgH";
+        let pages = decode(fumen).unwrap();
+        assert_eq!(pages.len(), 1);
+        for y in 0..TOP {
+            for x in 0..WIDTH {
+                let expected = (x < 6 && y < 4).then_some(PieceKind::Garbage);
+                assert_eq!(pages[0].get(XY(x, y)), expected, "{} {}", x, y);
+            }
+        }
+        assert_eq!(encode(&pages), fumen);
+        // also from a link, with the line breaks of the fumen site:
+        assert_eq!(
+            decode("https://fumen.zui.jp/?v115@9gF8DeF8De?F8DeF8NeAgH").unwrap(),
+            pages
+        );
+    }
+
+    #[test]
+    fn known_piece_and_comment() {
+        // a T at the bottom, pointing up:
+        let pages = decode("v115@vhAVQJ").unwrap();
+        let t = Piece {
+            kind: PieceKind::T,
+            rotation: Rotation::Spawn,
+            x: 4,
+            y: 0,
+        };
+        assert_eq!(pages[0].piece, Some(t));
+        assert_eq!(encode(&pages), "v115@vhAVQJ");
+        // an O stores its lower left cell, not the SRS center:
+        let o = Piece {
+            kind: PieceKind::O,
+            rotation: Rotation::Spawn,
+            x: 4,
+            y: 1,
+        };
+        let page = Page {
+            piece: Some(o),
+            ..Default::default()
+        };
+        assert_eq!(encode(&[page]), "v115@vhATQJ");
+        // "hi" as a comment:
+        let pages = decode("v115@vhAAgWCAouBAA").unwrap();
+        assert_eq!(pages[0].comment, "hi");
+        assert_eq!(encode(&pages), "v115@vhAAgWCAouBAA");
+    }
+
+    #[test]
+    fn pieces_and_comments_round_trip() {
+        let mut first = Page {
+            comment: "T-spin double: 100% (ok?) ✓".into(),
+            ..Default::default()
+        };
+        first.set(XY(0, 0), Some(PieceKind::Garbage));
+        first.set(XY(0, -1), Some(PieceKind::Garbage));
+        let mut pages = vec![first];
+        for (kind, rotation, x, y) in [
+            (PieceKind::T, Rotation::Spawn, 1, 10),
+            (PieceKind::I, Rotation::Left, 4, 10),
+            (PieceKind::O, Rotation::Reverse, 6, 10),
+            (PieceKind::S, Rotation::Right, 8, 10),
+            (PieceKind::Z, Rotation::Spawn, 4, 15),
+        ] {
+            let field = pages.last().unwrap().next_field();
+            pages.push(Page {
+                field,
+                piece: Some(Piece {
+                    kind,
+                    rotation,
+                    x,
+                    y,
+                }),
+                comment: "#Q=[L](T)IOJ".into(),
+                ..Default::default()
+            });
+        }
+        let fumen = encode(&pages);
+        let decoded = decode(&fumen).unwrap();
+        assert_eq!(decoded.len(), pages.len());
+        for (decoded, page) in decoded.iter().zip(&pages) {
+            assert_eq!(decoded, page);
+        }
+        // the pieces locked, every page builds on the one before:
+        assert_eq!(decoded[5].get(XY(8, 10)), Some(PieceKind::S));
+        assert_eq!(decoded[5].get(XY(4, 12)), Some(PieceKind::I));
+        assert_eq!(decoded[5].get(XY(0, -1)), Some(PieceKind::Garbage));
+        assert!(fumen.contains('?'));
+    }
+
+    #[test]
+    fn quiz_comments() {
+        let quiz = Quiz::parse("#Q=[L](T)IOJ").unwrap();
+        assert_eq!(quiz.hold, Some(PieceKind::L));
+        assert_eq!(quiz.current, Some(PieceKind::T));
+        assert_eq!(quiz.queue, vec![PieceKind::I, PieceKind::O, PieceKind::J]);
+        assert_eq!(quiz.comment(), "#Q=[L](T)IOJ");
+        assert_eq!(Quiz::parse("#Q=[](I)").unwrap().hold, None);
+        assert_eq!(Quiz::parse("just a comment"), None);
+    }
+
+    #[test]
+    fn games_round_trip() {
+        let mut position = Position::empty(10, 20);
+        for x in 0..9 {
+            position.set(XY(x, 19), Some(PieceKind::Garbage));
+        }
+        position.current = Some(PieceKind::T);
+        position.queue = vec![PieceKind::I];
+        let mut tetris = position.start(Settings::default());
+        tetris.move_rotate();
+        tetris.move_down();
+
+        let fumen = encode_tetris(&tetris, "hello").unwrap();
+        let page = &decode(&fumen).unwrap()[0];
+        assert_eq!(page.comment, "hello");
+        assert_eq!(
+            page.piece.map(|piece| (piece.kind, piece.rotation)),
+            Some((PieceKind::T, Rotation::Right))
+        );
+        assert_eq!(page.get(XY(0, 0)), Some(PieceKind::Garbage));
+
+        let back = decode_tetris(&fumen, 20, Settings::default()).unwrap();
+        let cells = |tetris: &Tetris| {
+            let mut cells: Vec<XY> = tetris.get_current_shape().get_pixels().collect();
+            cells.sort_by_key(|XY(x, y)| (*y, *x));
+            cells
+        };
+        assert_eq!(cells(&back), cells(&tetris));
+        assert_eq!(Position::of(&back).get(XY(3, 19)), Some(PieceKind::Garbage));
+        assert!(matches!(
+            encode_tetris(&Tetris::new(8, 20), ""),
+            Err(FumenError::Width(8))
+        ));
+
+        // on a 10x40 board the piece spawns above the fumen field, it gets left out:
+        let fumen = encode_tetris(&Tetris::new(10, 40), "").unwrap();
+        assert_eq!(decode(&fumen).unwrap()[0].piece, None);
+        let page = Page {
+            piece: Some(Piece {
+                kind: PieceKind::T,
+                rotation: Rotation::Spawn,
+                x: 4,
+                y: 30,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(decode(&encode(&[page])).unwrap()[0].piece, None);
+    }
+
+    #[test]
+    fn pages_turn_into_positions() {
+        let pages = vec![
+            Page {
+                comment: "#Q=[](T)IO".into(),
+                ..Default::default()
+            },
+            Page {
+                comment: "#Q=[T](I)O".into(),
+                ..Default::default()
+            },
+        ];
+        let positions = decode_positions(&encode(&pages), 20).unwrap();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].current, Some(PieceKind::T));
+        assert_eq!(positions[1].hold, Some(PieceKind::T));
+        assert_eq!(positions[1].queue, vec![PieceKind::O]);
+    }
+
+    #[test]
+    fn rejects_invalid_fumens() {
+        assert_eq!(decode("hello"), Err(FumenError::Version));
+        assert_eq!(decode("v115@vh!"), Err(FumenError::Character('!')));
+        assert_eq!(decode("v115@vhAA"), Err(FumenError::Truncated));
+        assert!(matches!(decode("v115@/hAAgH"), Err(FumenError::Invalid(_))));
+    }
+}
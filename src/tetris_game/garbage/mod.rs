@@ -0,0 +1,540 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use super::buf::RingBuffer;
+use super::event::{Event, MAX_EVENTS};
+use super::rng::Rng;
+use super::shapes::{PieceKind, Shape, XY};
+use super::timing::Phase;
+use super::Tetris;
+
+/*
+    Garbage: what clearing lines sends to the opponent, and how it arrives there.
+        - clears() turns the events of a board into one Clear per locked shape
+        - AttackTable says how many garbage lines a clear is worth (lines, T-spins, combos, back to back, perfect clears),
+          Attacker keeps track of the combo and back to back of one board while doing so
+        - Incoming is the meter of garbage waiting for a board: attacks of that board cancel it first,
+          the next lock that clears nothing lets all of it rise
+        - HolePlacement picks the hole of every garbage row, Tetris::add_garbage() pushes the board up by the rows
+        - exchange() is the step every mode shares for one clear: attack, cancel, let the rest rise
+          (the online hub runs it without a game, see online::hub)
+        - Board ties these together for one board of a match
+    The controllers (see versus and royale) decide who the attacks go to, the game itself knows nothing about opponents.
+*/
+
+#[derive(Debug, PartialEq)]
+pub enum AttackTableError {
+    /// the string is not a valid attack table
+    Parse(String),
+}
+
+impl std::fmt::Display for AttackTableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttackTableError::Parse(err) => write!(f, "invalid attack table: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AttackTableError {}
+
+/// How many lines of garbage a clear sends, loaded from json like
+/// { "lines": [0, 1, 2, 4], "t-spin": [2, 4, 6], "combo": [0, 1, 1, 2], "back-to-back": 1, "perfect-clear": 10 }
+/// (left out fields keep their default)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct AttackTable {
+    /// for clearing 1, 2, 3 and 4 lines
+    pub lines: [u32; 4],
+    /// for T-spins clearing 1, 2 and 3 lines (a T-spin without lines sends nothing)
+    pub t_spin: [u32; 3],
+    /// extra lines for the n-th clear in a row (0 is the first one), the last entry counts for longer combos
+    pub combo: Vec<u32>,
+    /// extra lines for a tetris or T-spin clear right after another one
+    pub back_to_back: u32,
+    /// extra lines for clearing the whole board
+    pub perfect_clear: u32,
+}
+
+impl Default for AttackTable {
+    // the guideline values
+    fn default() -> Self {
+        Self {
+            lines: [0, 1, 2, 4],
+            t_spin: [2, 4, 6],
+            combo: vec![0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+            back_to_back: 1,
+            perfect_clear: 10,
+        }
+    }
+}
+
+impl AttackTable {
+    pub fn from_json(json: &str) -> Result<Self, AttackTableError> {
+        serde_json::from_str(json).map_err(|err| AttackTableError::Parse(err.to_string()))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("attack table always serializes")
+    }
+
+    /// lines the clear is worth, as the combo-th clear in a row (0 for the first) and with or without back to back
+    pub fn attack(&self, clear: Clear, combo: u32, back_to_back: bool) -> u32 {
+        if clear.lines == 0 {
+            return 0;
+        }
+        let base = if clear.t_spin {
+            self.t_spin[clear.lines.min(3) as usize - 1]
+        } else {
+            self.lines[clear.lines.min(4) as usize - 1]
+        };
+        let combo = self
+            .combo
+            .get(combo as usize)
+            .or(self.combo.last())
+            .copied()
+            .unwrap_or(0);
+        let back_to_back = if back_to_back && clear.is_difficult() {
+            self.back_to_back
+        } else {
+            0
+        };
+        let perfect_clear = if clear.perfect_clear {
+            self.perfect_clear
+        } else {
+            0
+        };
+        base + combo + back_to_back + perfect_clear
+    }
+}
+
+/// What a locked shape did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Clear {
+    pub kind: Option<PieceKind>,
+    pub lines: u32,
+    pub t_spin: bool,
+    pub perfect_clear: bool,
+}
+
+impl Clear {
+    /// tetrises and T-spins with lines keep back to back going
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.t_spin && self.lines > 0)
+    }
+}
+
+/// one Clear per shape that locked in the events (the events of a lock always come together)
+pub fn clears(events: &[Event]) -> Vec<Clear> {
+    let mut clears: Vec<Clear> = vec![];
+    for event in events {
+        match (*event, clears.last_mut()) {
+            (Event::Lock(kind), _) => clears.push(Clear {
+                kind: Some(kind),
+                ..Default::default()
+            }),
+            (Event::LineClear { lines, .. }, Some(clear)) => clear.lines = lines,
+            (Event::TSpin { .. }, Some(clear)) => clear.t_spin = true,
+            (Event::PerfectClear, Some(clear)) => clear.perfect_clear = true,
+            _ => {}
+        }
+    }
+    clears
+}
+
+/// The combo and back to back of one board
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attacker {
+    /// clears in a row so far, None after a lock that cleared nothing
+    combo: Option<u32>,
+    back_to_back: bool,
+}
+
+impl Attacker {
+    /// the attack of the clear (the combo goes on or breaks, back to back too)
+    pub fn attack(&mut self, table: &AttackTable, clear: Clear) -> u32 {
+        if clear.lines == 0 {
+            self.combo = None;
+            return 0;
+        }
+        let combo = self.combo.map_or(0, |combo| combo + 1);
+        let attack = table.attack(clear, combo, self.back_to_back);
+        self.combo = Some(combo);
+        self.back_to_back = clear.is_difficult();
+        attack
+    }
+
+    /// the current combo (0 for a single clear, None if there is none)
+    pub fn combo(&self) -> Option<u32> {
+        self.combo
+    }
+
+    pub fn is_back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+}
+
+/// The garbage meter of a board: attacks waiting to rise, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Incoming {
+    attacks: VecDeque<u32>,
+}
+
+impl Incoming {
+    /// lines waiting in total
+    pub fn total(&self) -> u32 {
+        self.attacks.iter().sum()
+    }
+
+    pub fn attacks(&self) -> impl Iterator<Item = u32> + '_ {
+        self.attacks.iter().copied()
+    }
+
+    pub fn push(&mut self, lines: u32) {
+        if lines > 0 {
+            self.attacks.push_back(lines);
+        }
+    }
+
+    /// cancels waiting garbage (oldest first) with an attack, returns what is left of the attack
+    pub fn cancel(&mut self, mut attack: u32) -> u32 {
+        while let Some(oldest) = self.attacks.front_mut() {
+            if attack == 0 {
+                break;
+            }
+            let cancelled = attack.min(*oldest);
+            attack -= cancelled;
+            *oldest -= cancelled;
+            if *oldest == 0 {
+                self.attacks.pop_front();
+            }
+        }
+        attack
+    }
+
+    /// all waiting attacks (oldest first), the meter is empty afterwards
+    pub fn take(&mut self) -> Vec<u32> {
+        self.attacks.drain(..).collect()
+    }
+}
+
+/// Where the holes of garbage rows go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HolePlacement {
+    /// one random column for all rows of an attack
+    #[default]
+    PerAttack,
+    /// a random column for every row ("cheese")
+    PerRow,
+    /// always the same column
+    Column(i32),
+}
+
+impl HolePlacement {
+    /// the hole of every row of the attacks (oldest attack first, top row first), on a board that wide
+    pub fn holes(self, attacks: &[u32], width: i32, rng: &mut dyn Rng) -> Vec<i32> {
+        let mut random = || rng.next_index(width.max(1) as usize) as i32;
+        match self {
+            HolePlacement::PerAttack => attacks
+                .iter()
+                .flat_map(|lines| std::iter::repeat_n(random(), *lines as usize))
+                .collect(),
+            HolePlacement::PerRow => (0..attacks.iter().sum::<u32>()).map(|_| random()).collect(),
+            HolePlacement::Column(x) => {
+                let rows = attacks.iter().sum::<u32>() as usize;
+                vec![x.clamp(0, width.max(1) - 1); rows]
+            }
+        }
+    }
+}
+
+/// The garbage exchange of one clear of a board: its attack (after boost(), for bonuses of the mode) cancels
+/// the incoming garbage first, a clear without lines lets all of the incoming garbage rise.
+/// Returns what is left of the attack for the opponents, and the attacks that rise now (oldest first).
+pub fn exchange(
+    attacker: &mut Attacker,
+    incoming: &mut Incoming,
+    table: &AttackTable,
+    clear: Clear,
+    boost: impl Fn(u32) -> u32,
+) -> (u32, Vec<u32>) {
+    let attack = boost(attacker.attack(table, clear));
+    let attack = incoming.cancel(attack);
+    let rising = if clear.lines == 0 {
+        incoming.take()
+    } else {
+        vec![]
+    };
+    (attack, rising)
+}
+
+/// One board of a match: the game, its combo and back to back, the garbage waiting for it and what happened on it
+#[derive(Debug)]
+pub struct Board {
+    pub tetris: Tetris,
+    pub attacker: Attacker,
+    pub incoming: Incoming,
+    /// lines sent so far (after cancelling)
+    pub sent: u32,
+    /// the events of the game, for a frontend (only the last MAX_EVENTS)
+    events: RingBuffer<Event>,
+}
+
+impl Board {
+    pub fn new(tetris: Tetris) -> Self {
+        Self {
+            tetris,
+            attacker: Attacker::default(),
+            incoming: Incoming::default(),
+            sent: 0,
+            events: RingBuffer::with_capacity(MAX_EVENTS).overwriting(),
+        }
+    }
+
+    /// Goes through every shape that locked since the last call (see exchange()), the garbage that rises
+    /// gets added to the game right away. Returns the attacks that are left for the opponents, in order.
+    pub fn exchange(
+        &mut self,
+        table: &AttackTable,
+        holes: HolePlacement,
+        rng: &mut dyn Rng,
+        boost: impl Fn(u32) -> u32,
+    ) -> Vec<u32> {
+        let events = self.tetris.take_events();
+        let mut attacks = vec![];
+        for clear in clears(&events) {
+            let (attack, rising) =
+                exchange(&mut self.attacker, &mut self.incoming, table, clear, &boost);
+            if attack > 0 {
+                self.sent += attack;
+                attacks.push(attack);
+            }
+            if !rising.is_empty() {
+                let width = self.tetris.get_size().0;
+                self.tetris.add_garbage(&holes.holes(&rising, width, rng));
+            }
+        }
+        for event in events.into_iter().chain(self.tetris.take_events()) {
+            self.events.push_overwrite(event);
+        }
+        attacks
+    }
+
+    /// everything that happened on the board since the last call, oldest first
+    pub fn take_events(&mut self) -> Vec<Event> {
+        let events = &mut self.events;
+        std::iter::from_fn(|| events.try_pop().ok()).collect()
+    }
+}
+
+impl Tetris {
+    /// Pushes the board up by a row of garbage per hole (top row first), every row full but its hole.
+    /// The falling shape gets pushed up with it if it has to. Anything pushed out at the top ends the game.
+    pub fn add_garbage(&mut self, holes: &[i32]) {
+        if self.game_over || holes.is_empty() {
+            return;
+        }
+        let rows = holes.len() as i32;
+        for shape in self.sticky_bottom_shapes.iter_mut() {
+            *shape = &*shape + XY(0, -rows);
+        }
+        let (width, height) = (self.width, self.height);
+        let cells = holes.iter().enumerate().flat_map(|(n, hole)| {
+            let y = height - rows + n as i32;
+            (0..width).filter(move |x| x != hole).map(move |x| XY(x, y))
+        });
+        self.sticky_bottom_shapes
+            .push(Shape::new(0, PieceKind::Garbage, cells, XY(0, 0)));
+        // rows waiting to be cleared moved up too:
+        if let Phase::LineClear { rows: clearing, .. } = &mut self.phase {
+            clearing.iter_mut().for_each(|y| *y -= rows);
+        }
+        self.emit(Event::Garbage { lines: rows as u32 });
+
+        let mut topped_out = self
+            .sticky_bottom_shapes
+            .iter()
+            .flat_map(|shape| shape.get_pixels())
+            .any(|xy| xy.1 < 0);
+        if !topped_out && self.is_falling() {
+            let pushed = self.pushed_up(&self.current_shape);
+            topped_out = !self.fits(&pushed);
+            self.current_shape = pushed;
+            // the spawn the finesse inputs count from has to stay free as well:
+            self.finesse_spawn_pushed_up();
+        }
+        if topped_out {
+            self.game_over = true;
+            self.emit(Event::GameOver);
+        }
+    }
+
+    // the shape moved up until it does not overlap the board anymore (or left it at the top)
+    pub(super) fn pushed_up(&self, shape: &Shape) -> Shape {
+        let mut pushed = shape.clone();
+        while self.is_colliding(&pushed) && !self.is_out_of_bounds(&pushed) {
+            pushed = &pushed + XY(0, -1);
+        }
+        pushed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::rng::SeededRng;
+
+    fn clear(lines: u32, t_spin: bool) -> Clear {
+        Clear {
+            kind: Some(PieceKind::T),
+            lines,
+            t_spin,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn attack_table() {
+        let table = AttackTable::default();
+        assert_eq!(table.attack(clear(1, false), 0, false), 0);
+        assert_eq!(table.attack(clear(4, false), 0, false), 4);
+        assert_eq!(table.attack(clear(2, true), 0, false), 4);
+        assert_eq!(table.attack(clear(0, true), 3, true), 0);
+        // combos, back to back and perfect clears add up:
+        assert_eq!(table.attack(clear(2, false), 3, true), 1 + 2);
+        assert_eq!(table.attack(clear(4, false), 20, true), 4 + 5 + 1);
+        let perfect = Clear {
+            perfect_clear: true,
+            ..clear(1, false)
+        };
+        assert_eq!(table.attack(perfect, 0, false), 10);
+
+        let table = AttackTable::from_json(r#"{ "lines": [1, 2, 3, 5], "combo": [] }"#).unwrap();
+        assert_eq!(table.attack(clear(1, false), 4, false), 1);
+        assert_eq!(table.t_spin, AttackTable::default().t_spin);
+        assert_eq!(AttackTable::from_json(&table.to_json()), Ok(table));
+        assert!(AttackTable::from_json(r#"{ "lines": 4 }"#).is_err());
+    }
+
+    #[test]
+    fn combos_and_back_to_back() {
+        let table = AttackTable::default();
+        let mut attacker = Attacker::default();
+        let sent: Vec<u32> = [
+            clear(4, false),
+            clear(4, false),
+            clear(1, false),
+            clear(2, true),
+            clear(0, false),
+            clear(2, true),
+        ]
+        .into_iter()
+        .map(|clear| attacker.attack(&table, clear))
+        .collect();
+        // tetris, b2b tetris (+combo 1), single (combo 1, breaks b2b), T-spin double (combo 2),
+        // nothing (breaks the combo, not back to back), b2b T-spin double:
+        assert_eq!(sent, vec![4, 4 + 1 + 1, 1, 4 + 2, 0, 4 + 1]);
+        assert_eq!(attacker.combo(), Some(0));
+        assert!(attacker.is_back_to_back());
+    }
+
+    #[test]
+    fn events_become_clears() {
+        let events = [
+            Event::Spawn(PieceKind::T),
+            Event::Lock(PieceKind::T),
+            Event::TSpin { lines: 2 },
+            Event::LineClear {
+                lines: 2,
+                score: 100,
+            },
+            Event::Spawn(PieceKind::O),
+            Event::Lock(PieceKind::O),
+        ];
+        assert_eq!(
+            clears(&events),
+            vec![
+                clear(2, true),
+                Clear {
+                    kind: Some(PieceKind::O),
+                    ..Default::default()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn incoming_garbage_cancels() {
+        let mut incoming = Incoming::default();
+        incoming.push(2);
+        incoming.push(0);
+        incoming.push(3);
+        assert_eq!(incoming.total(), 5);
+        assert_eq!(incoming.cancel(3), 0);
+        assert_eq!(incoming.attacks().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(incoming.cancel(4), 2);
+        assert_eq!(incoming.total(), 0);
+        incoming.push(1);
+        incoming.push(2);
+        assert_eq!(incoming.take(), vec![1, 2]);
+        assert_eq!(incoming.total(), 0);
+    }
+
+    #[test]
+    fn exchange_cancels_then_rises() {
+        let table = AttackTable::default();
+        let (mut attacker, mut incoming) = (Attacker::default(), Incoming::default());
+        incoming.push(3);
+        incoming.push(2);
+        // a tetris cancels 4 of the 5 lines:
+        let tetris = exchange(&mut attacker, &mut incoming, &table, clear(4, false), |a| a);
+        assert_eq!(tetris, (0, vec![]));
+        assert_eq!(incoming.total(), 1);
+        // a T-spin double (back to back, boosted) cancels the rest and sends what is left:
+        let tsd = exchange(&mut attacker, &mut incoming, &table, clear(2, true), |a| {
+            a * 2
+        });
+        assert_eq!(tsd, ((4 + 1 + 1) * 2 - 1, vec![]));
+        // a lock without lines lets the meter rise:
+        incoming.push(2);
+        let nothing = exchange(&mut attacker, &mut incoming, &table, clear(0, false), |a| a);
+        assert_eq!(nothing, (0, vec![2]));
+        assert_eq!(incoming.total(), 0);
+    }
+
+    #[test]
+    fn holes() {
+        let mut rng = SeededRng::new(3);
+        let holes = HolePlacement::PerAttack.holes(&[2, 3], 10, &mut rng);
+        assert_eq!(holes.len(), 5);
+        assert!(holes[0] == holes[1] && holes[2..].iter().all(|x| *x == holes[2]));
+        assert!(holes.iter().all(|x| (0..10).contains(x)));
+        assert_eq!(HolePlacement::PerRow.holes(&[2, 3], 10, &mut rng).len(), 5);
+        assert_eq!(
+            HolePlacement::Column(12).holes(&[2], 10, &mut rng),
+            vec![9, 9]
+        );
+        // no board to put them in, still no panic:
+        for holes in [HolePlacement::Column(3), HolePlacement::PerRow] {
+            assert_eq!(holes.holes(&[1], 0, &mut rng), vec![0]);
+        }
+    }
+
+    #[test]
+    fn garbage_rises() {
+        let mut tetris = Tetris::new(10, 20);
+        tetris.set_board((0..4).map(|x| (XY(x, 19), PieceKind::I)));
+        tetris.add_garbage(&[3, 5]);
+        assert_eq!(tetris.get_kind(XY(0, 17)), Some(PieceKind::I));
+        assert_eq!(tetris.get_kind(XY(3, 18)), None);
+        assert_eq!(tetris.get_kind(XY(4, 18)), Some(PieceKind::Garbage));
+        assert_eq!(tetris.get_kind(XY(5, 19)), None);
+        assert!(tetris.take_events().contains(&Event::Garbage { lines: 2 }));
+        assert!(!tetris.is_game_over());
+        // the falling shape gets pushed up, out at the top the game is over:
+        tetris.add_garbage(&[0; 18]);
+        assert!(tetris.is_game_over());
+    }
+}
@@ -1,13 +1,36 @@
+pub mod bot;
 pub mod buf;
+pub mod event;
+pub mod finesse;
+pub mod fumen;
+pub mod garbage;
+pub mod movegen;
+pub mod piece_set;
+pub mod position;
+pub mod puzzle;
+pub mod replay;
+pub mod rng;
+pub mod royale;
 pub mod shapes;
+pub mod snapshot;
+pub mod timing;
+pub mod versus;
+
+use serde::{Deserialize, Serialize};
+
 use self::buf::RingBuffer;
-use self::shapes::{Shape, XY};
+use self::event::{Event, MAX_EVENTS};
+use self::finesse::{Finesse, FinesseMode};
+use self::piece_set::PieceSet;
+use self::rng::Rng;
+use self::shapes::{PieceKind, Shape, XY};
+use self::timing::{Clock, Phase, Timing, INSTANT_GRAVITY};
 
 /*
     Implements the Game logic.
         - excluding:
             - frontend
-            - timer implementation
+            - timer implementation (the frontend feeds elapsed time into update(), see timing)
             - io/ -input (keypresses etc)
 */
 
@@ -16,17 +39,70 @@ use self::shapes::{Shape, XY};
 pub struct Tetris {
     // game state
     score: i32,
+    /// lines cleared so far, every 10 lines the level goes up
+    lines: u32,
     game_over: bool,
+    paused: bool,
     // size of the playing field
     width: i32,
     height: i32,
     /// Player controlled shape and the next shape
     current_shape: Shape,
     next_shapes: RingBuffer<Shape>,
+    /// piece (index in the piece set) put aside with hold()
+    hold: Option<usize>,
+    /// hold() can only be used once per shape
+    hold_used: bool,
+    /// the last thing that moved the current shape was a rotation (a T-spin if it locks like that)
+    spun: bool,
     /// Pixels that build up on the bottom of the game:
     sticky_bottom_shapes: Vec<Shape>,
+    /// what the game got started with (piece set etc.)
+    settings: Settings,
+    /// state of the fixed timestep simulation (gravity, lock delay, DAS)
+    clock: Clock,
+    /// falling, or waiting in the line clear / entry delay after a lock
+    phase: Phase,
+    /// inputs the current shape took so far, and the faults so far (see finesse)
+    finesse: Finesse,
+    /// picks the upcoming shapes
+    rng: Box<dyn Rng>,
+    /// what happened since the last take_events() (only the last MAX_EVENTS)
+    events: RingBuffer<Event>,
 }
-#[derive(Debug)]
+
+/// Most upcoming shapes the preview can show
+pub const MAX_PREVIEW: usize = 6;
+
+/// Settings a game gets started with (in json everything is optional, see replay::Replay)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    /// how many upcoming shapes are queued up and shown in the preview (1 to MAX_PREVIEW)
+    pub preview_len: usize,
+    /// the pieces the game draws its shapes from
+    pub piece_set: PieceSet,
+    /// gravity, lock delay and DAS
+    pub timing: Timing,
+    /// level the game starts at (with Gravity::Level this sets the starting speed)
+    pub start_level: u32,
+    /// check the inputs of every shape against the fewest it could have taken
+    pub finesse: FinesseMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            preview_len: 3,
+            piece_set: PieceSet::standard(),
+            timing: Timing::default(),
+            start_level: 1,
+            finesse: FinesseMode::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     Left,
     Right,
@@ -35,24 +111,70 @@ pub enum Direction {
 impl Tetris {
     // constructor, starts a new game of width X heigth Pixels/Blocks
     pub fn new(width: u32, height: u32) -> Self {
-        let initial_next_shapes = vec![
-            &Shape::new() + XY((width as i32) / 2, 0),
-            &Shape::new() + XY((width as i32) / 2, 0),
-            &Shape::new() + XY((width as i32) / 2, 0),
-            &Shape::new() + XY((width as i32) / 2, 0),
-        ];
+        Self::with_settings(width, height, Settings::default())
+    }
+
+    // constructor, starts a new game of width X heigth Pixels/Blocks with custom settings
+    pub fn with_settings(width: u32, height: u32, settings: Settings) -> Self {
+        Self::with_rng(width, height, settings, rng::default_rng())
+    }
+
+    // constructor, like with_settings() but the upcoming shapes come from the given Rng
+    // (a rng::SeededRng makes the game deal the same pieces every time)
+    pub fn with_rng(width: u32, height: u32, settings: Settings, mut rng: Box<dyn Rng>) -> Self {
+        let preview_len = settings.preview_len.clamp(1, MAX_PREVIEW);
+        let mut random_shape = || {
+            rng.has_next()
+                .then(|| Self::random_shape(rng.as_mut(), &settings.piece_set, width as i32))
+        };
+        // (current shape first, so a rng::ScriptedRng deals its pieces in order)
+        let current_shape = random_shape().expect("a rng deals at least one shape");
+        // (a script can run out before the preview is full)
+        let mut next_shapes = RingBuffer::with_capacity(preview_len);
+        while !next_shapes.is_full() {
+            match random_shape() {
+                Some(shape) => next_shapes.try_push(shape).expect("not full"),
+                None => break,
+            }
+        }
         Self {
-            score: 0 as i32,
+            finesse: Finesse::new(&current_shape),
+            score: 0,
+            lines: 0,
             game_over: false,
+            paused: false,
             width: width as i32,
             height: height as i32,
-            // current shape starts in middle of screen (half width):
-            current_shape: &Shape::new() + XY((width as i32) / 2, 0),
-            next_shapes: RingBuffer::new(initial_next_shapes),
+            current_shape,
+            next_shapes,
+            hold: None,
+            hold_used: false,
+            spun: false,
             sticky_bottom_shapes: vec![],
+            settings,
+            clock: Clock::default(),
+            phase: Phase::Falling,
+            rng,
+            events: RingBuffer::with_capacity(MAX_EVENTS).overwriting(),
         }
     }
 
+    // generates a random new Shape from the piece set
+    // (see deal() for a rng that can run out)
+    fn random_shape(rng: &mut dyn Rng, piece_set: &PieceSet, width: i32) -> Shape {
+        let idx = rng.next_index(piece_set.len());
+        Self::spawn_shape(piece_set, idx, width)
+    }
+
+    // shapes start in middle of screen (half width):
+    fn spawn_shape(piece_set: &PieceSet, idx: usize, width: i32) -> Shape {
+        &piece_set.shape(idx) + XY(width / 2, 0)
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
     /// get_pixels, expose the game pixels to the outside (then draw the canvas in frontend)
     pub fn get_pixels(&self) -> impl Iterator<Item = XY> {
         // to not depend on the &self lifetime we rebind height and width:
@@ -61,38 +183,160 @@ impl Tetris {
         (0..height).flat_map(move |y| (0..width).map(move |x| XY(x, y)))
     }
 
-    // gets the next shape to draw the preview
-    pub fn get_4x4pixels(&self) -> impl Iterator<Item=XY> {
-        let xoff = self.width / 2 - 1;
-        (-1..3).flat_map(move |y| (xoff..xoff+4).map(move |x| XY(x,y)))
+    /// width and height of the preview box, big enough to fit every piece of the set
+    pub fn get_preview_size(&self) -> (i32, i32) {
+        let (min, max) = self.settings.piece_set.bounds();
+        (max.0 - min.0 + 1, max.1 - min.1 + 1)
     }
-    // return picels of said 4 by 4 pixels
-    pub fn get_4x4type(&self, xy: XY) -> Option<&'static str> {
-        if self.next_shapes.peek().has_xy(xy){
-            Some(self.next_shapes.peek().get_typ())
-        } else {
-            None
-        }
+
+    // gets the pixels of the preview box, to draw the upcoming shapes in
+    pub fn get_preview_pixels(&self) -> impl Iterator<Item=XY> {
+        let (min, max) = self.settings.piece_set.bounds();
+        let (min, max) = (&min + XY(self.width / 2, 0), &max + XY(self.width / 2, 0));
+        (min.1..=max.1).flat_map(move |y| (min.0..=max.0).map(move |x| XY(x,y)))
+    }
+    // return pixels of said preview box, for the n-th upcoming shape (0 is the next one)
+    pub fn get_preview_kind(&self, n: usize, xy: XY) -> Option<PieceKind> {
+        self.next_shapes
+            .get(n)
+            .filter(|shape| shape.has_xy(xy))
+            .map(|shape| shape.get_kind())
+    }
+
+    /// the next (up to) n upcoming shapes, starting with the one that spawns next
+    pub fn next_queue(&self, n: usize) -> impl Iterator<Item = &Shape> {
+        self.next_shapes.iter().take(n)
+    }
+
+    /// how many upcoming shapes get queued up (set by Settings::preview_len)
+    /// (the queue itself can be shorter, once a scripted game runs out of pieces)
+    pub fn preview_len(&self) -> usize {
+        self.next_shapes.capacity()
     }
 
     pub fn get_score(&self) -> String {
         format!("score: {}", self.score)
     }
 
-    /// get type of the shape on point xy
-    pub fn get_typ(&self, xy: XY) -> Option<&'static str> {
-        if self.current_shape.has_xy(xy) {
+    /// the score as a number (get_score() is the text for the view)
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn get_lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// starts at Settings::start_level and goes up every 10 cleared lines
+    pub fn get_level(&self) -> u32 {
+        self.settings.start_level.max(1) + self.lines / 10
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes the game. While paused time stands still and the current shape can not be moved.
+    pub fn toggle_pause(&mut self) {
+        if !self.game_over {
+            self.paused = !self.paused;
+            self.emit(Event::Pause(self.paused));
+        }
+    }
+
+    /// kind of the shape put aside with hold()
+    pub fn get_hold(&self) -> Option<PieceKind> {
+        self.hold.map(|idx| self.settings.piece_set.pieces[idx].kind)
+    }
+
+    /// like get_preview_kind(), but for the shape put aside with hold()
+    pub fn get_hold_preview_kind(&self, xy: XY) -> Option<PieceKind> {
+        self.hold
+            .map(|idx| Self::spawn_shape(&self.settings.piece_set, idx, self.width))
+            .filter(|shape| shape.has_xy(xy))
+            .map(|shape| shape.get_kind())
+    }
+
+    /// width and height of the playing field
+    pub fn get_size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
+
+    // getter
+    pub fn get_current_shape(&self) -> &Shape {
+        &self.current_shape
+    }
+
+    /// true if the current shape can not fall any further
+    pub fn is_grounded(&self) -> bool {
+        let new_pos = &self.current_shape + XY(0, 1);
+        self.is_out_of_bounds(&new_pos) || self.is_colliding(&new_pos)
+    }
+
+    /// the 'ghost' of the current shape: where it would land if it dropped straight down
+    pub fn get_ghost(&self) -> Shape {
+        let mut ghost = self.current_shape.clone();
+        loop {
+            let new_pos = &ghost + XY(0, 1);
+            if self.is_out_of_bounds(&new_pos) || self.is_colliding(&new_pos) {
+                return ghost;
+            }
+            ghost = new_pos;
+        }
+    }
+
+    /// falling, or one of the delays between two shapes
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    /// the full rows (top to bottom) that are about to collapse, empty outside of the line clear delay
+    pub fn clearing_rows(&self) -> &[i32] {
+        match &self.phase {
+            Phase::LineClear { rows, .. } => rows,
+            _ => &[],
+        }
+    }
+
+    /// true while the current shape is on the board (and not waiting in a delay)
+    pub fn is_falling(&self) -> bool {
+        self.phase == Phase::Falling
+    }
+
+    /// get kind of the shape on point xy
+    pub fn get_kind(&self, xy: XY) -> Option<PieceKind> {
+        if self.is_falling() && self.current_shape.has_xy(xy) {
             // xy is in current shape:
-            Some(self.current_shape.get_typ())
+            Some(self.current_shape.get_kind())
         } else {
             // check if sticky_bottom contains xy:
             self.sticky_bottom_shapes
                 .iter()
                 .find(|shape| shape.has_xy(xy))
-                .map(|shape| shape.get_typ())
+                .map(|shape| shape.get_kind())
         }
     }
 
+    /// Replaces the locked cells of the board (for puzzles etc.), cells outside the board get left out
+    pub fn set_board(&mut self, cells: impl IntoIterator<Item = (XY, PieceKind)>) {
+        let mut by_kind: Vec<Vec<XY>> = vec![vec![]; PieceKind::ALL.len()];
+        for (xy, kind) in cells {
+            if xy.0 >= 0 && xy.0 < self.width && xy.1 >= 0 && xy.1 < self.height {
+                by_kind[kind as usize].push(xy);
+            }
+        }
+        self.sticky_bottom_shapes = PieceKind::ALL
+            .into_iter()
+            .zip(by_kind)
+            .filter(|(_, cells)| !cells.is_empty())
+            .map(|(kind, cells)| Shape::new(0, kind, cells, XY(0, 0)))
+            .collect();
+    }
+
     // private helper functionality:
 
     // check if a shape is colliding with the game pixels
@@ -119,7 +363,7 @@ impl Tetris {
             .filter(|xy| xy.1 == y)
             .collect::<std::collections::HashSet<_>>()
             .len() as i32;
-        return len == self.width;
+        len == self.width
     }
 
     // removes a (full) line of rows from the game and makes the line above "fall down"
@@ -129,15 +373,48 @@ impl Tetris {
         }
     }
 
-    // Points per line cleared: 1line:40     2lines:100      3liens:300  4lines:1200
-    fn remove_full_lines(&mut self) {
-        let mut lines_cleared = 0;
-        for y in 0..self.height {
-            if self.is_line_full(y) {
-                self.remove_line(y);
-                lines_cleared +=1;
-            }
+    // T-spin (3 corner rule): a T that got rotated into place, with 3 of the 4 corners around its center blocked
+    fn is_t_spin(&self) -> bool {
+        if !self.spun || self.current_shape.get_kind() != PieceKind::T {
+            return false;
         }
+        // the center is the one pixel with 3 neighbours:
+        let cells: Vec<XY> = self.current_shape.get_pixels().collect();
+        let Some(XY(x, y)) = cells.iter().copied().find(|XY(x, y)| {
+            [XY(x - 1, *y), XY(x + 1, *y), XY(*x, y - 1), XY(*x, y + 1)]
+                .iter()
+                .filter(|xy| cells.contains(xy))
+                .count()
+                == 3
+        }) else {
+            return false;
+        };
+        let blocked = [XY(x - 1, y - 1), XY(x + 1, y - 1), XY(x - 1, y + 1), XY(x + 1, y + 1)]
+            .into_iter()
+            .filter(|xy| {
+                let corner = Shape::new(0, PieceKind::Garbage, [*xy], XY(0, 0));
+                self.is_out_of_bounds(&corner) || self.is_colliding(&corner)
+            })
+            .count();
+        blocked >= 3
+    }
+
+    // all full rows, top to bottom
+    fn full_lines(&self) -> Vec<i32> {
+        (0..self.height).filter(|y| self.is_line_full(*y)).collect()
+    }
+
+    // removes the rows, top to bottom (so the rows below keep their y while the ones above fall down)
+    fn remove_lines(&mut self, rows: &[i32]) {
+        for y in rows {
+            self.remove_line(*y);
+        }
+    }
+
+    // Points per line cleared: 1line:40     2lines:100      3liens:300  4lines:1200
+    fn score_lines(&mut self, lines_cleared: u32) {
+        let (score, level) = (self.score, self.get_level());
+        self.lines += lines_cleared;
         match lines_cleared{
             4 => self.score += 1200,
             3 => self.score += 300,
@@ -145,47 +422,207 @@ impl Tetris {
             1 => self.score += 40,
             _ => {}
         }
+        if lines_cleared > 0 {
+            self.emit(Event::LineClear { lines: lines_cleared, score: self.score - score });
+        }
+        if self.get_level() > level {
+            self.emit(Event::LevelUp(self.get_level()));
+        }
     }
 
-    /// Main Game Loop, gets Called from frontend.
-    /// Every Tick the block moves down one field
+    fn emit(&mut self, event: Event) {
+        self.events.push_overwrite(event);
+    }
+
+    /// Everything that happened since the last call, oldest first
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::iter::from_fn(|| self.events.try_pop().ok()).collect()
+    }
+
+    /// Drops the shape by one field, or locks it in place if it can not fall any further.
+    /// The frontend usually calls update() instead, which runs gravity and lock delay on a fixed timestep
+    /// (delays are counted in those frames, tick() skips them)
     pub fn tick(&mut self) {
         if self.game_over {
             return;
         }
+        if !self.is_falling() {
+            self.end_delay();
+        } else if !self.move_down() {
+            self.lock_current_shape();
+        }
+    }
 
+    /// moves the current shape down once, if that is a legal move (it does not lock the shape)
+    pub fn move_down(&mut self) -> bool {
+        if self.paused || !self.is_falling() {
+            return false;
+        }
         let new_pos = &self.current_shape + XY(0, 1); // move 1 pixel down
         if self.is_out_of_bounds(&new_pos) || self.is_colliding(&new_pos) {
-            // current shape hit bottom
-            // -> so we 1. add the current shape to the sticky_bottom_shapes:
-            // ->    we 2. create a new current shape for the top:
-            self.next_shape();
+            return false;
+        }
+        self.current_shape = new_pos;
+        self.spun = false;
+        true
+    }
 
-            self.remove_full_lines();
+    // current shape hit bottom
+    // -> so we 1. add the current shape to the sticky_bottom_shapes:
+    // ->    we 2. score the full lines and let them collapse (after the line clear delay):
+    // ->    we 3. create a new current shape for the top (after the entry delay):
+    fn lock_current_shape(&mut self) {
+        if !self.check_finesse() {
+            return;
+        }
+        self.emit(Event::Lock(self.current_shape.get_kind()));
+        let t_spin = self.is_t_spin();
+        self.sticky_bottom_shapes.push(self.current_shape.clone());
+        self.hold_used = false;
 
-            // if no more space left -> GameOver:
-            if self.is_colliding(&self.current_shape) {
-                self.game_over = true;
-            }
+        let rows = self.full_lines();
+        if t_spin {
+            self.emit(Event::TSpin { lines: rows.len() as u32 });
+        }
+        self.score_lines(rows.len() as u32);
+        // nothing left besides the full rows:
+        let perfect_clear = !rows.is_empty()
+            && self.sticky_bottom_shapes.iter().flat_map(|shape| shape.get_pixels()).all(|xy| rows.contains(&xy.1));
+        if perfect_clear {
+            self.emit(Event::PerfectClear);
+        }
+        let frames = self.settings.timing.line_clear_frames;
+        if !rows.is_empty() && frames > 0 {
+            self.phase = Phase::LineClear { rows, frame: 0, frames };
         } else {
-            self.current_shape = new_pos;
+            self.remove_lines(&rows);
+            self.lines_settled();
         }
     }
 
-    // helper for tick(), gets a new shape from the "RingBuffered" next_shapes queue
-    // sets that shape to current shape and inserts a new shape to the queue
-    fn next_shape(&mut self) {
-        let random_shape = &Shape::new() + XY(self.width / 2, 0);
-        let next_shape = self.next_shapes.pop_and_push(random_shape);
+    // the board is done clearing lines, the next shape comes (after the entry delay)
+    fn lines_settled(&mut self) {
+        let frames = self.settings.timing.are_frames;
+        if frames > 0 {
+            self.phase = Phase::Entry { frame: 0, frames };
+        } else {
+            self.phase = Phase::Falling;
+            if self.next_shape() {
+                self.spawned();
+            }
+        }
+    }
+
+    // the current delay ran out (or got skipped)
+    fn end_delay(&mut self) {
+        match std::mem::take(&mut self.phase) {
+            Phase::Falling => {}
+            Phase::LineClear { rows, .. } => {
+                self.remove_lines(&rows);
+                self.lines_settled();
+            }
+            Phase::Entry { .. } => {
+                if self.next_shape() {
+                    self.spawned();
+                }
+            }
+        }
+    }
+
+    // a new current shape appeared at the top
+    fn spawned(&mut self) {
+        self.clock.on_spawn();
+        self.spun = false;
+        // if no more space left -> GameOver:
+        if self.is_colliding(&self.current_shape) {
+            self.game_over = true;
+            self.emit(Event::GameOver);
+            return;
+        }
+        self.emit(Event::Spawn(self.current_shape.get_kind()));
+        // at 20G the new shape does not even get to show up at the top:
+        if self.gravity() >= INSTANT_GRAVITY {
+            self.current_shape = self.get_ghost();
+        }
+        self.finesse.spawned(&self.current_shape);
+    }
+
+    /// Drops the current shape all the way down and locks it in place right away
+    pub fn hard_drop(&mut self) {
+        if self.game_over || self.paused || !self.is_falling() {
+            return;
+        }
+        let ghost = self.get_ghost();
+        // dropping down after a rotation is no spin anymore:
+        if ghost.get_pixels().collect::<std::collections::HashSet<_>>() != self.current_shape.get_pixels().collect() {
+            self.spun = false;
+        }
+        self.current_shape = ghost;
+        self.lock_current_shape();
+    }
 
-        let new_sticky = std::mem::replace(&mut self.current_shape, next_shape);
-        self.sticky_bottom_shapes.push(new_sticky);
+    /// Puts the current shape aside and continues with the one put aside before (or the next one).
+    /// Only works once until the next shape locks. Returns false if it was not allowed.
+    pub fn hold(&mut self) -> bool {
+        if self.game_over || self.paused || self.hold_used || !self.is_falling() {
+            return false;
+        }
+        // nothing to swap with, the script ran out:
+        if self.hold.is_none() && self.next_shapes.is_empty() {
+            return false;
+        }
+        self.emit(Event::Hold(self.current_shape.get_kind()));
+        self.current_shape = match self.hold.replace(self.current_shape.get_piece()) {
+            Some(idx) => Self::spawn_shape(&self.settings.piece_set, idx, self.width),
+            None => self.take_next().expect("the queue is not empty"),
+        };
+        self.hold_used = true;
+        self.spawned();
+        true
+    }
+
+    // a new shape from the rng, None once it ran out (a rng::ScriptedRng::once)
+    fn deal(&mut self) -> Option<Shape> {
+        self.rng
+            .has_next()
+            .then(|| Self::random_shape(self.rng.as_mut(), &self.settings.piece_set, self.width))
+    }
+
+    // the first shape of the "RingBuffered" next_shapes queue, a new shape gets inserted to the queue
+    fn take_next(&mut self) -> Option<Shape> {
+        match self.deal() {
+            Some(shape) => Some(self.next_shapes.pop_and_push(shape)),
+            None => self.next_shapes.try_pop().ok(),
+        }
+    }
+
+    // helper for tick(), sets the next shape from the queue to current shape
+    // false if there is none left: the game is over
+    fn next_shape(&mut self) -> bool {
+        match self.take_next() {
+            Some(shape) => {
+                self.current_shape = shape;
+                true
+            }
+            None => {
+                self.game_over = true;
+                self.emit(Event::GameOver);
+                false
+            }
+        }
     }
 
     /// Player Interacting with left right input -> move shape left/right
-    pub fn move_current_shape(&mut self, dir: Direction) {
+    /// returns false if the move was illegal
+    pub fn move_current_shape(&mut self, dir: Direction) -> bool {
+        self.count_input();
+        self.shift_current_shape(dir)
+    }
+
+    // moves the shape left/right, without counting it as an input (the auto repeat of a held key)
+    fn shift_current_shape(&mut self, dir: Direction) -> bool {
         if self.game_over {
-            return;
+            return false;
         }
         let move_dir = match dir {
             Direction::Left => XY(-1, 0),
@@ -193,55 +630,83 @@ impl Tetris {
         };
         let new_pos = &self.current_shape + move_dir;
         // check if new position is not an illegal move:
-        if !(self.is_out_of_bounds(&new_pos) || self.is_colliding(&new_pos)) {
-            self.current_shape = new_pos;
-        }
+        let moved = self.do_if_is_legal_move(new_pos);
+        self.spun &= !moved;
+        moved
     }
 
-    /// Player Interacting with up/down input -> rotate current shape
+    /// Player Interacting with up/down input -> rotate current shape (clockwise)
     pub fn move_rotate(&mut self) {
+        self.count_input();
+        self.try_rotation(self.current_shape.rotated_shape());
+    }
+
+    /// rotate current shape counter clockwise
+    pub fn move_rotate_ccw(&mut self) {
+        self.count_input();
+        self.try_rotation(self.current_shape.rotated_shape_ccw());
+    }
+
+    /// rotate current shape by 180 degrees (in one step, it does not need the room for the 90 degree rotation)
+    pub fn move_rotate_180(&mut self) {
+        self.count_input();
+        self.try_rotation(self.current_shape.rotated_shape().rotated_shape());
+    }
+
+    fn try_rotation(&mut self, rotated: Shape) {
         if self.game_over {
             return;
         }
+        if let Some(kicked) = self.kicked(rotated) {
+            self.spun |= self.do_if_is_legal_move(kicked);
+        }
+    }
+
+    // where a rotated shape ends up, None if it does not fit anywhere
+    fn kicked(&self, rotated: Shape) -> Option<Shape> {
+        Self::kick(&self.settings.piece_set, rotated, |shape| self.fits(shape))
+    }
+
+    // the rotation rules, for any board (fits() says if a shape fits on it)
+    fn kick(piece_set: &PieceSet, rotated: Shape, fits: impl Fn(&Shape) -> bool) -> Option<Shape> {
         // first we check if the rotated Position is a legal move:
-        let mut new_shape = self.current_shape.rotated_shape();
-        if self.do_if_is_legal_move(new_shape) {
-            return
+        if fits(&rotated) {
+            return Some(rotated);
         }
         // If Basic Rotation fails, 'Wall Kicks' are attempted to push the shape 'away from the sides etc.'
         // https://tetris.wiki/Super_Rotation_System
-        // Since this version only implements one rotation anyways we just simply try to push the shape left and right by an offset and call it a day
-        new_shape = &self.current_shape.rotated_shape() +  XY(1, 0);
-        if self.do_if_is_legal_move(new_shape){
-           return
-        }
-        new_shape = &self.current_shape.rotated_shape() +  XY(2, 0);
-        if self.do_if_is_legal_move(new_shape){
-           return
-        }
-        new_shape = &self.current_shape.rotated_shape() +  XY(-1, 0);
-        if self.do_if_is_legal_move(new_shape){
-           return
-        }
-        new_shape = &self.current_shape.rotated_shape() +  XY(-2, 0);
-        if self.do_if_is_legal_move(new_shape){
-           return
-        }
+        // Since this version only implements one rotation anyways we just simply try to push the shape by the offsets
+        // in the kick table of the piece (by default left and right by one and two) and call it a day
+        piece_set
+            .kicks(rotated.get_piece())
+            .iter()
+            .map(|kick| &rotated + *kick)
+            .find(|kicked| fits(kicked))
+    }
+
+    // true if the shape is inside the board and does not overlap anything
+    fn fits(&self, shape: &Shape) -> bool {
+        !self.is_out_of_bounds(shape) && !self.is_colliding(shape)
     }
 
     // check if new position is not an illegal move (collision with existing shapes or out of bounds)
     fn do_if_is_legal_move(&mut self, new_shape: Shape) -> bool{
-        if !self.is_out_of_bounds(&new_shape) && !self.is_colliding(&new_shape) {
+        if self.paused || !self.is_falling() {
+            return false
+        }
+        if self.fits(&new_shape) {
             self.current_shape = new_shape;
+            self.clock.on_moved();
             return true
         }
-        return false
+        false
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
 
     #[test]
     fn test() {
@@ -253,4 +718,188 @@ mod tests {
 
         println!("{:#?}", gamestate);
     }
+
+    #[test]
+    fn next_queue_follows_settings() {
+        let gamestate = Tetris::with_settings(10, 20, Settings { preview_len: 5, ..Default::default() });
+        assert_eq!(gamestate.preview_len(), 5);
+        assert_eq!(gamestate.next_queue(2).count(), 2);
+        assert_eq!(gamestate.next_queue(9).count(), 5);
+
+        // preview length gets clamped to 1..=MAX_PREVIEW
+        let gamestate = Tetris::with_settings(10, 20, Settings { preview_len: 0, ..Default::default() });
+        assert_eq!(gamestate.preview_len(), 1);
+        let gamestate = Tetris::with_settings(10, 20, Settings { preview_len: 99, ..Default::default() });
+        assert_eq!(gamestate.preview_len(), MAX_PREVIEW);
+    }
+
+    #[test]
+    fn ghost_lands_on_the_floor() {
+        let gamestate = Tetris::new(10, 20);
+        let ghost = gamestate.get_ghost();
+        assert_eq!(ghost.get_kind(), gamestate.current_shape.get_kind());
+        assert_eq!(ghost.get_pixels().map(|xy| xy.1).max(), Some(19));
+        let dropped = &ghost + XY(0, 1);
+        assert!(gamestate.is_out_of_bounds(&dropped));
+    }
+
+    #[test]
+    fn hard_drop_locks_on_the_ghost() {
+        let mut gamestate = Tetris::new(10, 20);
+        let ghost: HashSet<XY> = gamestate.get_ghost().get_pixels().collect();
+        gamestate.hard_drop();
+        assert_eq!(gamestate.sticky_bottom_shapes.len(), 1);
+        let locked: HashSet<XY> = gamestate.sticky_bottom_shapes[0].get_pixels().collect();
+        assert_eq!(locked, ghost);
+    }
+
+    #[test]
+    fn hold_swaps_once_per_shape() {
+        let mut gamestate = Tetris::new(10, 20);
+        let first = gamestate.current_shape.get_kind();
+        let next = gamestate.next_queue(1).next().unwrap().get_kind();
+        assert_eq!(gamestate.get_hold(), None);
+
+        assert!(gamestate.hold());
+        assert_eq!(gamestate.get_hold(), Some(first));
+        assert_eq!(gamestate.current_shape.get_kind(), next);
+        assert!(!gamestate.hold());
+
+        // after locking the shape hold works again and brings back the first one:
+        gamestate.hard_drop();
+        let current = gamestate.current_shape.get_kind();
+        assert!(gamestate.hold());
+        assert_eq!(gamestate.current_shape.get_kind(), first);
+        assert_eq!(gamestate.get_hold(), Some(current));
+    }
+
+    #[test]
+    fn rotations_undo_each_other() {
+        let mut gamestate = Tetris::new(10, 20);
+        gamestate.move_down();
+        gamestate.move_down();
+        let pixels = |tetris: &Tetris| -> HashSet<XY> { tetris.current_shape.get_pixels().collect() };
+        let start = pixels(&gamestate);
+
+        gamestate.move_rotate();
+        gamestate.move_rotate_ccw();
+        assert_eq!(pixels(&gamestate), start);
+
+        gamestate.move_rotate_180();
+        gamestate.move_rotate();
+        gamestate.move_rotate();
+        gamestate.move_rotate();
+        gamestate.move_rotate_ccw();
+        assert_eq!(pixels(&gamestate), start);
+    }
+
+    #[test]
+    fn pause_freezes_the_game() {
+        let mut gamestate = Tetris::new(10, 20);
+        let start: HashSet<XY> = gamestate.current_shape.get_pixels().collect();
+        gamestate.toggle_pause();
+        assert!(gamestate.is_paused());
+        assert_eq!(gamestate.update(10_000.0), 0);
+        assert!(!gamestate.move_current_shape(Direction::Left));
+        gamestate.hard_drop();
+        assert!(!gamestate.hold());
+        assert_eq!(gamestate.current_shape.get_pixels().collect::<HashSet<_>>(), start);
+
+        gamestate.toggle_pause();
+        assert!(gamestate.move_current_shape(Direction::Left));
+    }
+
+    #[test]
+    fn same_seed_deals_the_same_pieces() {
+        let deal = |seed| {
+            let mut gamestate = Tetris::with_rng(10, 20, Settings::default(), Box::new(rng::SeededRng::new(seed)));
+            (0..8)
+                .map(|_| {
+                    let kind = gamestate.current_shape.get_kind();
+                    gamestate.hard_drop();
+                    kind
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(deal(7), deal(7));
+        assert_ne!(deal(7), deal(8));
+    }
+
+    #[test]
+    fn events_get_queued_until_taken() {
+        let mut gamestate = Tetris::new(10, 20);
+        assert_eq!(gamestate.take_events(), vec![]);
+        let first = gamestate.current_shape.get_kind();
+        gamestate.hard_drop();
+        let next = gamestate.current_shape.get_kind();
+        assert_eq!(gamestate.take_events(), vec![Event::Lock(first), Event::Spawn(next)]);
+        assert_eq!(gamestate.take_events(), vec![]);
+
+        gamestate.hold();
+        gamestate.toggle_pause();
+        let events = gamestate.take_events();
+        assert_eq!(events[0], Event::Hold(next));
+        assert_eq!(events.last(), Some(&Event::Pause(true)));
+
+        // nobody taking them does not grow the queue forever:
+        for _ in 0..MAX_EVENTS + 1 {
+            gamestate.toggle_pause();
+        }
+        let events = gamestate.take_events();
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(events.last(), Some(&Event::Pause(false)));
+
+        while !gamestate.is_game_over() {
+            gamestate.hard_drop();
+        }
+        assert_eq!(gamestate.take_events().last(), Some(&Event::GameOver));
+    }
+
+    #[test]
+    fn line_clears_are_events() {
+        let mut gamestate = Tetris::with_settings(
+            4,
+            4,
+            Settings {
+                piece_set: PieceSet::from_json(
+                    r#"{ "name": "bar", "pieces": [{ "name": "I", "kind": "I", "cells": [[-2, 0], [-1, 0], [0, 0], [1, 0]], "center": [0, 0] }] }"#,
+                )
+                .unwrap(),
+                ..Default::default()
+            },
+        );
+        gamestate.hard_drop();
+        let events = gamestate.take_events();
+        assert!(events.contains(&Event::LineClear { lines: 1, score: 40 }), "{:?}", events);
+    }
+
+    #[test]
+    fn plays_with_custom_piece_set() {
+        let settings = Settings {
+            piece_set: PieceSet::tiny(),
+            ..Default::default()
+        };
+        let mut gamestate = Tetris::with_settings(10, 20, settings);
+        for _ in 0..200 {
+            gamestate.move_rotate();
+            gamestate.tick();
+            assert!(gamestate.current_shape.get_pixels().count() <= 3);
+        }
+        let kinds = [PieceKind::O, PieceKind::S, PieceKind::I, PieceKind::L];
+        assert!(gamestate.sticky_bottom_shapes.iter().all(|s| kinds.contains(&s.get_kind())));
+    }
+
+    #[test]
+    fn next_queue_spawns_in_order() {
+        let mut gamestate = Tetris::new(10, 20);
+        let upcoming: Vec<_> = gamestate.next_queue(3).map(|s| s.get_kind()).collect();
+        for kind in upcoming {
+            // drop the current shape until the next one spawns
+            let sticky = gamestate.sticky_bottom_shapes.len();
+            while gamestate.sticky_bottom_shapes.len() == sticky {
+                gamestate.tick();
+            }
+            assert_eq!(gamestate.current_shape.get_kind(), kind);
+        }
+    }
 }
@@ -0,0 +1,447 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::shapes::{Shape, XY};
+use super::timing::INSTANT_GRAVITY;
+use super::{Direction, Tetris};
+
+/*
+    Move generator: every spot a shape can lock in, and the shortest way to get there.
+        - a breadth first search over the states of the shape (position and rotation)
+        - moves follow the rules of the game: left/right, one row down, soft drop, rotations with kicks,
+          at 20G every move ends on the stack
+        - so it finds the spots dropping from above can not reach: tucks under overhangs, slides, spins
+    Used by the bot, and for hints ("how do I get there?").
+    Not modelled: the lock delay running out (or its limited resets) while the moves get made.
+*/
+
+/// One input sent to the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Move {
+    Left,
+    Right,
+    /// one row down (what gravity does)
+    Down,
+    /// down as far as it goes, without locking
+    SoftDrop,
+    RotateCW,
+    RotateCCW,
+    Rotate180,
+    /// left/right held down until the shape hits something (one press, the auto repeat does the rest)
+    DasLeft,
+    DasRight,
+    HardDrop,
+    Hold,
+}
+
+impl Move {
+    /// every move of a shape, in the order the search tries them (cheap ones first)
+    pub const SHAPE_MOVES: [Move; 7] = [
+        Move::Left,
+        Move::Right,
+        Move::RotateCW,
+        Move::RotateCCW,
+        Move::Rotate180,
+        Move::SoftDrop,
+        Move::Down,
+    ];
+
+    /// the moves a player makes (see finesse), gravity does Move::Down for free
+    pub const PLAYER_MOVES: [Move; 9] = [
+        Move::Left,
+        Move::Right,
+        Move::DasLeft,
+        Move::DasRight,
+        Move::RotateCW,
+        Move::RotateCCW,
+        Move::Rotate180,
+        Move::SoftDrop,
+        Move::Down,
+    ];
+
+    /// sends the move to the game, false if it did not do anything
+    pub fn apply(self, tetris: &mut Tetris) -> bool {
+        match self {
+            Move::Left => tetris.move_current_shape(Direction::Left),
+            Move::Right => tetris.move_current_shape(Direction::Right),
+            Move::Down => tetris.move_down(),
+            Move::SoftDrop => {
+                let moved = tetris.move_down();
+                while tetris.move_down() {}
+                moved
+            }
+            Move::RotateCW | Move::RotateCCW | Move::Rotate180 => {
+                let before = tetris.current_shape.clone();
+                match self {
+                    Move::RotateCW => tetris.move_rotate(),
+                    Move::RotateCCW => tetris.move_rotate_ccw(),
+                    _ => tetris.move_rotate_180(),
+                }
+                before.get_pixels().collect::<HashSet<_>>()
+                    != tetris.current_shape.get_pixels().collect()
+            }
+            Move::DasLeft | Move::DasRight => {
+                let dir = if self == Move::DasLeft {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                };
+                let before = tetris.current_shape.clone();
+                tetris.start_shift(dir);
+                while tetris.shift_current_shape(dir) {}
+                tetris.stop_shift(dir);
+                before.get_pixels().collect::<HashSet<_>>()
+                    != tetris.current_shape.get_pixels().collect()
+            }
+            Move::HardDrop => {
+                let falling = tetris.is_falling() && !tetris.is_paused() && !tetris.is_game_over();
+                tetris.hard_drop();
+                falling
+            }
+            Move::Hold => tetris.hold(),
+        }
+    }
+}
+
+/// A spot a shape can lock in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    /// the cells the shape locks into, row by row
+    pub cells: Vec<XY>,
+    /// the fewest moves that get it there, ends with Move::HardDrop
+    pub moves: Vec<Move>,
+}
+
+// the locked cells of the game, so the search does not have to go through every shape for every step
+struct Board {
+    width: i32,
+    height: i32,
+    filled: Vec<bool>,
+}
+
+impl Board {
+    fn new(tetris: &Tetris) -> Self {
+        let (width, height) = (tetris.width, tetris.height);
+        let mut filled = vec![false; (width * height) as usize];
+        for XY(x, y) in tetris
+            .sticky_bottom_shapes
+            .iter()
+            .flat_map(|shape| shape.get_pixels())
+        {
+            if x >= 0 && x < width && y >= 0 && y < height {
+                filled[(y * width + x) as usize] = true;
+            }
+        }
+        Self {
+            width,
+            height,
+            filled,
+        }
+    }
+
+    fn is_free(&self, XY(x, y): XY) -> bool {
+        x >= 0
+            && x < self.width
+            && y >= 0
+            && y < self.height
+            && !self.filled[(y * self.width + x) as usize]
+    }
+
+    fn fits(&self, shape: &Shape) -> bool {
+        shape.get_pixels().all(|xy| self.is_free(xy))
+    }
+
+    // (counts the rows on the cells, a new shape only gets made for where it lands)
+    fn drop_down(&self, shape: &Shape) -> Shape {
+        let cells: Vec<XY> = shape.get_pixels().collect();
+        let mut rows = 0;
+        while cells
+            .iter()
+            .all(|XY(x, y)| self.is_free(XY(*x, y + rows + 1)))
+        {
+            rows += 1;
+        }
+        shape + XY(0, rows)
+    }
+}
+
+// the cells of a shape, row by row
+fn sorted_cells(shape: &Shape) -> Vec<XY> {
+    let mut cells: Vec<XY> = shape.get_pixels().collect();
+    cells.sort_by_key(|xy| (xy.1, xy.0));
+    cells
+}
+
+// a state of the search: the shape and how often it got turned clockwise (mod 4),
+// different rotations can cover the same cells but turn differently from there
+struct Node {
+    shape: Shape,
+    rotation: u8,
+    /// what it costs to get here
+    cost: u32,
+    /// the node it was reached from, and with what move
+    parent: Option<(usize, Move)>,
+}
+
+/// Every spot the shape (as it is now, on the board of the game) can lock in, with the fewest moves to get there.
+/// Spots come in the order the search finds them, those with the shortest paths first.
+pub fn paths(tetris: &Tetris, shape: &Shape) -> Vec<Path> {
+    paths_with(tetris, shape, &Move::SHAPE_MOVES, |_| 1)
+}
+
+/// Like paths(), but only with the given moves, and each move costs what cost() says (0 or 1).
+/// Paths are the cheapest ones instead of the shortest ones.
+pub fn paths_with(
+    tetris: &Tetris,
+    shape: &Shape,
+    moves: &[Move],
+    cost: impl Fn(Move) -> u32,
+) -> Vec<Path> {
+    let board = Board::new(tetris);
+    let instant = tetris.gravity() >= INSTANT_GRAVITY;
+    let settle = |shape: Shape| {
+        if instant {
+            board.drop_down(&shape)
+        } else {
+            shape
+        }
+    };
+    let turn =
+        |rotated: Shape| Tetris::kick(&tetris.settings.piece_set, rotated, |s| board.fits(s));
+    let slide = |shape: &Shape, dir: XY| {
+        let mut slid = shape + dir;
+        if !board.fits(&slid) {
+            return None;
+        }
+        while board.fits(&(&slid + dir)) {
+            slid = &slid + dir;
+        }
+        Some(slid)
+    };
+    if !board.fits(shape) {
+        return vec![];
+    }
+
+    let mut nodes = vec![Node {
+        shape: settle(shape.clone()),
+        rotation: 0,
+        cost: 0,
+        parent: None,
+    }];
+    let mut seen = HashMap::from([((sorted_cells(&nodes[0].shape), 0), 0)]);
+    // (a 0-1 breadth first search: free moves go to the front of the queue)
+    let mut queue = VecDeque::from([(0, 0)]);
+    let mut locks: HashMap<Vec<XY>, usize> = HashMap::new();
+    let mut order = vec![];
+
+    while let Some((idx, at_cost)) = queue.pop_front() {
+        if at_cost > nodes[idx].cost {
+            continue; // got there cheaper in the meantime
+        }
+        let (shape, rotation) = (nodes[idx].shape.clone(), nodes[idx].rotation);
+        // a hard drop from here locks it where it lands:
+        let cells = sorted_cells(&board.drop_down(&shape));
+        if !locks.contains_key(&cells) {
+            locks.insert(cells.clone(), idx);
+            order.push(cells);
+        }
+        for &step in moves {
+            let moved = match step {
+                Move::Left => Some((&shape + XY(-1, 0), rotation)),
+                Move::Right => Some((&shape + XY(1, 0), rotation)),
+                Move::DasLeft => slide(&shape, XY(-1, 0)).map(|s| (s, rotation)),
+                Move::DasRight => slide(&shape, XY(1, 0)).map(|s| (s, rotation)),
+                Move::Down => Some((&shape + XY(0, 1), rotation)),
+                Move::SoftDrop => Some((board.drop_down(&shape), rotation)),
+                Move::RotateCW => turn(shape.rotated_shape()).map(|s| (s, (rotation + 1) % 4)),
+                Move::Rotate180 => {
+                    turn(shape.rotated_shape().rotated_shape()).map(|s| (s, (rotation + 2) % 4))
+                }
+                Move::RotateCCW => turn(shape.rotated_shape_ccw()).map(|s| (s, (rotation + 3) % 4)),
+                Move::HardDrop | Move::Hold => None,
+            };
+            let Some((moved, rotation)) = moved else {
+                continue;
+            };
+            if !board.fits(&moved) {
+                continue;
+            }
+            let moved = settle(moved);
+            let step_cost = cost(step);
+            let new_cost = nodes[idx].cost + step_cost;
+            let key = (sorted_cells(&moved), rotation);
+            let next = match seen.get(&key) {
+                Some(&known) if nodes[known].cost <= new_cost => continue,
+                Some(&known) => {
+                    nodes[known].cost = new_cost;
+                    nodes[known].parent = Some((idx, step));
+                    known
+                }
+                None => {
+                    nodes.push(Node {
+                        shape: moved,
+                        rotation,
+                        cost: new_cost,
+                        parent: Some((idx, step)),
+                    });
+                    seen.insert(key, nodes.len() - 1);
+                    nodes.len() - 1
+                }
+            };
+            if step_cost == 0 {
+                queue.push_front((next, new_cost));
+            } else {
+                queue.push_back((next, new_cost));
+            }
+        }
+    }
+
+    order
+        .into_iter()
+        .map(|cells| {
+            let mut moves = vec![Move::HardDrop];
+            let mut idx = locks[&cells];
+            while let Some((parent, step)) = nodes[idx].parent {
+                moves.push(step);
+                idx = parent;
+            }
+            moves.reverse();
+            Path { cells, moves }
+        })
+        .collect()
+}
+
+/// every spot the current shape can lock in (nothing while the game waits in a delay)
+pub fn placements(tetris: &Tetris) -> Vec<Path> {
+    if !tetris.is_falling() || tetris.is_game_over() {
+        return vec![];
+    }
+    paths(tetris, &tetris.current_shape)
+}
+
+/// the fewest moves that get the current shape into these cells, None if it can not get there
+pub fn path_to(tetris: &Tetris, cells: &[XY]) -> Option<Vec<Move>> {
+    let mut cells = cells.to_vec();
+    cells.sort_by_key(|xy| (xy.1, xy.0));
+    placements(tetris)
+        .into_iter()
+        .find(|path| path.cells == cells)
+        .map(|path| path.moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::{piece_set::PieceSet, shapes::PieceKind, Settings};
+
+    // a game with only the given piece of the standard set, on a board with these cells filled
+    fn game(kind: PieceKind, rows: &[&str]) -> Tetris {
+        let standard = PieceSet::standard();
+        let idx = (0..standard.len())
+            .find(|idx| standard.shape(*idx).get_kind() == kind)
+            .unwrap();
+        let mut tetris = Tetris::with_settings(10, rows.len() as u32, Settings::default());
+        tetris.current_shape = Tetris::spawn_shape(&standard, idx, 10);
+        for (y, row) in rows.iter().enumerate() {
+            let cells = row
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| *c == '#')
+                .map(|(x, _)| XY(x as i32, y as i32));
+            tetris
+                .sticky_bottom_shapes
+                .push(Shape::new(0, PieceKind::Garbage, cells, XY(0, 0)));
+        }
+        tetris
+    }
+
+    fn replay(mut tetris: Tetris, moves: &[Move]) -> Vec<XY> {
+        let locked = tetris.sticky_bottom_shapes.len();
+        for step in moves {
+            step.apply(&mut tetris);
+        }
+        sorted_cells(&tetris.sticky_bottom_shapes[locked])
+    }
+
+    #[test]
+    fn paths_replay_on_the_game() {
+        let rows = [
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..###.....",
+            "#.#####.##",
+        ];
+        for kind in [PieceKind::T, PieceKind::I, PieceKind::S, PieceKind::L] {
+            let tetris = game(kind, &rows);
+            let paths = placements(&tetris);
+            assert!(!paths.is_empty());
+            for path in paths {
+                assert_eq!(
+                    replay(game(kind, &rows), &path.moves),
+                    path.cells,
+                    "{:?} {:?}",
+                    kind,
+                    path.moves
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn finds_tucks_under_overhangs() {
+        // the O fits under the overhang on the left, but only by sliding in from the right:
+        let rows = [
+            "..........",
+            "..........",
+            "..........",
+            "####......",
+            "..........",
+            "..........",
+        ];
+        let tetris = game(PieceKind::O, &rows);
+        let tuck = [XY(0, 4), XY(1, 4), XY(0, 5), XY(1, 5)];
+        let moves = path_to(&tetris, &tuck).unwrap();
+        assert!(moves.contains(&Move::SoftDrop), "{:?}", moves);
+        assert_eq!(moves.last(), Some(&Move::HardDrop));
+        assert_eq!(replay(game(PieceKind::O, &rows), &moves), tuck);
+    }
+
+    #[test]
+    fn finds_t_spins() {
+        use crate::tetris_game::{event::Event, puzzle::Puzzle};
+        // the slot of the puzzle is covered at (2, 17), the T has to spin in after dropping:
+        let puzzle = Puzzle::by_name("T-spin double").unwrap();
+        let mut tetris = puzzle.start(Settings::default()).unwrap();
+        let tsd = [XY(2, 18), XY(3, 18), XY(4, 18), XY(3, 19)];
+        let moves = path_to(&tetris, &tsd).unwrap();
+        let [.., spin, Move::HardDrop] = moves[..] else {
+            panic!("{:?} does not end with a hard drop", moves);
+        };
+        assert!(
+            matches!(spin, Move::RotateCW | Move::RotateCCW | Move::Rotate180),
+            "{:?}",
+            moves
+        );
+        assert!(moves.contains(&Move::SoftDrop) || moves.contains(&Move::Down));
+
+        for step in &moves {
+            step.apply(&mut tetris);
+        }
+        assert!(tetris.take_events().contains(&Event::TSpin { lines: 2 }));
+    }
+
+    #[test]
+    fn paths_are_the_shortest() {
+        let tetris = game(PieceKind::O, &["..........", "..........", ".........."]);
+        for path in placements(&tetris) {
+            // an O on an empty board only needs to shift and drop:
+            let shifts = path
+                .moves
+                .iter()
+                .filter(|m| matches!(m, Move::Left | Move::Right))
+                .count();
+            assert_eq!(path.moves.len(), shifts + 1, "{:?}", path.moves);
+        }
+    }
+}
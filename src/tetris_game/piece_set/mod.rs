@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+use super::shapes::{PieceKind, Shape, XY};
+
+/*
+    Data driven piece definitions.
+        - a PieceSet is a list of PieceDefs, loaded from a json string like:
+            {
+                "name": "my-set",
+                "pieces": [
+                    { "name": "T", "kind": "T", "cells": [[0, 0], [0, 1], [-1, 1], [1, 1]], "center": [0, 1] }
+                ]
+            }
+        - cells and center are relative to the spawn position (x to the right, y down)
+        - kicks are optional, if missing the default wall kicks are used
+*/
+
+/// the offsets a rotation tries (in order) if the rotated shape itself does not fit
+pub const DEFAULT_KICKS: [XY; 4] = [XY(1, 0), XY(2, 0), XY(-1, 0), XY(-2, 0)];
+
+fn default_kicks() -> Vec<XY> {
+    DEFAULT_KICKS.to_vec()
+}
+
+/// Definition of one piece in a set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PieceDef {
+    pub name: String,
+    /// what the frontend draws the piece as
+    pub kind: PieceKind,
+    /// the filled pixels, relative to the spawn position
+    pub cells: Vec<XY>,
+    /// the point the piece rotates around
+    pub center: XY,
+    /// wall kicks, tried in order if the basic rotation is an illegal move
+    #[serde(default = "default_kicks")]
+    pub kicks: Vec<XY>,
+}
+
+/// A set of pieces a game draws its shapes from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PieceSet {
+    pub name: String,
+    pub pieces: Vec<PieceDef>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PieceSetError {
+    /// the string is not a valid piece set definition
+    Parse(String),
+    /// a set needs at least one piece
+    NoPieces,
+    /// every piece needs at least one cell
+    NoCells(String),
+}
+
+impl std::fmt::Display for PieceSetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PieceSetError::Parse(err) => write!(f, "invalid piece set: {}", err),
+            PieceSetError::NoPieces => write!(f, "piece set has no pieces"),
+            PieceSetError::NoCells(name) => write!(f, "piece '{}' has no cells", name),
+        }
+    }
+}
+
+impl std::error::Error for PieceSetError {}
+
+impl PieceSet {
+    /// loads and validates a piece set from its json definition
+    pub fn from_json(json: &str) -> Result<Self, PieceSetError> {
+        let set: PieceSet =
+            serde_json::from_str(json).map_err(|err| PieceSetError::Parse(err.to_string()))?;
+        set.validate()?;
+        Ok(set)
+    }
+
+    /// a game can be played with the set (it has pieces, and they have cells)
+    pub fn validate(&self) -> Result<(), PieceSetError> {
+        if self.pieces.is_empty() {
+            return Err(PieceSetError::NoPieces);
+        }
+        if let Some(piece) = self.pieces.iter().find(|piece| piece.cells.is_empty()) {
+            return Err(PieceSetError::NoCells(piece.name.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("piece set always serializes")
+    }
+
+    /// the 7 classic tetrominos I, J, L, O, S, T, Z
+    pub fn standard() -> Self {
+        Self::from_json(include_str!("standard.json")).expect("built in set is valid")
+    }
+
+    /// the 12 pentominos (5 pixels each)
+    pub fn pentomino() -> Self {
+        Self::from_json(include_str!("pentomino.json")).expect("built in set is valid")
+    }
+
+    /// monomino, domino and the two triominos
+    pub fn tiny() -> Self {
+        Self::from_json(include_str!("tiny.json")).expect("built in set is valid")
+    }
+
+    /// finds a built in set by its name
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(Self::standard()),
+            "pentomino" => Some(Self::pentomino()),
+            "tiny" => Some(Self::tiny()),
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    /// creates the shape for the piece with index idx (at the spawn-relative position)
+    pub fn shape(&self, idx: usize) -> Shape {
+        let def = &self.pieces[idx];
+        Shape::new(idx, def.kind, def.cells.iter().copied(), def.center)
+    }
+
+    /// the wall kicks of the piece with index idx
+    pub fn kicks(&self, idx: usize) -> &[XY] {
+        &self.pieces[idx].kicks
+    }
+
+    /// smallest and biggest XY any piece occupies at spawn (useful to size a preview)
+    pub fn bounds(&self) -> (XY, XY) {
+        let cells = self.pieces.iter().flat_map(|piece| piece.cells.iter());
+        let min = cells.clone().fold(XY(i32::MAX, i32::MAX), |min, xy| {
+            XY(min.0.min(xy.0), min.1.min(xy.1))
+        });
+        let max = cells.fold(XY(i32::MIN, i32::MIN), |max, xy| {
+            XY(max.0.max(xy.0), max.1.max(xy.1))
+        });
+        (min, max)
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_sets_load() {
+        assert_eq!(PieceSet::standard().len(), 7);
+        assert_eq!(PieceSet::pentomino().len(), 12);
+        assert_eq!(PieceSet::tiny().len(), 4);
+        assert!(PieceSet::pentomino()
+            .pieces
+            .iter()
+            .all(|p| p.cells.len() == 5));
+        assert_eq!(PieceSet::by_name("tiny"), Some(PieceSet::tiny()));
+        assert_eq!(PieceSet::by_name("hexomino"), None);
+    }
+
+    #[test]
+    fn kicks_default_when_missing() {
+        let set = PieceSet::from_json(
+            r#"{ "name": "dot", "pieces": [{ "name": "dot", "kind": "O", "cells": [[0, 0]], "center": [0, 0] }] }"#,
+        )
+        .unwrap();
+        assert_eq!(set.kicks(0), &DEFAULT_KICKS);
+        assert_eq!(set.shape(0).get_kind(), PieceKind::O);
+        assert!(set.shape(0).has_xy(XY(0, 0)));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let set = PieceSet::pentomino();
+        assert_eq!(PieceSet::from_json(&set.to_json()), Ok(set));
+    }
+
+    #[test]
+    fn rejects_invalid_sets() {
+        assert!(matches!(
+            PieceSet::from_json("{"),
+            Err(PieceSetError::Parse(_))
+        ));
+        assert_eq!(
+            PieceSet::from_json(r#"{ "name": "none", "pieces": [] }"#),
+            Err(PieceSetError::NoPieces)
+        );
+        assert_eq!(
+            PieceSet::from_json(
+                r#"{ "name": "x", "pieces": [{ "name": "empty", "kind": "T", "cells": [], "center": [0, 0] }] }"#
+            ),
+            Err(PieceSetError::NoCells("empty".into()))
+        );
+    }
+
+    #[test]
+    fn bounds_cover_all_pieces() {
+        assert_eq!(PieceSet::standard().bounds(), (XY(-1, 0), XY(2, 1)));
+        assert_eq!(PieceSet::pentomino().bounds(), (XY(-2, 0), XY(2, 2)));
+    }
+}
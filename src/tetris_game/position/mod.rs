@@ -0,0 +1,386 @@
+use super::piece_set::PieceSet;
+use super::rng::{self, QueueRng, Rng};
+use super::shapes::{PieceKind, XY};
+use super::{Settings, Tetris};
+
+/*
+    A position to start a game from: the locked cells of the board, the current piece, the queue and hold.
+        - Position::of() takes it from a running game, the board editor builds one cell by cell
+        - start() sets up a game from it, after the queue the pieces are random again
+        - to_code()/from_code() turn it into a short string to share, like
+            v1:10x20:184_T5_9G_:IOS:L
+          (version : size : the board row by row, run length encoded : current piece and queue : hold)
+          '_' is an empty cell, G garbage, I J L O S T Z a cell of that kind, a number in front repeats it
+*/
+
+/// version of the code to_code() writes
+const CODE_VERSION: &str = "v1";
+/// the most cells a position can have
+pub const MAX_CELLS: u32 = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    width: i32,
+    height: i32,
+    /// row by row
+    cells: Vec<Option<PieceKind>>,
+    /// the piece the game starts with, the first of the queue if None
+    pub current: Option<PieceKind>,
+    pub queue: Vec<PieceKind>,
+    pub hold: Option<PieceKind>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PositionError {
+    /// not a code to_code() could have written
+    Format(String),
+    /// a letter that is not a piece kind
+    UnknownKind(char),
+    /// the board does not have width * height cells
+    Size,
+}
+
+impl std::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PositionError::Format(err) => write!(f, "invalid position code: {}", err),
+            PositionError::UnknownKind(c) => write!(f, "unknown piece '{}'", c),
+            PositionError::Size => write!(f, "the board does not match its size"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+/// Reads pieces like "TIOJ" (no garbage, spaces get ignored)
+pub fn parse_pieces(text: &str) -> Result<Vec<PieceKind>, PositionError> {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match PieceKind::from_letter(c.to_ascii_uppercase()) {
+            Some(PieceKind::Garbage) | None => Err(PositionError::UnknownKind(c)),
+            Some(kind) => Ok(kind),
+        })
+        .collect()
+}
+
+/// The letters of the pieces, like "TIOJ"
+pub fn pieces_text(pieces: &[PieceKind]) -> String {
+    pieces.iter().map(|kind| kind.letter()).collect()
+}
+
+// the first piece of the set with that kind
+fn piece_index(piece_set: &PieceSet, kind: PieceKind) -> Option<usize> {
+    (0..piece_set.len()).find(|idx| piece_set.shape(*idx).get_kind() == kind)
+}
+
+impl Position {
+    /// an empty board, no pieces (at most MAX_CELLS big, a board bigger than that gets less rows)
+    pub fn empty(width: u32, height: u32) -> Self {
+        let width = width.min(MAX_CELLS);
+        let height = height.min(MAX_CELLS / width.max(1));
+        Self {
+            width: width as i32,
+            height: height as i32,
+            cells: vec![None; (width * height) as usize],
+            current: None,
+            queue: vec![],
+            hold: None,
+        }
+    }
+
+    /// the position of a running game (during a delay the next piece counts as the current one)
+    pub fn of(tetris: &Tetris) -> Self {
+        let (width, height) = tetris.get_size();
+        let mut position = Self::empty(width as u32, height as u32);
+        for shape in &tetris.sticky_bottom_shapes {
+            for xy in shape.get_pixels() {
+                position.set(xy, Some(shape.get_kind()));
+            }
+        }
+        let queue = tetris.next_shapes.iter().map(|shape| shape.get_kind());
+        position.current = tetris.is_falling().then(|| tetris.current_shape.get_kind());
+        position.queue = queue.collect();
+        position.hold = tetris.get_hold();
+        position
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    pub fn get(&self, XY(x, y): XY) -> Option<PieceKind> {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return None;
+        }
+        self.cells[(y * self.width + x) as usize]
+    }
+
+    /// fills (or empties) a cell, cells outside the board get ignored
+    pub fn set(&mut self, XY(x, y): XY, kind: Option<PieceKind>) {
+        if x >= 0 && x < self.width && y >= 0 && y < self.height {
+            self.cells[(y * self.width + x) as usize] = kind;
+        }
+    }
+
+    /// empties every cell (the pieces stay)
+    pub fn clear_board(&mut self) {
+        self.cells.fill(None);
+    }
+
+    /// the filled cells, row by row
+    pub fn cells(&self) -> impl Iterator<Item = (XY, PieceKind)> + '_ {
+        self.cells.iter().enumerate().filter_map(|(idx, kind)| {
+            let xy = XY(idx as i32 % self.width, idx as i32 / self.width);
+            kind.map(|kind| (xy, kind))
+        })
+    }
+
+    /// A game starting from the position, with random pieces after the queue
+    pub fn start(&self, settings: Settings) -> Tetris {
+        self.start_with_rng(settings, rng::default_rng())
+    }
+
+    /// like start(), the pieces after the queue come from the given Rng
+    pub fn start_with_rng(&self, settings: Settings, then: Box<dyn Rng>) -> Tetris {
+        let script = self
+            .current
+            .iter()
+            .chain(self.queue.iter())
+            .filter_map(|kind| piece_index(&settings.piece_set, *kind))
+            .collect();
+        let hold = self
+            .hold
+            .and_then(|kind| piece_index(&settings.piece_set, kind));
+        let (width, height) = (self.width as u32, self.height as u32);
+        let mut tetris = Tetris::with_rng(
+            width,
+            height,
+            settings,
+            Box::new(QueueRng::new(script, then)),
+        );
+        tetris.set_board(self.cells());
+        tetris.hold = hold;
+        tetris
+    }
+
+    /// the position as a short string, see from_code()
+    pub fn to_code(&self) -> String {
+        let mut board = String::new();
+        let mut cells = self.cells.iter().peekable();
+        while let Some(kind) = cells.next() {
+            let mut run = 1;
+            while cells.next_if_eq(&kind).is_some() {
+                run += 1;
+            }
+            if run > 1 {
+                board.push_str(&run.to_string());
+            }
+            board.push(kind.map(|kind| kind.letter()).unwrap_or('_'));
+        }
+        let pieces: Vec<PieceKind> = self
+            .current
+            .iter()
+            .chain(self.queue.iter())
+            .copied()
+            .collect();
+        format!(
+            "{}:{}x{}:{}:{}:{}",
+            CODE_VERSION,
+            self.width,
+            self.height,
+            board,
+            pieces_text(&pieces),
+            self.hold
+                .map(|kind| kind.letter().to_string())
+                .unwrap_or_default()
+        )
+    }
+
+    /// reads a position written by to_code() (the first piece becomes the current one)
+    pub fn from_code(code: &str) -> Result<Self, PositionError> {
+        let parts: Vec<&str> = code.trim().split(':').collect();
+        let [version, size, board, pieces, hold] = parts[..] else {
+            return Err(PositionError::Format("expected 5 parts".into()));
+        };
+        if version != CODE_VERSION {
+            return Err(PositionError::Format(format!(
+                "unknown version {}",
+                version
+            )));
+        }
+        let (width, height) = size
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)))
+            .filter(|(w, h)| *w > 0 && *h > 0 && (*w as u64) * (*h as u64) <= MAX_CELLS as u64)
+            .ok_or_else(|| PositionError::Format(format!("bad size {}", size)))?;
+
+        let mut cells = vec![];
+        let mut run = String::new();
+        for c in board.chars() {
+            if c.is_ascii_digit() {
+                run.push(c);
+                continue;
+            }
+            let kind = match c {
+                '_' => None,
+                _ => Some(PieceKind::from_letter(c).ok_or(PositionError::UnknownKind(c))?),
+            };
+            let n = if run.is_empty() {
+                1
+            } else {
+                run.parse::<usize>().map_err(|_| PositionError::Size)?
+            };
+            if cells
+                .len()
+                .checked_add(n)
+                .is_none_or(|len| len > (width * height) as usize)
+            {
+                return Err(PositionError::Size);
+            }
+            cells.extend(std::iter::repeat_n(kind, n));
+            run.clear();
+        }
+        if !run.is_empty() || cells.len() != (width * height) as usize {
+            return Err(PositionError::Size);
+        }
+
+        let mut pieces = parse_pieces(pieces)?.into_iter();
+        let hold = match parse_pieces(hold)?[..] {
+            [] => None,
+            [kind] => Some(kind),
+            _ => return Err(PositionError::Format("more than one hold piece".into())),
+        };
+        Ok(Self {
+            width: width as i32,
+            height: height as i32,
+            cells,
+            current: pieces.next(),
+            queue: pieces.collect(),
+            hold,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::rng::SeededRng;
+
+    fn position() -> Position {
+        let mut position = Position::empty(10, 20);
+        for x in 0..9 {
+            position.set(XY(x, 19), Some(PieceKind::Garbage));
+        }
+        position.set(XY(4, 18), Some(PieceKind::T));
+        position.current = Some(PieceKind::I);
+        position.queue = vec![PieceKind::O, PieceKind::S];
+        position.hold = Some(PieceKind::L);
+        position
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        let position = position();
+        let code = position.to_code();
+        assert_eq!(code, "v1:10x20:184_T5_9G_:IOS:L");
+        assert_eq!(Position::from_code(&code), Ok(position));
+        let empty = Position::empty(4, 4);
+        assert_eq!(empty.to_code(), "v1:4x4:16_::");
+        assert_eq!(Position::from_code(&empty.to_code()), Ok(empty));
+    }
+
+    #[test]
+    fn rejects_invalid_codes() {
+        assert!(matches!(
+            Position::from_code("hello"),
+            Err(PositionError::Format(_))
+        ));
+        assert!(matches!(
+            Position::from_code("v9:4x4:16_::"),
+            Err(PositionError::Format(_))
+        ));
+        assert!(matches!(
+            Position::from_code("v1:4:16_::"),
+            Err(PositionError::Format(_))
+        ));
+        assert!(matches!(
+            Position::from_code("v1:99999x99999:_::"),
+            Err(PositionError::Format(_))
+        ));
+        assert_eq!(
+            Position::from_code("v1:4x4:15_::"),
+            Err(PositionError::Size)
+        );
+        assert_eq!(
+            Position::from_code("v1:4x4:17_::"),
+            Err(PositionError::Size)
+        );
+        assert_eq!(
+            Position::from_code("v1:4x4:_18446744073709551615_::"),
+            Err(PositionError::Size)
+        );
+        assert_eq!(
+            Position::from_code("v1:4x4:16X::"),
+            Err(PositionError::UnknownKind('X'))
+        );
+        assert_eq!(
+            Position::from_code("v1:4x4:16_:TG:"),
+            Err(PositionError::UnknownKind('G'))
+        );
+        assert!(matches!(
+            Position::from_code("v1:4x4:16_::TI"),
+            Err(PositionError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn empty_positions_stay_small() {
+        assert_eq!(Position::empty(10, 20).size(), (10, 20));
+        assert_eq!(Position::empty(1 << 16, 1 << 16).size(), (1 << 16, 1));
+        let position = Position::empty(100, u32::MAX);
+        assert_eq!(position.size(), (100, 655));
+        assert!(position.cells.len() <= MAX_CELLS as usize);
+    }
+
+    #[test]
+    fn games_start_from_the_position() {
+        let position = position();
+        let mut tetris = position.start_with_rng(Settings::default(), Box::new(SeededRng::new(1)));
+        let frame = tetris.snapshot();
+        assert_eq!(frame.active_kind, PieceKind::I);
+        assert_eq!(frame.cell(XY(0, 19)), Some(PieceKind::Garbage));
+        assert_eq!(frame.cell(XY(4, 18)), Some(PieceKind::T));
+        assert_eq!(tetris.get_hold(), Some(PieceKind::L));
+        assert_eq!(
+            Position::of(&tetris),
+            position_with_random_queue(&position, &tetris)
+        );
+        // the queue comes next:
+        tetris.hard_drop();
+        assert_eq!(tetris.get_current_shape().get_kind(), PieceKind::O);
+        tetris.hard_drop();
+        assert_eq!(tetris.get_current_shape().get_kind(), PieceKind::S);
+    }
+
+    // the game previews more pieces than the position had, those are random
+    fn position_with_random_queue(position: &Position, tetris: &Tetris) -> Position {
+        let mut expected = position.clone();
+        expected.queue = tetris
+            .next_shapes
+            .iter()
+            .map(|shape| shape.get_kind())
+            .collect();
+        assert_eq!(expected.queue[..2], position.queue[..]);
+        expected
+    }
+
+    #[test]
+    fn pieces_parse() {
+        assert_eq!(
+            parse_pieces("t i o"),
+            Ok(vec![PieceKind::T, PieceKind::I, PieceKind::O])
+        );
+        assert_eq!(pieces_text(&[PieceKind::Z, PieceKind::J]), "ZJ");
+        assert_eq!(parse_pieces("TQ"), Err(PositionError::UnknownKind('Q')));
+    }
+}
@@ -0,0 +1,414 @@
+use serde::{Deserialize, Serialize};
+
+use super::event::Event;
+use super::piece_set::PieceSet;
+use super::rng::ScriptedRng;
+use super::shapes::{PieceKind, XY};
+use super::{Settings, Tetris};
+
+/*
+    Puzzles: a starting board, a fixed sequence of pieces and a goal to reach with them.
+        - loaded from a json string like:
+            {
+                "name": "T-spin double",
+                "goal": { "t-spin": 2 },
+                "pieces": "T",
+                "board": [
+                    "###.......",
+                    "##...#####",
+                    "###.######"
+                ]
+            }
+        - board rows sit at the bottom of the field, '.' is empty, '#' (or G) garbage, I J L O S T Z cells of that kind
+        - pieces are the kinds of the standard set, dealt in order
+        - goals: { "lines": 4 }, { "t-spin": 2 } (a T-spin clearing that many lines) or "perfect-clear"
+    PuzzleRun follows the events of the game to tell when the puzzle got solved (or failed).
+*/
+
+fn default_height() -> u32 {
+    20
+}
+
+/// What a puzzle asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Goal {
+    /// clear at least that many lines in total
+    Lines(u32),
+    /// a T-spin that clears that many lines
+    TSpin(u32),
+    /// clear lines until the board is empty
+    PerfectClear,
+}
+
+impl Goal {
+    pub fn describe(&self) -> String {
+        match self {
+            Goal::Lines(1) => "clear a line".to_string(),
+            Goal::Lines(lines) => format!("clear {} lines", lines),
+            Goal::TSpin(0) => "perform a T-spin".to_string(),
+            Goal::TSpin(1) => "perform a T-spin single".to_string(),
+            Goal::TSpin(2) => "perform a T-spin double".to_string(),
+            Goal::TSpin(lines) => format!("perform a T-spin clearing {} lines", lines),
+            Goal::PerfectClear => "perfect clear".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    /// the pieces in the order they get dealt, like "TIO"
+    pub pieces: String,
+    /// the rows at the bottom of the field, all of them as wide as the field
+    pub board: Vec<String>,
+    #[serde(default = "default_height")]
+    pub height: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PuzzleError {
+    /// the string is not a valid puzzle definition
+    Parse(String),
+    /// a puzzle needs at least one piece
+    NoPieces,
+    /// a piece or board cell that is not a kind
+    UnknownKind(char),
+    /// the board needs at least one row, all of the same width
+    BoardWidth,
+    /// more board rows than the field is high
+    BoardHeight,
+}
+
+impl std::fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PuzzleError::Parse(err) => write!(f, "invalid puzzle: {}", err),
+            PuzzleError::NoPieces => write!(f, "puzzle has no pieces"),
+            PuzzleError::UnknownKind(c) => write!(f, "unknown piece '{}'", c),
+            PuzzleError::BoardWidth => write!(f, "board rows need to be equally wide"),
+            PuzzleError::BoardHeight => write!(f, "board does not fit the field"),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+// the kind a character of the puzzle format stands for ('.' is no kind at all)
+fn kind_of(c: char) -> Result<Option<PieceKind>, PuzzleError> {
+    Ok(match c {
+        '.' => None,
+        '#' => Some(PieceKind::Garbage),
+        _ => Some(PieceKind::from_letter(c).ok_or(PuzzleError::UnknownKind(c))?),
+    })
+}
+
+impl Puzzle {
+    /// loads and validates a puzzle from its json definition
+    pub fn from_json(json: &str) -> Result<Self, PuzzleError> {
+        let puzzle: Puzzle =
+            serde_json::from_str(json).map_err(|err| PuzzleError::Parse(err.to_string()))?;
+        puzzle.validate()?;
+        Ok(puzzle)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("puzzle always serializes")
+    }
+
+    fn validate(&self) -> Result<(), PuzzleError> {
+        if self.pieces.is_empty() {
+            return Err(PuzzleError::NoPieces);
+        }
+        for c in self.pieces.chars() {
+            if kind_of(c)?.is_none_or(|kind| kind == PieceKind::Garbage) {
+                return Err(PuzzleError::UnknownKind(c));
+            }
+        }
+        let width = self.width();
+        if width == 0
+            || self
+                .board
+                .iter()
+                .any(|row| row.chars().count() != width as usize)
+        {
+            return Err(PuzzleError::BoardWidth);
+        }
+        if self.board.len() > self.height as usize {
+            return Err(PuzzleError::BoardHeight);
+        }
+        self.cells().map(|_| ())
+    }
+
+    /// the built in puzzle pack
+    pub fn pack() -> Vec<Puzzle> {
+        let pack: Vec<Puzzle> =
+            serde_json::from_str(include_str!("pack.json")).expect("built in puzzles are valid");
+        debug_assert!(pack.iter().all(|puzzle| puzzle.validate().is_ok()));
+        pack
+    }
+
+    /// finds a puzzle of the built in pack by its name
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::pack().into_iter().find(|puzzle| puzzle.name == name)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.board
+            .first()
+            .map(|row| row.chars().count())
+            .unwrap_or(0) as u32
+    }
+
+    /// the filled cells of the board, on a field of the puzzles height
+    pub fn cells(&self) -> Result<Vec<(XY, PieceKind)>, PuzzleError> {
+        let top = self.height as i32 - self.board.len() as i32;
+        let mut cells = vec![];
+        for (y, row) in self.board.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if let Some(kind) = kind_of(c)? {
+                    cells.push((XY(x as i32, top + y as i32), kind));
+                }
+            }
+        }
+        Ok(cells)
+    }
+
+    /// the pieces as indices into the standard set
+    fn piece_indices(&self, piece_set: &PieceSet) -> Vec<usize> {
+        self.pieces
+            .chars()
+            .filter_map(|c| kind_of(c).ok().flatten())
+            .filter_map(|kind| {
+                (0..piece_set.len()).find(|idx| piece_set.shape(*idx).get_kind() == kind)
+            })
+            .collect()
+    }
+
+    /// A game set up for the puzzle: its board, its pieces (with the standard set) and otherwise the settings.
+    /// Errors like from_json() for a puzzle that got put together by hand.
+    pub fn start(&self, settings: Settings) -> Result<Tetris, PuzzleError> {
+        self.validate()?;
+        let piece_set = PieceSet::standard();
+        let pieces = self.piece_indices(&piece_set);
+        // no need to preview what comes after the last piece:
+        let preview_len = settings.preview_len.min(pieces.len() - 1).max(1);
+        let settings = Settings {
+            piece_set,
+            preview_len,
+            ..settings
+        };
+        // (once: the preview and the hold end with the script, the game is over after the last piece)
+        let rng = Box::new(ScriptedRng::once(pieces));
+        let mut tetris = Tetris::with_rng(self.width(), self.height, settings, rng);
+        tetris.set_board(self.cells()?);
+        Ok(tetris)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PuzzleStatus {
+    Playing,
+    Solved,
+    /// ran out of pieces (or topped out) before reaching the goal
+    Failed,
+}
+
+/// Keeps track of a puzzle being played, feed it every event of the game with observe()
+#[derive(Debug, Clone)]
+pub struct PuzzleRun {
+    goal: Goal,
+    pieces: usize,
+    locked: usize,
+    lines: u32,
+    status: PuzzleStatus,
+}
+
+impl PuzzleRun {
+    pub fn new(puzzle: &Puzzle) -> Self {
+        Self {
+            goal: puzzle.goal,
+            pieces: puzzle.pieces.chars().count(),
+            locked: 0,
+            lines: 0,
+            status: PuzzleStatus::Playing,
+        }
+    }
+
+    pub fn goal(&self) -> Goal {
+        self.goal
+    }
+
+    pub fn status(&self) -> PuzzleStatus {
+        self.status
+    }
+
+    /// pieces that did not lock yet
+    pub fn pieces_left(&self) -> usize {
+        self.pieces.saturating_sub(self.locked)
+    }
+
+    /// the status after the event
+    pub fn observe(&mut self, event: &Event) -> PuzzleStatus {
+        if self.status != PuzzleStatus::Playing {
+            return self.status;
+        }
+        let solved = match (event, self.goal) {
+            (Event::Lock(_), _) => {
+                self.locked += 1;
+                false
+            }
+            (Event::LineClear { lines, .. }, Goal::Lines(goal)) => {
+                self.lines += lines;
+                self.lines >= goal
+            }
+            (Event::TSpin { lines }, Goal::TSpin(goal)) => *lines == goal,
+            (Event::PerfectClear, Goal::PerfectClear) => true,
+            // a piece after the last one, the goal did not get reached with the pieces:
+            (Event::Spawn(_), _) if self.locked >= self.pieces => {
+                self.status = PuzzleStatus::Failed;
+                false
+            }
+            (Event::GameOver, _) => {
+                self.status = PuzzleStatus::Failed;
+                false
+            }
+            _ => false,
+        };
+        if solved {
+            self.status = PuzzleStatus::Solved;
+        }
+        self.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::movegen::Move;
+
+    // plays the moves, the status after all the events
+    fn play(puzzle: &Puzzle, moves: &[Move]) -> PuzzleStatus {
+        let mut tetris = puzzle.start(Settings::default()).unwrap();
+        let mut run = PuzzleRun::new(puzzle);
+        for step in moves {
+            step.apply(&mut tetris);
+            for event in tetris.take_events() {
+                run.observe(&event);
+            }
+        }
+        run.status()
+    }
+
+    #[test]
+    fn pack_loads_and_round_trips() {
+        let pack = Puzzle::pack();
+        assert!(pack.len() >= 4);
+        for puzzle in pack {
+            assert_eq!(Puzzle::from_json(&puzzle.to_json()), Ok(puzzle.clone()));
+            assert_eq!(Puzzle::by_name(&puzzle.name), Some(puzzle));
+        }
+    }
+
+    #[test]
+    fn starts_from_the_board_and_the_script() {
+        let puzzle = Puzzle::by_name("T-spin double").unwrap();
+        let tetris = puzzle.start(Settings::default()).unwrap();
+        let frame = tetris.snapshot();
+        assert_eq!(frame.active_kind, PieceKind::T);
+        assert_eq!(frame.cell(XY(0, 17)), Some(PieceKind::Garbage));
+        assert_eq!(frame.cell(XY(3, 17)), None);
+        assert_eq!(frame.cell(XY(3, 19)), None);
+    }
+
+    #[test]
+    fn pack_puzzles_are_solvable() {
+        use Move::*;
+        let solutions: [(&str, &[Move]); 4] = [
+            ("First Tetris", &[Down, RotateCW, DasRight, HardDrop]),
+            ("T-slot", &[Rotate180, Left, Left, Left, HardDrop]),
+            (
+                "T-spin double",
+                &[RotateCW, Left, Left, SoftDrop, RotateCW, HardDrop],
+            ),
+            ("Two squares", &[Left, Left, HardDrop, HardDrop]),
+        ];
+        for (name, moves) in solutions {
+            let puzzle = Puzzle::by_name(name).unwrap();
+            assert_eq!(play(&puzzle, moves), PuzzleStatus::Solved, "{}", name);
+        }
+    }
+
+    #[test]
+    fn fails_when_the_pieces_run_out() {
+        let puzzle = Puzzle::by_name("T-spin double").unwrap();
+        // a plain drop fits, but is no T-spin:
+        assert_eq!(play(&puzzle, &[Move::HardDrop]), PuzzleStatus::Failed);
+    }
+
+    #[test]
+    fn preview_ends_with_the_script() {
+        let puzzle = Puzzle {
+            pieces: "TIOS".into(),
+            ..Puzzle::by_name("Two squares").unwrap()
+        };
+        let settings = Settings {
+            preview_len: 5,
+            ..Default::default()
+        };
+        let mut tetris = puzzle.start(settings).unwrap();
+        let queue = |tetris: &Tetris| {
+            let current = tetris.get_current_shape().get_kind();
+            let next = tetris.next_queue(5).map(|shape| shape.get_kind());
+            std::iter::once(current).chain(next).collect::<Vec<_>>()
+        };
+        use PieceKind::*;
+        assert_eq!(queue(&tetris), [T, I, O, S]);
+        assert!(tetris.hold());
+        assert_eq!(queue(&tetris), [I, O, S]);
+        tetris.hard_drop();
+        assert_eq!(queue(&tetris), [O, S]);
+        tetris.hard_drop();
+        assert_eq!(queue(&tetris), [S]);
+        assert_eq!(tetris.preview_len(), 3);
+        // the T still comes out of the hold, but after that there is nothing more to deal:
+        assert!(tetris.hold());
+        assert_eq!(queue(&tetris), [T]);
+        tetris.hard_drop();
+        assert!(tetris.is_game_over());
+    }
+
+    #[test]
+    fn rejects_invalid_puzzles() {
+        assert!(matches!(Puzzle::from_json("{"), Err(PuzzleError::Parse(_))));
+        let puzzle = |pieces: &str, board: &str| {
+            Puzzle::from_json(&format!(
+                r#"{{ "name": "x", "goal": "perfect-clear", "pieces": "{}", "board": [{}] }}"#,
+                pieces, board
+            ))
+        };
+        assert_eq!(puzzle("", r#"".....""#), Err(PuzzleError::NoPieces));
+        assert_eq!(
+            puzzle("TX", r#"".....""#),
+            Err(PuzzleError::UnknownKind('X'))
+        );
+        assert_eq!(
+            puzzle("T", r#"".....", "....""#),
+            Err(PuzzleError::BoardWidth)
+        );
+        assert_eq!(
+            puzzle("T", r#""..?..""#),
+            Err(PuzzleError::UnknownKind('?'))
+        );
+        assert!(puzzle("T", r#""..T..""#).is_ok());
+
+        // put together by hand, start() checks it:
+        let mut puzzle = Puzzle::by_name("T-spin double").unwrap();
+        puzzle.pieces.clear();
+        assert!(matches!(
+            puzzle.start(Settings::default()),
+            Err(PuzzleError::NoPieces)
+        ));
+    }
+}
@@ -0,0 +1,561 @@
+use serde::{Deserialize, Serialize};
+
+use super::rng::SeededRng;
+use super::timing::{Gravity, Timing};
+use super::{Direction, Settings, Tetris};
+
+/*
+    Replays: a game as its seed, its settings and the inputs with the frame they happened on.
+        - record() applies an input to the game and writes it down, finish() notes where the game ended
+        - simulate() plays the replay again from the start, verify() checks a submitted score against it:
+          the score only counts if the replay comes out exactly the same, and was played by the rules of the leaderboard
+          (a Ruleset: the board size and the settings, without the ones that are only up to the player)
+    Why this plays out the same in the browser (wasm) and natively:
+        - time is counted in fixed frames (see timing), an input belongs to the frame count it happened at,
+          so how often and how regularly the frontend called update() does not matter
+        - the pieces come from a SeededRng (integer math only)
+        - the only floats in the simulation are the gravity (multiplications, divisions and ceil(),
+          which IEEE 754 defines exactly, no powf etc.), so both targets round the same
+    Games with the thread rng can not be replayed, recording starts a seeded game.
+*/
+
+/// version of the replay format, replays of other versions get rejected
+pub const REPLAY_VERSION: u32 = 1;
+/// longest replay that gets simulated (two hours)
+pub const MAX_FRAMES: u64 = 2 * 60 * 60 * 60;
+/// largest board a replay can have
+pub const MAX_SIZE: u32 = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    /// not a replay (or submission) in json
+    Parse(String),
+    /// written by another version of the format
+    Version(u32),
+    /// the board size or settings can not be played
+    Settings(String),
+    /// played by other rules than the ones asked for
+    Ruleset {
+        expected: Box<Ruleset>,
+        actual: Box<Ruleset>,
+    },
+    /// inputs out of order, after the end, or while time could not pass
+    Inputs(String),
+    /// longer than MAX_FRAMES
+    TooLong(u64),
+    /// the replay plays out differently than claimed
+    Mismatch { claimed: Outcome, actual: Outcome },
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Parse(err) => write!(f, "invalid replay: {}", err),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay version {} is not supported (only {})",
+                version, REPLAY_VERSION
+            ),
+            ReplayError::Settings(err) => write!(f, "invalid settings: {}", err),
+            ReplayError::Ruleset { expected, actual } => write!(
+                f,
+                "played by other rules: {} instead of {}",
+                actual, expected
+            ),
+            ReplayError::Inputs(err) => write!(f, "invalid inputs: {}", err),
+            ReplayError::TooLong(frames) => write!(
+                f,
+                "replay is too long ({} frames, at most {})",
+                frames, MAX_FRAMES
+            ),
+            ReplayError::Mismatch { claimed, actual } => write!(
+                f,
+                "the replay does not match: claimed {}, replayed {}",
+                claimed, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Everything a player can do to the game (what Input::apply() and the js_api call)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Command {
+    /// one step left/right, without auto shift
+    Left,
+    Right,
+    /// left/right pressed (moves, then auto shifts) and released
+    ShiftLeft,
+    ShiftRight,
+    StopLeft,
+    StopRight,
+    /// soft drop pressed and released
+    SoftDrop,
+    StopSoftDrop,
+    /// one step down (without soft drop gravity)
+    Down,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+    Pause,
+}
+
+impl Command {
+    pub fn apply(self, tetris: &mut Tetris) {
+        match self {
+            Command::Left => {
+                tetris.move_current_shape(Direction::Left);
+            }
+            Command::Right => {
+                tetris.move_current_shape(Direction::Right);
+            }
+            Command::ShiftLeft => tetris.start_shift(Direction::Left),
+            Command::ShiftRight => tetris.start_shift(Direction::Right),
+            Command::StopLeft => tetris.stop_shift(Direction::Left),
+            Command::StopRight => tetris.stop_shift(Direction::Right),
+            Command::SoftDrop => tetris.set_soft_drop(true),
+            Command::StopSoftDrop => tetris.set_soft_drop(false),
+            Command::Down => {
+                tetris.move_down();
+            }
+            Command::HardDrop => tetris.hard_drop(),
+            Command::RotateCw => tetris.move_rotate(),
+            Command::RotateCcw => tetris.move_rotate_ccw(),
+            Command::Rotate180 => tetris.move_rotate_180(),
+            Command::Hold => {
+                tetris.hold();
+            }
+            Command::Pause => tetris.toggle_pause(),
+        }
+    }
+}
+
+/// How a game ended up, what a score submission claims
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Outcome {
+    pub score: i32,
+    pub lines: u32,
+    pub level: u32,
+    /// frames the game ran
+    pub frames: u64,
+    pub game_over: bool,
+}
+
+impl Outcome {
+    pub fn of(tetris: &Tetris) -> Self {
+        Self {
+            score: tetris.score(),
+            lines: tetris.get_lines(),
+            level: tetris.get_level(),
+            frames: tetris.frame_count(),
+            game_over: tetris.is_game_over(),
+        }
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "score {} lines {} level {} after {} frames{}",
+            self.score,
+            self.lines,
+            self.level,
+            self.frames,
+            if self.game_over { " (game over)" } else { "" }
+        )
+    }
+}
+
+/// The rules a game got played by: board size and settings. Scores are only comparable under the same rules.
+/// Preferences of the player (preview length, finesse checking, DAS and ARR) are left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Ruleset {
+    width: u32,
+    height: u32,
+    settings: Settings,
+}
+
+impl Default for Ruleset {
+    // the standard game
+    fn default() -> Self {
+        Self::new(10, 20, Settings::default())
+    }
+}
+
+impl Ruleset {
+    pub fn new(width: u32, height: u32, settings: Settings) -> Self {
+        let defaults = Settings::default();
+        Self {
+            width,
+            height,
+            settings: Settings {
+                preview_len: defaults.preview_len,
+                finesse: defaults.finesse,
+                timing: Timing {
+                    das_frames: defaults.timing.das_frames,
+                    arr_frames: defaults.timing.arr_frames,
+                    ..settings.timing
+                },
+                ..settings
+            },
+        }
+    }
+
+    /// the rules the replay got played by
+    pub fn of(replay: &Replay) -> Self {
+        Self::new(replay.width, replay.height, replay.settings.clone())
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+}
+
+impl std::fmt::Display for Ruleset {
+    // like "10x20" for the standard game, "4x12, other pieces, from level 5" otherwise
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let defaults = Self::default().settings;
+        write!(f, "{}x{}", self.width, self.height)?;
+        if self.settings.piece_set != defaults.piece_set {
+            write!(f, ", other pieces")?;
+        }
+        if self.settings.timing != defaults.timing {
+            write!(f, ", other timing")?;
+        }
+        if self.settings.start_level != defaults.start_level {
+            write!(f, ", from level {}", self.settings.start_level)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub settings: Settings,
+    /// the frame count of the game when the input happened, and the input (in order)
+    pub inputs: Vec<(u64, Command)>,
+    /// the frame count the game ended at
+    pub frames: u64,
+}
+
+/// A score with the replay that has to prove it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Submission {
+    pub outcome: Outcome,
+    pub replay: Replay,
+}
+
+impl Replay {
+    /// a new replay, start() gives the game to record
+    pub fn new(width: u32, height: u32, settings: Settings, seed: u64) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            width,
+            height,
+            settings,
+            inputs: vec![],
+            frames: 0,
+        }
+    }
+
+    /// the game as it was at frame 0
+    pub fn start(&self) -> Result<Tetris, ReplayError> {
+        if self.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(self.version));
+        }
+        let size = 1..=MAX_SIZE;
+        if !size.contains(&self.width) || !size.contains(&self.height) {
+            return Err(ReplayError::Settings(format!(
+                "a {}x{} board",
+                self.width, self.height
+            )));
+        }
+        self.settings
+            .piece_set
+            .validate()
+            .map_err(|err| ReplayError::Settings(err.to_string()))?;
+        check_timing(&self.settings.timing)?;
+        Ok(Tetris::with_rng(
+            self.width,
+            self.height,
+            self.settings.clone(),
+            Box::new(SeededRng::new(self.seed)),
+        ))
+    }
+
+    /// applies the input to the game (started with start()) and writes it down
+    pub fn record(&mut self, tetris: &mut Tetris, command: Command) {
+        self.inputs.push((tetris.frame_count(), command));
+        command.apply(tetris);
+        self.frames = tetris.frame_count();
+    }
+
+    /// notes where the game ended, the submission for its score
+    pub fn finish(&mut self, tetris: &Tetris) -> Submission {
+        self.frames = tetris.frame_count();
+        Submission {
+            outcome: Outcome::of(tetris),
+            replay: self.clone(),
+        }
+    }
+
+    /// Plays the replay from the start, returns how it ends
+    pub fn simulate(&self) -> Result<Outcome, ReplayError> {
+        if self.frames > MAX_FRAMES {
+            return Err(ReplayError::TooLong(self.frames));
+        }
+        let mut tetris = self.start()?;
+        for (frame, command) in &self.inputs {
+            if *frame > self.frames {
+                return Err(ReplayError::Inputs(format!(
+                    "input at frame {} after the end",
+                    frame
+                )));
+            }
+            run_until(&mut tetris, *frame)?;
+            if tetris.frame_count() > *frame {
+                return Err(ReplayError::Inputs(format!(
+                    "input at frame {} out of order",
+                    frame
+                )));
+            }
+            command.apply(&mut tetris);
+        }
+        run_until(&mut tetris, self.frames)?;
+        Ok(Outcome::of(&tetris))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("replays always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ReplayError> {
+        serde_json::from_str(json).map_err(|err| ReplayError::Parse(err.to_string()))
+    }
+}
+
+// timings the game can run with (no negative or NaN speeds)
+fn check_timing(timing: &Timing) -> Result<(), ReplayError> {
+    if let Gravity::Fixed(gravity) = timing.gravity {
+        if !(gravity >= 0.0 && gravity.is_finite()) {
+            return Err(ReplayError::Settings(format!("gravity of {}G", gravity)));
+        }
+    }
+    let factor = timing.soft_drop_factor;
+    if !(factor > 0.0 && factor.is_finite()) {
+        return Err(ReplayError::Settings(format!(
+            "soft drop factor of {}",
+            factor
+        )));
+    }
+    Ok(())
+}
+
+// simulates frames until the frame count (or the end of the game)
+fn run_until(tetris: &mut Tetris, frame: u64) -> Result<(), ReplayError> {
+    while tetris.frame_count() < frame && !tetris.is_game_over() {
+        // update() does not run frames while paused, a real game can not have any:
+        if tetris.is_paused() {
+            return Err(ReplayError::Inputs(format!(
+                "frames pass while paused (at frame {})",
+                tetris.frame_count()
+            )));
+        }
+        tetris.frame();
+    }
+    Ok(())
+}
+
+impl Submission {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("submissions always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ReplayError> {
+        serde_json::from_str(json).map_err(|err| ReplayError::Parse(err.to_string()))
+    }
+}
+
+/// Re-simulates the replay of the submission, Ok only if it got played by the rules and ends exactly as claimed
+pub fn verify(submission: &Submission, rules: &Ruleset) -> Result<Outcome, ReplayError> {
+    let expected = Ruleset::new(rules.width, rules.height, rules.settings.clone());
+    let actual = Ruleset::of(&submission.replay);
+    if actual != expected {
+        return Err(ReplayError::Ruleset {
+            expected: Box::new(expected),
+            actual: Box::new(actual),
+        });
+    }
+    let actual = submission.replay.simulate()?;
+    if actual != submission.outcome {
+        return Err(ReplayError::Mismatch {
+            claimed: submission.outcome,
+            actual,
+        });
+    }
+    Ok(actual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::piece_set::PieceSet;
+
+    // a game played by the clock: every input between two update()s with uneven time steps
+    // (small pieces on a narrow board, so lines get cleared)
+    fn play(seed: u64, settings: Settings) -> Submission {
+        let settings = Settings {
+            piece_set: PieceSet::tiny(),
+            ..settings
+        };
+        let mut replay = Replay::new(4, 12, settings, seed);
+        let mut tetris = replay.start().unwrap();
+        let steps = [7.0, 16.0, 33.3, 5.5, 50.0];
+        let commands = [
+            Command::ShiftLeft,
+            Command::StopLeft,
+            Command::HardDrop,
+            Command::RotateCw,
+            Command::SoftDrop,
+            Command::StopSoftDrop,
+            Command::HardDrop,
+            Command::ShiftRight,
+            Command::StopRight,
+            Command::HardDrop,
+            Command::Hold,
+            Command::Right,
+            Command::Left,
+            Command::Left,
+            Command::HardDrop,
+        ];
+        for (idx, command) in commands.iter().cycle().take(300).enumerate() {
+            tetris.update(steps[idx % steps.len()]);
+            replay.record(&mut tetris, *command);
+        }
+        tetris.update(500.0);
+        replay.finish(&tetris)
+    }
+
+    #[test]
+    fn replays_play_out_the_same() {
+        let settings = Settings {
+            timing: Timing {
+                gravity: Gravity::Fixed(0.1),
+                are_frames: 3,
+                line_clear_frames: 5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let submission = play(42, settings);
+        assert!(submission.outcome.frames > 0);
+        assert_eq!(
+            verify(&submission, &Ruleset::of(&submission.replay)),
+            Ok(submission.outcome)
+        );
+
+        // and the same after going through json:
+        let json = submission.to_json();
+        assert_eq!(Submission::from_json(&json), Ok(submission.clone()));
+        assert_eq!(
+            verify(
+                &Submission::from_json(&json).unwrap(),
+                &Ruleset::of(&submission.replay)
+            ),
+            Ok(submission.outcome)
+        );
+    }
+
+    #[test]
+    fn faked_scores_get_rejected() {
+        let submission = play(2, Settings::default());
+        let rules = Ruleset::of(&submission.replay);
+        assert!(submission.outcome.lines > 0);
+        let mut faked = submission.clone();
+        faked.outcome.score += 1000;
+        assert!(matches!(
+            verify(&faked, &rules),
+            Err(ReplayError::Mismatch { actual, .. }) if actual == submission.outcome
+        ));
+        // another seed deals other pieces:
+        let mut reseeded = submission.clone();
+        reseeded.replay.seed = 3;
+        assert!(verify(&reseeded, &rules).is_err());
+        // an input that did not happen:
+        let mut edited = submission.clone();
+        edited
+            .replay
+            .inputs
+            .insert(3, (edited.replay.inputs[2].0, Command::HardDrop));
+        assert!(verify(&edited, &rules).is_err());
+
+        // a real game, but by easier rules (small board, tiny pieces) than the standard game:
+        assert!(matches!(
+            verify(&submission, &Ruleset::default()),
+            Err(ReplayError::Ruleset { .. })
+        ));
+        // preferences of the player do not change the rules:
+        let mut settings = submission.replay.settings.clone();
+        settings.preview_len = 1;
+        settings.timing.das_frames = 4;
+        assert_eq!(
+            verify(&submission, &Ruleset::new(4, 12, settings)),
+            Ok(submission.outcome)
+        );
+        assert_eq!(rules.to_string(), "4x12, other pieces");
+        assert_eq!(Ruleset::default().to_string(), "10x20");
+    }
+
+    #[test]
+    fn broken_replays_get_rejected() {
+        let replay = play(1, Settings::default()).replay;
+        let mut reversed = replay.clone();
+        reversed.inputs.reverse();
+        assert!(matches!(reversed.simulate(), Err(ReplayError::Inputs(_))));
+
+        let mut paused = Replay::new(10, 20, Settings::default(), 1);
+        paused.inputs = vec![(0, Command::Pause)];
+        paused.frames = 10;
+        assert!(matches!(paused.simulate(), Err(ReplayError::Inputs(_))));
+
+        let mut long = replay.clone();
+        long.frames = MAX_FRAMES + 1;
+        assert_eq!(long.simulate(), Err(ReplayError::TooLong(MAX_FRAMES + 1)));
+        let mut huge = replay.clone();
+        huge.width = 10_000;
+        assert!(matches!(huge.simulate(), Err(ReplayError::Settings(_))));
+        for gravity in [-1.0, f64::NAN, f64::INFINITY] {
+            let mut broken = replay.clone();
+            broken.settings.timing.gravity = Gravity::Fixed(gravity);
+            assert!(matches!(broken.simulate(), Err(ReplayError::Settings(_))));
+        }
+        let mut broken = replay.clone();
+        broken.settings.timing.soft_drop_factor = -2.0;
+        assert!(matches!(broken.simulate(), Err(ReplayError::Settings(_))));
+        let mut future = replay;
+        future.version = REPLAY_VERSION + 1;
+        assert!(matches!(future.simulate(), Err(ReplayError::Version(_))));
+        assert!(matches!(
+            Replay::from_json(r#"{"seed":1}"#),
+            Err(ReplayError::Parse(_))
+        ));
+    }
+}
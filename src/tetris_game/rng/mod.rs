@@ -0,0 +1,177 @@
+/*
+    Where the randomness for the piece queue comes from.
+        - Rng is all the game needs, so embedding the engine does not require the rand crate
+        - SeededRng is a small built in PRNG: the same seed always deals the same pieces
+        - with the "rand" feature ThreadRng uses rand::thread_rng() instead
+        - ScriptedRng is not random at all, it deals a fixed sequence (over and over, or just once for puzzles)
+        - QueueRng deals a fixed sequence first and then goes on with another Rng (custom positions)
+*/
+
+/// Source of randomness for picking the next piece
+pub trait Rng: std::fmt::Debug {
+    /// a random index in 0..len (len is never 0)
+    fn next_index(&mut self, len: usize) -> usize;
+
+    /// false once there is nothing left to deal, the game stops asking then (only a script runs out)
+    fn has_next(&self) -> bool {
+        true
+    }
+}
+
+/// ### SeededRng
+/// xorshift64* - tiny, fast and good enough to shuffle some tetrominos.
+/// Not meant for anything that has to be unpredictable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 of the seed, so small or zero seeds still give a good (and never 0) state:
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        Self { state: z.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_index(&mut self, len: usize) -> usize {
+        // the upper bits are the better ones, the modulo bias does not matter for a handful of pieces
+        ((self.next_u64() >> 32) % len as u64) as usize
+    }
+}
+
+/// ### ScriptedRng
+/// Deals the given piece indices in order, starts over after the last one (or runs out, see once()).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptedRng {
+    indices: Vec<usize>,
+    next: usize,
+    repeat: bool,
+}
+
+impl ScriptedRng {
+    /// indices into the piece set of the game (needs at least one)
+    pub fn new(indices: Vec<usize>) -> Self {
+        assert!(!indices.is_empty(), "a script needs at least one piece");
+        Self {
+            indices,
+            next: 0,
+            repeat: true,
+        }
+    }
+
+    /// like new(), but deals every index just once: the game gets no more pieces after the last one
+    pub fn once(indices: Vec<usize>) -> Self {
+        Self {
+            repeat: false,
+            ..Self::new(indices)
+        }
+    }
+}
+
+impl Rng for ScriptedRng {
+    fn next_index(&mut self, len: usize) -> usize {
+        let idx = self.indices[self.next % self.indices.len()];
+        self.next += 1;
+        idx % len
+    }
+
+    fn has_next(&self) -> bool {
+        self.repeat || self.next < self.indices.len()
+    }
+}
+
+/// ### QueueRng
+/// Deals the given piece indices first, then whatever the other Rng picks.
+#[derive(Debug)]
+pub struct QueueRng {
+    queue: std::collections::VecDeque<usize>,
+    then: Box<dyn Rng>,
+}
+
+impl QueueRng {
+    pub fn new(queue: Vec<usize>, then: Box<dyn Rng>) -> Self {
+        Self {
+            queue: queue.into(),
+            then,
+        }
+    }
+}
+
+impl Rng for QueueRng {
+    fn next_index(&mut self, len: usize) -> usize {
+        match self.queue.pop_front() {
+            Some(idx) => idx % len,
+            None => self.then.next_index(len),
+        }
+    }
+}
+
+/// rand::thread_rng() as Rng
+#[cfg(feature = "rand")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadRng;
+
+#[cfg(feature = "rand")]
+impl Rng for ThreadRng {
+    fn next_index(&mut self, len: usize) -> usize {
+        use rand::Rng as _;
+        rand::thread_rng().gen_range(0..len)
+    }
+}
+
+/// The Rng a game uses if it does not get one: ThreadRng with the "rand" feature,
+/// otherwise a SeededRng seeded from the system clock.
+pub fn default_rng() -> Box<dyn Rng> {
+    #[cfg(feature = "rand")]
+    {
+        Box::new(ThreadRng)
+    }
+    #[cfg(not(feature = "rand"))]
+    {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        Box::new(SeededRng::new(seed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = SeededRng::new(42);
+        let mut b = SeededRng::new(42);
+        let mut c = SeededRng::new(43);
+        let a: Vec<usize> = (0..32).map(|_| a.next_index(7)).collect();
+        let b: Vec<usize> = (0..32).map(|_| b.next_index(7)).collect();
+        let c: Vec<usize> = (0..32).map(|_| c.next_index(7)).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn indices_stay_in_range_and_cover_it() {
+        let mut rng = SeededRng::new(0);
+        let mut seen = [0; 7];
+        for _ in 0..7000 {
+            seen[rng.next_index(7)] += 1;
+        }
+        // every piece shows up, roughly equally often:
+        assert!(seen.iter().all(|n| (800..1200).contains(n)), "{:?}", seen);
+    }
+}
@@ -0,0 +1,562 @@
+use super::bot::Bot;
+use super::event::Event;
+use super::garbage::{AttackTable, Attacker, Board, HolePlacement, Incoming};
+use super::rng::{Rng, SeededRng};
+use super::shapes::XY;
+use super::{Settings, Tetris};
+
+/*
+    Royale: a match of N boards, the last one standing wins.
+        - garbage works like in versus (see garbage): clears attack, attacks cancel the own incoming garbage first,
+          a lock without lines lets the incoming garbage rise
+        - what is left of an attack goes to the targets the attacker picked with its Targeting
+          (split evenly when there is more than one target)
+        - a board that tops out is eliminated, the KO goes to the last player that sent garbage to it.
+          Every KO earns a badge, plus the badges of the eliminated player.
+          Badges multiply the attack: every level of badge_levels reached adds badge_bonus percent
+        - eliminated() is the order players dropped out, standings() the final ranking
+    Nothing in here needs a browser or a clock: with_seed() plays the same match every time,
+    play_round() lets bots play it out (a bot tournament is a loop over matches).
+*/
+
+/// The rules of a match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoyaleSettings {
+    pub attack: AttackTable,
+    pub holes: HolePlacement,
+    /// badges needed for each bonus level, ascending
+    pub badge_levels: Vec<u32>,
+    /// percent of extra attack per bonus level reached
+    pub badge_bonus: u32,
+}
+
+impl Default for RoyaleSettings {
+    // badge levels like Tetris 99: +25% at 2, 6, 14 and 30 badges
+    fn default() -> Self {
+        Self {
+            attack: AttackTable::default(),
+            holes: HolePlacement::default(),
+            badge_levels: vec![2, 6, 14, 30],
+            badge_bonus: 25,
+        }
+    }
+}
+
+impl RoyaleSettings {
+    /// the attack after the badge bonus (rounded down)
+    pub fn boosted(&self, attack: u32, badges: u32) -> u32 {
+        let levels = self
+            .badge_levels
+            .iter()
+            .filter(|level| badges >= **level)
+            .count() as u32;
+        attack * (100 + levels * self.badge_bonus) / 100
+    }
+}
+
+/// Who a player sends its garbage to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Targeting {
+    /// a random opponent, picked again for every attack
+    #[default]
+    Random,
+    /// everyone targeting this player right now (a random opponent if nobody does)
+    Attackers,
+    /// the opponent closest to topping out (highest stack plus incoming garbage)
+    KoBait,
+    /// all opponents, the attack split evenly
+    EvenSplit,
+}
+
+impl Targeting {
+    pub const ALL: [Targeting; 4] = [
+        Targeting::Random,
+        Targeting::Attackers,
+        Targeting::KoBait,
+        Targeting::EvenSplit,
+    ];
+}
+
+#[derive(Debug)]
+struct Player {
+    /// the game with its garbage, like in versus
+    board: Board,
+    targeting: Targeting,
+    /// who the last attack went to
+    targets: Vec<usize>,
+    /// who sent the last garbage to this player (gets the KO)
+    last_attacker: Option<usize>,
+    kos: u32,
+    badges: u32,
+    alive: bool,
+}
+
+impl Player {
+    fn new(tetris: Tetris) -> Self {
+        Self {
+            board: Board::new(tetris),
+            targeting: Targeting::default(),
+            targets: vec![],
+            last_attacker: None,
+            kos: 0,
+            badges: 0,
+            alive: true,
+        }
+    }
+
+    // rows the stack reaches up, plus the garbage waiting to rise
+    fn danger(&self) -> i32 {
+        let (_, height) = self.board.tetris.get_size();
+        let top = self
+            .board
+            .tetris
+            .sticky_bottom_shapes
+            .iter()
+            .flat_map(|shape| shape.get_pixels())
+            .map(|XY(_, y)| y)
+            .min()
+            .unwrap_or(height);
+        height - top + self.board.incoming.total() as i32
+    }
+}
+
+#[derive(Debug)]
+pub struct Royale {
+    players: Vec<Player>,
+    settings: RoyaleSettings,
+    /// picks targets and holes
+    rng: Box<dyn Rng>,
+    /// players in the order they got eliminated
+    eliminated: Vec<usize>,
+}
+
+impl Royale {
+    /// a match between players boards of the same size and settings, the same seed plays out the same every time
+    /// (every board gets the same pieces)
+    pub fn with_seed(
+        players: usize,
+        width: u32,
+        height: u32,
+        settings: Settings,
+        royale: RoyaleSettings,
+        seed: u64,
+    ) -> Self {
+        let boards = (0..players)
+            .map(|_| {
+                Tetris::with_rng(
+                    width,
+                    height,
+                    settings.clone(),
+                    Box::new(SeededRng::new(seed)),
+                )
+            })
+            .collect();
+        Self::with_boards(
+            boards,
+            royale,
+            Box::new(SeededRng::new(seed.wrapping_add(1))),
+        )
+    }
+
+    /// a match between games that are already set up, targets and holes come from the rng
+    pub fn with_boards(boards: Vec<Tetris>, royale: RoyaleSettings, rng: Box<dyn Rng>) -> Self {
+        Self {
+            players: boards.into_iter().map(Player::new).collect(),
+            settings: royale,
+            rng,
+            eliminated: vec![],
+        }
+    }
+
+    pub fn settings(&self) -> &RoyaleSettings {
+        &self.settings
+    }
+
+    /// how many players the match started with
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+
+    pub fn board(&self, player: usize) -> &Tetris {
+        &self.players[player].board.tetris
+    }
+
+    /// for the inputs of the player
+    pub fn board_mut(&mut self, player: usize) -> &mut Tetris {
+        &mut self.players[player].board.tetris
+    }
+
+    /// the garbage waiting to rise on the board of the player
+    pub fn incoming(&self, player: usize) -> &Incoming {
+        &self.players[player].board.incoming
+    }
+
+    /// the combo and back to back of the player
+    pub fn attacker(&self, player: usize) -> &Attacker {
+        &self.players[player].board.attacker
+    }
+
+    pub fn targeting(&self, player: usize) -> Targeting {
+        self.players[player].targeting
+    }
+
+    pub fn set_targeting(&mut self, player: usize, targeting: Targeting) {
+        self.players[player].targeting = targeting;
+    }
+
+    /// who the last attack of the player went to
+    pub fn targets(&self, player: usize) -> &[usize] {
+        &self.players[player].targets
+    }
+
+    /// the players still in the match whose last attack went to the player
+    pub fn attackers_of(&self, player: usize) -> Vec<usize> {
+        (0..self.players.len())
+            .filter(|idx| *idx != player && self.players[*idx].alive)
+            .filter(|idx| self.players[*idx].targets.contains(&player))
+            .collect()
+    }
+
+    /// lines the player sent so far (with the badge bonus, what got cancelled does not count)
+    pub fn sent(&self, player: usize) -> u32 {
+        self.players[player].board.sent
+    }
+
+    pub fn kos(&self, player: usize) -> u32 {
+        self.players[player].kos
+    }
+
+    pub fn badges(&self, player: usize) -> u32 {
+        self.players[player].badges
+    }
+
+    pub fn is_alive(&self, player: usize) -> bool {
+        self.players[player].alive
+    }
+
+    /// the players still in the match
+    pub fn alive(&self) -> Vec<usize> {
+        (0..self.players.len())
+            .filter(|idx| self.players[*idx].alive)
+            .collect()
+    }
+
+    /// players in the order they got eliminated (players topping out together in seat order)
+    pub fn eliminated(&self) -> &[usize] {
+        &self.eliminated
+    }
+
+    /// at most one player left
+    pub fn is_over(&self) -> bool {
+        self.alive().len() <= 1
+    }
+
+    /// the last one standing, None while the match goes on (or if the last ones topped out together)
+    pub fn winner(&self) -> Option<usize> {
+        match self.alive()[..] {
+            [winner] => Some(winner),
+            _ => None,
+        }
+    }
+
+    /// the ranking: players still in first (in seat order), then the eliminated ones, last eliminated first
+    pub fn standings(&self) -> Vec<usize> {
+        let mut standings = self.alive();
+        standings.extend(self.eliminated.iter().rev());
+        standings
+    }
+
+    /// Advances all games still in by the real time that passed, then exchanges the garbage
+    pub fn update(&mut self, elapsed_ms: f64) {
+        if !self.is_over() {
+            for player in self.players.iter_mut().filter(|player| player.alive) {
+                player.board.tetris.update(elapsed_ms);
+            }
+        }
+        self.exchange();
+    }
+
+    /// Every player still in places its current shape where its bot says, garbage gets exchanged after each one
+    /// (bots[player % bots.len()] plays for the player, without bots nothing happens)
+    pub fn play_round(&mut self, bots: &[Bot]) {
+        if bots.is_empty() {
+            return;
+        }
+        for idx in 0..self.players.len() {
+            if self.is_over() {
+                return;
+            }
+            if self.players[idx].alive {
+                bots[idx % bots.len()].play(&mut self.players[idx].board.tetris);
+                self.exchange();
+            }
+        }
+    }
+
+    /// Sends the attacks of everything that locked since the last call, lets garbage rise and eliminates
+    /// who topped out. update() does this on its own, call it directly when driving the boards without time.
+    pub fn exchange(&mut self) {
+        if self.is_over() {
+            return;
+        }
+        for idx in 0..self.players.len() {
+            if !self.players[idx].alive {
+                continue;
+            }
+            let (settings, player) = (&self.settings, &mut self.players[idx]);
+            let badges = player.badges;
+            let attacks = player.board.exchange(
+                &settings.attack,
+                settings.holes,
+                self.rng.as_mut(),
+                |attack| settings.boosted(attack, badges),
+            );
+            for attack in attacks {
+                self.send(idx, attack);
+            }
+        }
+        self.eliminate();
+    }
+
+    // sends what is left of an attack to the targets of the attacker
+    fn send(&mut self, from: usize, attack: u32) {
+        let targets = self.pick_targets(from);
+        if targets.is_empty() {
+            return;
+        }
+        // evenly, the first targets get the rest:
+        let (share, rest) = (attack / targets.len() as u32, attack % targets.len() as u32);
+        for (n, target) in targets.iter().enumerate() {
+            let lines = share + u32::from((n as u32) < rest);
+            if lines > 0 {
+                self.players[*target].board.incoming.push(lines);
+                self.players[*target].last_attacker = Some(from);
+            }
+        }
+        self.players[from].targets = targets;
+    }
+
+    // the targets of the next attack of the player
+    fn pick_targets(&mut self, from: usize) -> Vec<usize> {
+        // opponents in seat order, starting after the player (so splits do not always favor the same seats):
+        let count = self.players.len();
+        let opponents: Vec<usize> = (1..count)
+            .map(|offset| (from + offset) % count)
+            .filter(|idx| self.players[*idx].alive)
+            .collect();
+        if opponents.is_empty() {
+            return vec![];
+        }
+        let targeting = self.players[from].targeting;
+        match targeting {
+            Targeting::Attackers => {
+                let attackers: Vec<usize> = opponents
+                    .iter()
+                    .copied()
+                    .filter(|idx| self.players[*idx].targets.contains(&from))
+                    .collect();
+                if !attackers.is_empty() {
+                    return attackers;
+                }
+            }
+            Targeting::KoBait => {
+                // the first of the most endangered (max_by_key would take the last):
+                let danger = |idx: &usize| self.players[*idx].danger();
+                let most = opponents.iter().map(danger).max().unwrap_or_default();
+                return opponents
+                    .iter()
+                    .copied()
+                    .filter(|idx| danger(idx) == most)
+                    .take(1)
+                    .collect();
+            }
+            Targeting::EvenSplit => return opponents,
+            Targeting::Random => {}
+        }
+        vec![opponents[self.rng.next_index(opponents.len())]]
+    }
+
+    // takes out everyone that topped out, the KOs go to who sent them the last garbage
+    fn eliminate(&mut self) {
+        let topped_out: Vec<usize> = (0..self.players.len())
+            .filter(|idx| {
+                self.players[*idx].alive && self.players[*idx].board.tetris.is_game_over()
+            })
+            .collect();
+        for idx in &topped_out {
+            self.players[*idx].alive = false;
+            self.players[*idx].board.incoming.take();
+            self.eliminated.push(*idx);
+        }
+        for idx in topped_out {
+            if let Some(ko) = self.players[idx].last_attacker.filter(|ko| *ko != idx) {
+                let badges = self.players[idx].badges;
+                let player = &mut self.players[ko];
+                player.kos += 1;
+                player.badges += 1 + badges;
+            }
+        }
+    }
+
+    /// everything that happened on the board of the player since the last call, oldest first
+    pub fn take_events(&mut self, player: usize) -> Vec<Event> {
+        self.players[player].board.take_events()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::position::Position;
+    use crate::tetris_game::shapes::PieceKind;
+    use crate::tetris_game::Direction;
+
+    // a board with the bottom rows full except for column 0, an I ready to clear them
+    fn ready(rows: i32) -> Tetris {
+        let mut position = Position::empty(10, 20);
+        for y in 20 - rows..20 {
+            for x in 1..10 {
+                position.set(XY(x, y), Some(PieceKind::Garbage));
+            }
+        }
+        position.current = Some(PieceKind::I);
+        position.queue = vec![PieceKind::O; 3];
+        position.start_with_rng(Settings::default(), Box::new(SeededRng::new(1)))
+    }
+
+    fn tetris_on(royale: &mut Royale, player: usize) {
+        let tetris = royale.board_mut(player);
+        tetris.move_down();
+        tetris.move_rotate();
+        while tetris.move_current_shape(Direction::Left) {}
+        tetris.hard_drop();
+        royale.exchange();
+    }
+
+    fn settings() -> RoyaleSettings {
+        RoyaleSettings {
+            attack: AttackTable {
+                lines: [1, 2, 3, 4],
+                combo: vec![0],
+                back_to_back: 0,
+                perfect_clear: 0,
+                ..Default::default()
+            },
+            holes: HolePlacement::Column(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn targeting_strategies() {
+        let boards = (0..4).map(|_| ready(4)).collect();
+        let mut royale = Royale::with_boards(boards, settings(), Box::new(SeededRng::new(1)));
+
+        // even split: 4 lines over 3 opponents, the one after the attacker gets the rest
+        royale.set_targeting(0, Targeting::EvenSplit);
+        tetris_on(&mut royale, 0);
+        assert_eq!(royale.targets(0), &[1, 2, 3]);
+        let incoming: Vec<u32> = (1..4).map(|idx| royale.incoming(idx).total()).collect();
+        assert_eq!(incoming, vec![2, 1, 1]);
+        assert_eq!(royale.attackers_of(2), vec![0]);
+
+        // attackers: back at 0
+        royale.set_targeting(2, Targeting::Attackers);
+        tetris_on(&mut royale, 2);
+        assert_eq!(royale.targets(2), &[0]);
+        // (1 cancelled, 3 sent)
+        assert_eq!(royale.incoming(0).total(), 3);
+        assert_eq!(royale.sent(2), 3);
+
+        // ko bait: 1 still has its stack and the most garbage coming
+        royale.set_targeting(3, Targeting::KoBait);
+        tetris_on(&mut royale, 3);
+        assert_eq!(royale.targets(3), &[1]);
+        assert_eq!(royale.incoming(1).total(), 5);
+
+        // nothing left to send after cancelling:
+        tetris_on(&mut royale, 1);
+        assert_eq!(royale.incoming(1).total(), 1);
+        assert_eq!(royale.sent(1), 0);
+        assert!(royale.targets(1).is_empty());
+    }
+
+    #[test]
+    fn kos_badges_and_elimination_order() {
+        let mut settings = settings();
+        settings.badge_levels = vec![1];
+        settings.badge_bonus = 50;
+        assert_eq!(settings.boosted(4, 0), 4);
+        assert_eq!(settings.boosted(4, 1), 6);
+        assert_eq!(settings.boosted(3, 1), 4);
+
+        let boards = (0..3).map(|_| ready(4)).collect();
+        let mut royale = Royale::with_boards(boards, settings, Box::new(SeededRng::new(1)));
+        royale.set_targeting(0, Targeting::KoBait);
+        royale.players[2].badges = 2;
+        // 2 has a nearly full board, 0 sends it over the top:
+        royale.board_mut(2).add_garbage(&[0; 12]);
+        tetris_on(&mut royale, 0);
+        assert_eq!(royale.targets(0), &[2]);
+        royale.board_mut(2).hard_drop();
+        royale.exchange();
+        assert!(!royale.is_alive(2));
+        assert_eq!(royale.eliminated(), &[2]);
+        assert_eq!((royale.kos(0), royale.badges(0)), (1, 3));
+        assert_eq!(royale.winner(), None);
+
+        // with the badges the next tetris sends 6:
+        royale.board_mut(1).add_garbage(&[0; 10]);
+        let sent = royale.sent(0);
+        royale.players[0].board.tetris = ready(4);
+        tetris_on(&mut royale, 0);
+        assert_eq!(royale.sent(0) - sent, 6);
+        royale.board_mut(1).hard_drop();
+        royale.exchange();
+        assert_eq!(royale.winner(), Some(0));
+        assert_eq!(royale.standings(), vec![0, 1, 2]);
+        // nothing changes after the end:
+        royale.update(1000.0);
+        assert_eq!(royale.eliminated(), &[2, 1]);
+    }
+
+    // a small bot tournament, twice
+    fn tournament(seed: u64) -> (Vec<usize>, Vec<u32>) {
+        let mut royale = Royale::with_seed(3, 10, 20, Settings::default(), settings(), seed);
+        for (idx, targeting) in [
+            Targeting::EvenSplit,
+            Targeting::KoBait,
+            Targeting::Attackers,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            royale.set_targeting(idx, targeting);
+        }
+        // garbage from the start, so something happens in a few rounds:
+        for idx in 0..3 {
+            royale.board_mut(idx).add_garbage(&[idx as i32; 12]);
+        }
+        // without bots nobody moves:
+        let before = Position::of(royale.board(0));
+        royale.play_round(&[]);
+        assert_eq!(Position::of(royale.board(0)), before);
+        let bots = [Bot::default()];
+        for _ in 0..5 {
+            royale.play_round(&bots);
+        }
+        let sent = (0..3).map(|idx| royale.sent(idx)).collect();
+        (royale.standings(), sent)
+    }
+
+    #[test]
+    fn matches_are_deterministic() {
+        let (standings, sent) = tournament(4);
+        assert!(sent.iter().any(|sent| *sent > 0));
+        assert_eq!((standings, sent), tournament(4));
+    }
+}
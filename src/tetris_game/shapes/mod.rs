@@ -1,15 +1,65 @@
 use std::{collections::HashSet, ops::Add};
 
-use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 /*
 Viable Tetris shapes include Tetrominos like
     I, J, L, O, S, T, Z
 from https://de.wikipedia.org/wiki/Tetris
+
+The actual pixels of every shape are defined in a PieceSet (see tetris_game::piece_set)
 */
 
+/// The kind of a shape (and of every pixel it leaves on the board).
+/// How a kind gets drawn (emoji, color...) is up to the frontend.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum PieceKind {
+    I,
+    J,
+    L,
+    O,
+    S,
+    T,
+    Z,
+    /// leftover pixels that did not come from a player controlled shape
+    Garbage,
+}
+
+impl PieceKind {
+    /// all kinds, in the order of their discriminant (usable as index with `kind as usize`)
+    pub const ALL: [PieceKind; 8] = [
+        PieceKind::I,
+        PieceKind::J,
+        PieceKind::L,
+        PieceKind::O,
+        PieceKind::S,
+        PieceKind::T,
+        PieceKind::Z,
+        PieceKind::Garbage,
+    ];
+
+    /// the letter of the kind, G for garbage
+    pub fn letter(self) -> char {
+        match self {
+            PieceKind::I => 'I',
+            PieceKind::J => 'J',
+            PieceKind::L => 'L',
+            PieceKind::O => 'O',
+            PieceKind::S => 'S',
+            PieceKind::T => 'T',
+            PieceKind::Z => 'Z',
+            PieceKind::Garbage => 'G',
+        }
+    }
+
+    /// the kind with that letter (see letter())
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.letter() == letter)
+    }
+}
+
 /// Represents the X and Y choords of one "gamePixel" (4 of with make one Tetris shape usually)
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct XY(pub i32, pub i32);
 
 /// A Shape is just a container for The XY Pixels mostly.
@@ -21,8 +71,10 @@ pub struct Shape {
     pixels: HashSet<XY>,
     /// Anchor we rotate on  
     anchor: XY,
-    /// info about color etc:
-    typ: &'static str,
+    /// what kind of piece this is (the frontend picks color etc. from that)
+    kind: PieceKind,
+    /// index of the piece definition (in the games PieceSet) this shape was made from
+    piece: usize,
 }
 
 impl Shape {
@@ -32,8 +84,13 @@ impl Shape {
     }
 
     // getter
-    pub fn get_typ(&self) -> &'static str {
-        self.typ
+    pub fn get_kind(&self) -> PieceKind {
+        self.kind
+    }
+
+    // getter
+    pub fn get_piece(&self) -> usize {
+        self.piece
     }
 
     pub fn has_xy(&self, xy: XY) -> bool {
@@ -61,7 +118,23 @@ impl Shape {
         Self {
             pixels: new_pixels,
             anchor: self.anchor,
-            typ: self.typ,
+            kind: self.kind,
+            piece: self.piece,
+        }
+    }
+
+    pub fn rotated_shape_ccw(&self) -> Self {
+        let XY(x_off, y_off) = self.anchor;
+        let new_pixels = self
+            .get_pixels()
+            // same as clockwise, but new_x = old_y and new_y = -old_x
+            .map(|XY(x, y)| XY(y - y_off + x_off, -x + x_off + y_off))
+            .collect();
+        Self {
+            pixels: new_pixels,
+            anchor: self.anchor,
+            kind: self.kind,
+            piece: self.piece,
         }
     }
 
@@ -75,86 +148,18 @@ impl Shape {
             .collect();
     }
 
-    // constructors:
-    pub fn new_i() -> Self {
-        Self {
-            pixels: [XY(-1, 0), XY(0, 0), XY(1, 0), XY(2, 0)]
-                .into_iter()
-                .collect(),
-            anchor: XY(0, 0),
-            typ: "🟦",
-        }
-    }
-    pub fn new_j() -> Self {
-        Self {
-            pixels: [XY(-1, 0), XY(-1, 1), XY(0, 1), XY(1, 1)]
-                .into_iter()
-                .collect(),
-            anchor: XY(0, 1),
-            typ: "🟫",
-        }
-    }
-    pub fn new_l() -> Self {
-        Self {
-            pixels: [XY(-1, 1), XY(0, 1), XY(1, 1), XY(1, 0)]
-                .into_iter()
-                .collect(),
-            anchor: XY(0, 1),
-            typ: "🟧",
-        }
-    }
-    pub fn new_o() -> Self {
-        Self {
-            pixels: [XY(0, 0), XY(1, 0), XY(0, 1), XY(1, 1)]
-                .into_iter()
-                .collect(),
-            anchor: XY(0, 1),
-
-            typ: "🟨",
-        }
-    }
-    pub fn new_s() -> Self {
+    // constructor, pixels and anchor are relative to the spawn position
+    pub fn new(
+        piece: usize,
+        kind: PieceKind,
+        pixels: impl IntoIterator<Item = XY>,
+        anchor: XY,
+    ) -> Self {
         Self {
-            pixels: [XY(-1, 1), XY(0, 0), XY(0, 1), XY(1, 0)]
-                .into_iter()
-                .collect(),
-            anchor: XY(0, 1),
-            typ: "🟩",
-        }
-    }
-    pub fn new_t() -> Self {
-        Self {
-            pixels: [XY(0, 0), XY(0, 1), XY(-1, 1), XY(1, 1)]
-                .into_iter()
-                .collect(),
-            anchor: XY(0, 1),
-
-            typ: "🟪",
-        }
-    }
-    pub fn new_z() -> Self {
-        Self {
-            pixels: [XY(-1, 0), XY(0, 0), XY(0, 1), XY(1, 1)]
-                .into_iter()
-                .collect(),
-            anchor: XY(0, 1),
-
-            typ: "🟥",
-        }
-    }
-
-    /// generates a random new Shape
-    pub fn new() -> Self {
-        let rng = rand::thread_rng().gen_range(0..7); //TODO: when all figures implemented check if all get drawn
-        match rng {
-            0 => Self::new_i(),
-            1 => Self::new_j(),
-            2 => Self::new_l(),
-            3 => Self::new_o(),
-            4 => Self::new_s(),
-            5 => Self::new_t(),
-            6 => Self::new_z(),
-            _ => unreachable!(), // as long as we only use default shapes this should never trigger
+            pixels: pixels.into_iter().collect(),
+            anchor,
+            kind,
+            piece,
         }
     }
 }
@@ -167,7 +172,8 @@ impl Add<XY> for &Shape {
         Shape {
             pixels: self.pixels.iter().map(|xy| xy + rhs).collect(),
             anchor: &self.anchor + rhs,
-            typ: &self.typ,
+            kind: self.kind,
+            piece: self.piece,
         }
     }
 }
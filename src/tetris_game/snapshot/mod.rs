@@ -0,0 +1,229 @@
+use super::shapes::{PieceKind, Shape, XY};
+use super::timing::Phase;
+use super::Tetris;
+
+/*
+    Everything a frontend draws, taken from the game in one go.
+        - Tetris::snapshot() walks the shapes once, the frontend then only reads plain vectors
+        - immutable and comparable: renderers keep the last one and skip (or limit) redrawing
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/// An upcoming (or held) shape, placed inside a preview box
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preview {
+    pub kind: PieceKind,
+    /// the filled cells, relative to the top left of the preview box
+    pub cells: Vec<XY>,
+}
+
+impl Preview {
+    /// the preview box as cells, row by row
+    pub fn grid(&self, (width, height): (i32, i32)) -> Vec<Option<PieceKind>> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| XY(x, y)))
+            .map(|xy| self.cells.contains(&xy).then_some(self.kind))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub width: i32,
+    pub height: i32,
+    /// the locked cells (without the current shape), row by row
+    pub board: Vec<Option<PieceKind>>,
+    /// cells of the current shape (empty while the game waits in a delay)
+    pub active: Vec<XY>,
+    pub active_kind: PieceKind,
+    /// cells of the ghost that are not covered by the current shape
+    pub ghost: Vec<XY>,
+    /// width and height of one preview box
+    pub preview_size: (i32, i32),
+    /// the upcoming shapes, next one first
+    pub queue: Vec<Preview>,
+    pub hold: Option<Preview>,
+    pub score: i32,
+    pub level: u32,
+    pub lines: u32,
+    pub status: Status,
+    /// falling or one of the delays, Phase::LineClear has the rows that are about to collapse
+    pub phase: Phase,
+}
+
+impl Snapshot {
+    /// index of xy in the row by row cells, None outside the board
+    pub fn index(&self, xy: XY) -> Option<usize> {
+        let inside = xy.0 >= 0 && xy.0 < self.width && xy.1 >= 0 && xy.1 < self.height;
+        inside.then(|| (xy.1 * self.width + xy.0) as usize)
+    }
+
+    /// kind of the cell at xy, including the current shape (None if empty or outside)
+    pub fn cell(&self, xy: XY) -> Option<PieceKind> {
+        if self.active.contains(&xy) {
+            return Some(self.active_kind);
+        }
+        self.index(xy).and_then(|idx| self.board[idx])
+    }
+
+    /// every cell of the board (with the current shape), row by row
+    pub fn cells(&self) -> Vec<Option<PieceKind>> {
+        let mut cells = self.board.clone();
+        for xy in &self.active {
+            if let Some(idx) = self.index(*xy) {
+                cells[idx] = Some(self.active_kind);
+            }
+        }
+        cells
+    }
+
+    pub fn is_ghost(&self, xy: XY) -> bool {
+        self.ghost.contains(&xy)
+    }
+
+    /// the full rows that are about to collapse (during the line clear delay)
+    pub fn clearing_rows(&self) -> &[i32] {
+        match &self.phase {
+            Phase::LineClear { rows, .. } => rows,
+            _ => &[],
+        }
+    }
+
+    /// cells that look different than in the other snapshot (of a board with the same size)
+    pub fn changed_cells(&self, other: &Snapshot) -> Vec<XY> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| XY(x, y)))
+            .filter(|xy| {
+                self.cell(*xy) != other.cell(*xy) || self.is_ghost(*xy) != other.is_ghost(*xy)
+            })
+            .collect()
+    }
+}
+
+// shapes keep their pixels in a set, sorted (row by row) snapshots of the same board are equal
+fn sorted(cells: impl Iterator<Item = XY>) -> Vec<XY> {
+    let mut cells: Vec<XY> = cells.collect();
+    cells.sort_by_key(|xy| (xy.1, xy.0));
+    cells
+}
+
+impl Tetris {
+    /// Everything there is to draw, in one call
+    pub fn snapshot(&self) -> Snapshot {
+        let mut board = vec![None; (self.width * self.height) as usize];
+        for shape in &self.sticky_bottom_shapes {
+            for XY(x, y) in shape.get_pixels() {
+                if x >= 0 && x < self.width && y >= 0 && y < self.height {
+                    board[(y * self.width + x) as usize] = Some(shape.get_kind());
+                }
+            }
+        }
+        let current = &self.current_shape;
+        let falling = self.is_falling();
+
+        // previews are placed like the shapes spawn, relative to the corner of the piece set bounds:
+        let (min, _) = self.settings.piece_set.bounds();
+        let origin = &min + XY(self.width / 2, 0);
+        let preview = |shape: &Shape| Preview {
+            kind: shape.get_kind(),
+            cells: sorted(
+                shape
+                    .get_pixels()
+                    .map(|XY(x, y)| XY(x - origin.0, y - origin.1)),
+            ),
+        };
+
+        Snapshot {
+            width: self.width,
+            height: self.height,
+            board,
+            active: sorted(current.get_pixels().filter(|_| falling)),
+            active_kind: current.get_kind(),
+            ghost: sorted(
+                self.get_ghost()
+                    .get_pixels()
+                    .filter(|xy| falling && !current.has_xy(*xy)),
+            ),
+            preview_size: self.get_preview_size(),
+            queue: self.next_shapes.iter().map(preview).collect(),
+            hold: self.hold.map(|idx| {
+                preview(&Self::spawn_shape(
+                    &self.settings.piece_set,
+                    idx,
+                    self.width,
+                ))
+            }),
+            score: self.score,
+            level: self.get_level(),
+            lines: self.lines,
+            status: if self.game_over {
+                Status::GameOver
+            } else if self.paused {
+                Status::Paused
+            } else {
+                Status::Playing
+            },
+            phase: self.phase.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_matches_the_getters() {
+        let mut tetris = Tetris::new(10, 20);
+        tetris.hard_drop();
+        tetris.hold();
+        tetris.move_rotate();
+        let snapshot = tetris.snapshot();
+
+        let cells: Vec<_> = tetris.get_pixels().map(|xy| tetris.get_kind(xy)).collect();
+        assert_eq!(snapshot.cells(), cells);
+        for xy in tetris.get_pixels() {
+            assert_eq!(snapshot.cell(xy), tetris.get_kind(xy));
+        }
+        assert_eq!(snapshot.status, Status::Playing);
+        assert_eq!(
+            snapshot.hold.as_ref().map(|hold| hold.kind),
+            tetris.get_hold()
+        );
+
+        // the preview boxes line up with the old per cell getters:
+        let size = snapshot.preview_size;
+        let grid: Vec<_> = tetris
+            .get_preview_pixels()
+            .map(|xy| tetris.get_preview_kind(0, xy))
+            .collect();
+        assert_eq!(snapshot.queue[0].grid(size), grid);
+        let hold: Vec<_> = tetris
+            .get_preview_pixels()
+            .map(|xy| tetris.get_hold_preview_kind(xy))
+            .collect();
+        assert_eq!(snapshot.hold.unwrap().grid(size), hold);
+    }
+
+    #[test]
+    fn diffs_between_snapshots() {
+        let mut tetris = Tetris::new(10, 20);
+        let before = tetris.snapshot();
+        assert_eq!(before, tetris.snapshot());
+        assert!(before.changed_cells(&tetris.snapshot()).is_empty());
+
+        tetris.move_down();
+        let after = tetris.snapshot();
+        assert_ne!(before, after);
+        // the shape moved, the ghost stayed where it is:
+        let changed = before.changed_cells(&after);
+        assert!(!changed.is_empty());
+        assert!(changed.iter().all(|xy| xy.1 < 3));
+    }
+}
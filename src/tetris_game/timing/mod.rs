@@ -0,0 +1,507 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Direction, Tetris};
+
+/*
+    Fixed timestep simulation.
+        - the frontend feeds in the real time that passed (update(elapsed_ms))
+        - the game then runs as many fixed frames (60 per second) as fit into that time
+        - every frame: auto shift (DAS), gravity and the lock delay advance by one
+    So the game plays the same no matter how often (or how regularly) the frontend calls in.
+
+    Gravity is measured in G = cells per frame (like the Guideline and TGM do):
+        - 1/60 G falls one row per second
+        - 1 G falls one row every frame
+        - 20 G is 'instant', the shape shows up directly on the stack
+    Internally the fraction gets accumulated in SUBCELLS per cell, so it stays exact (and deterministic).
+
+    Between two shapes the game can wait (see Phase):
+        - line clear delay: the full rows stay on the board a few frames (so the frontend can animate them)
+        - entry delay (ARE): the board settled, the next shape shows up once it ran out
+*/
+
+/// milliseconds one simulated frame takes (the simulation runs at a fixed 60 frames per second)
+pub const FRAME_MS: f64 = 1000.0 / 60.0;
+/// most time one update() catches up on (after the browser tab was in the background etc.)
+const MAX_CATCH_UP_MS: f64 = 250.0;
+/// how often moving/rotating a grounded shape can restart the lock delay
+pub const MAX_LOCK_RESETS: u32 = 15;
+/// gravity (in G) from where on shapes fall to the stack instantly
+pub const INSTANT_GRAVITY: f64 = 20.0;
+/// fixed point resolution of the gravity accumulator
+const SUBCELLS: u32 = 65536;
+
+/// How fast shapes fall
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Gravity {
+    /// always the same speed, in G (cells per frame)
+    Fixed(f64),
+    /// speeds up with the level (see guideline_gravity)
+    Level,
+}
+
+impl Gravity {
+    /// the gravity in G, for the given level
+    pub fn cells_per_frame(&self, level: u32) -> f64 {
+        match *self {
+            Gravity::Fixed(g) => g,
+            Gravity::Level => guideline_gravity(level),
+        }
+    }
+}
+
+/// Gravity of the Tetris Guideline: (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds per row,
+/// capped at 20G. Uses plain multiplication (no powf), so it comes out the same on every target.
+pub fn guideline_gravity(level: u32) -> f64 {
+    let level = level.max(1);
+    let base = 0.8 - (level - 1) as f64 * 0.007;
+    // from level 116 on the formula goes negative, way past 20G anyways:
+    if base <= 0.0 {
+        return INSTANT_GRAVITY;
+    }
+    let seconds_per_row = (1..level).fold(1.0, |acc, _| acc * base);
+    (1.0 / (seconds_per_row * 60.0)).min(INSTANT_GRAVITY)
+}
+
+/// Timings of the game, frame counts are in fixed frames (60 per second)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Timing {
+    /// how fast shapes fall by themself
+    pub gravity: Gravity,
+    /// while soft drop is held the gravity gets multiplied by this (at least 1 G / 3 frames)
+    pub soft_drop_factor: f64,
+    /// frames a grounded shape waits before it locks in place
+    pub lock_delay_frames: u32,
+    /// delayed auto shift: frames a left/right input is held before it starts repeating
+    pub das_frames: u32,
+    /// auto repeat rate: frames between repeated moves (0 moves all the way instantly)
+    pub arr_frames: u32,
+    /// entry delay (ARE): frames after a lock before the next shape spawns (0 spawns right away)
+    pub are_frames: u32,
+    /// frames full rows stay on the board before they collapse (0 collapses them right away)
+    pub line_clear_frames: u32,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            gravity: Gravity::Level,
+            soft_drop_factor: 20.0,
+            lock_delay_frames: 30,
+            das_frames: 10,
+            arr_frames: 2,
+            are_frames: 0,
+            line_clear_frames: 0,
+        }
+    }
+}
+
+/// What the game is doing right now. Frame counts run from 0 up to frames.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Phase {
+    /// the current shape falls and takes input
+    #[default]
+    Falling,
+    /// the full rows (top to bottom) are still on the board, they collapse once frames ran out
+    LineClear { rows: Vec<i32>, frame: u32, frames: u32 },
+    /// entry delay: the board settled, the next shape spawns once frames ran out
+    Entry { frame: u32, frames: u32 },
+}
+
+impl Phase {
+    /// how far the delay is along, from 0 to 1 (0 while falling)
+    pub fn progress(&self) -> f64 {
+        match *self {
+            Phase::Falling => 0.0,
+            Phase::LineClear { frame, frames, .. } | Phase::Entry { frame, frames } => {
+                frame as f64 / frames.max(1) as f64
+            }
+        }
+    }
+}
+
+/// state of the simulation between frames
+#[derive(Debug, Clone, Default)]
+pub(super) struct Clock {
+    /// time that was fed in but did not fill a whole frame yet
+    leftover_ms: f64,
+    /// frames simulated since the start of the game
+    frame: u64,
+    /// how far the shape fell since the last full row, in SUBCELLS
+    gravity: u32,
+    /// frames the shape has been grounded
+    lock_frames: u32,
+    /// how often the lock delay got restarted for the current shape
+    lock_resets: u32,
+    /// held left/right input and for how many frames it is held
+    shift: Option<(Direction, u32)>,
+    soft_drop: bool,
+}
+
+impl Clock {
+    // a new shape spawned, it gets a fresh lock delay
+    pub(super) fn on_spawn(&mut self) {
+        self.gravity = 0;
+        self.lock_frames = 0;
+        self.lock_resets = 0;
+    }
+
+    // the shape moved, restarts the lock delay (a limited number of times)
+    pub(super) fn on_moved(&mut self) {
+        if self.lock_frames > 0 && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_frames = 0;
+            self.lock_resets += 1;
+        }
+    }
+}
+
+impl Tetris {
+    /// Advances the game by the real time that passed since the last call.
+    /// Returns how many fixed frames got simulated.
+    pub fn update(&mut self, elapsed_ms: f64) -> u32 {
+        // time does not pass while paused:
+        if self.paused {
+            return 0;
+        }
+        self.clock.leftover_ms += elapsed_ms.clamp(0.0, MAX_CATCH_UP_MS);
+        let mut frames = 0;
+        while self.clock.leftover_ms >= FRAME_MS {
+            self.clock.leftover_ms -= FRAME_MS;
+            self.frame();
+            frames += 1;
+        }
+        frames
+    }
+
+    /// Simulates exactly one fixed frame
+    pub fn frame(&mut self) {
+        if self.game_over {
+            return;
+        }
+        self.clock.frame += 1;
+        let timing = self.settings.timing.clone();
+
+        // shapes wait while the game is in one of the delays:
+        if self.phase != Phase::Falling {
+            self.advance_delay();
+            return;
+        }
+
+        // auto shift of a held left/right input:
+        if let Some((dir, held)) = self.clock.shift {
+            let held = held + 1;
+            self.clock.shift = Some((dir, held));
+            if held >= timing.das_frames {
+                if timing.arr_frames == 0 {
+                    while self.shift_current_shape(dir) {}
+                } else if (held - timing.das_frames).is_multiple_of(timing.arr_frames) {
+                    self.shift_current_shape(dir);
+                }
+            }
+        }
+
+        // gravity:
+        let gravity = self.gravity();
+        if gravity >= INSTANT_GRAVITY {
+            self.current_shape = self.get_ghost();
+        } else {
+            // rounded up, so 1/n G falls exactly every n frames:
+            self.clock.gravity += (gravity * SUBCELLS as f64).ceil() as u32;
+            while self.clock.gravity >= SUBCELLS {
+                self.clock.gravity -= SUBCELLS;
+                if !self.move_down() {
+                    self.clock.gravity = 0;
+                    break;
+                }
+            }
+        }
+
+        // lock delay:
+        if self.is_grounded() {
+            self.clock.lock_frames += 1;
+            if self.clock.lock_frames >= timing.lock_delay_frames {
+                self.lock_current_shape();
+            }
+        } else {
+            self.clock.lock_frames = 0;
+        }
+    }
+
+    // one frame of the line clear or entry delay passed
+    fn advance_delay(&mut self) {
+        let done = match &mut self.phase {
+            Phase::Falling => false,
+            Phase::LineClear { frame, frames, .. } | Phase::Entry { frame, frames } => {
+                *frame += 1;
+                *frame >= *frames
+            }
+        };
+        if done {
+            self.end_delay();
+        }
+    }
+
+    /// the current gravity in G (cells per frame), including soft drop
+    pub fn gravity(&self) -> f64 {
+        let timing = &self.settings.timing;
+        let gravity = timing.gravity.cells_per_frame(self.get_level());
+        if self.clock.soft_drop {
+            (gravity * timing.soft_drop_factor).max(1.0 / 3.0)
+        } else {
+            gravity
+        }
+    }
+
+    /// frames simulated since the start of the game
+    pub fn frame_count(&self) -> u64 {
+        self.clock.frame
+    }
+
+    /// left/right input got pressed: moves once right away, then auto repeats after the DAS delay
+    pub fn start_shift(&mut self, dir: Direction) {
+        self.clock.shift = Some((dir, 0));
+        self.count_input();
+        self.shift_current_shape(dir);
+    }
+
+    /// left/right input got released
+    pub fn stop_shift(&mut self, dir: Direction) {
+        if matches!(self.clock.shift, Some((held, _)) if held == dir) {
+            self.clock.shift = None;
+        }
+    }
+
+    /// soft drop input pressed/released: the gravity gets multiplied by soft_drop_factor
+    pub fn set_soft_drop(&mut self, soft_drop: bool) {
+        if soft_drop && !self.clock.soft_drop {
+            self.count_input();
+        }
+        self.clock.soft_drop = soft_drop;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::{
+        shapes::{PieceKind, XY},
+        Settings,
+    };
+
+    fn with_gravity(gravity: Gravity) -> Tetris {
+        let settings = Settings {
+            timing: Timing {
+                gravity,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Tetris::with_settings(10, 20, settings)
+    }
+
+    fn lowest_y(tetris: &Tetris) -> i32 {
+        tetris.current_shape.get_pixels().map(|xy| xy.1).max().unwrap()
+    }
+
+    fn leftmost_x(tetris: &Tetris) -> i32 {
+        tetris.current_shape.get_pixels().map(|xy| xy.0).min().unwrap()
+    }
+
+    #[test]
+    fn gravity_runs_on_fixed_frames() {
+        let mut tetris = with_gravity(Gravity::Fixed(1.0 / 30.0));
+        let start = lowest_y(&tetris);
+        // 30 frames per row = 500ms, no matter how the time is split up:
+        assert_eq!(tetris.update(250.0), 15);
+        assert_eq!(lowest_y(&tetris), start);
+        for _ in 0..25 {
+            tetris.update(10.0);
+        }
+        assert_eq!(lowest_y(&tetris), start + 1);
+        assert_eq!(tetris.frame_count(), 30);
+    }
+
+    #[test]
+    fn catch_up_is_capped() {
+        let mut tetris = Tetris::new(10, 20);
+        assert_eq!(tetris.update(10_000.0), 15);
+    }
+
+    #[test]
+    fn soft_drop_speeds_up_gravity() {
+        let mut tetris = with_gravity(Gravity::Fixed(1.0 / 60.0));
+        let start = lowest_y(&tetris);
+        // 20 * 1/60 G = 1/3 G
+        tetris.set_soft_drop(true);
+        for _ in 0..9 {
+            tetris.frame();
+        }
+        assert_eq!(lowest_y(&tetris), start + 3);
+        tetris.set_soft_drop(false);
+        for _ in 0..9 {
+            tetris.frame();
+        }
+        assert_eq!(lowest_y(&tetris), start + 3);
+    }
+
+    #[test]
+    fn fractional_gravity_accumulates() {
+        let mut tetris = with_gravity(Gravity::Fixed(0.25));
+        let start = lowest_y(&tetris);
+        for _ in 0..8 {
+            tetris.frame();
+        }
+        assert_eq!(lowest_y(&tetris), start + 2);
+
+        let mut tetris = with_gravity(Gravity::Fixed(3.0));
+        let start = lowest_y(&tetris);
+        tetris.frame();
+        assert_eq!(lowest_y(&tetris), start + 3);
+    }
+
+    #[test]
+    fn instant_gravity_spawns_on_the_stack() {
+        let mut tetris = with_gravity(Gravity::Fixed(INSTANT_GRAVITY));
+        tetris.frame();
+        assert_eq!(lowest_y(&tetris), 19);
+        for _ in 0..tetris.settings.timing.lock_delay_frames {
+            tetris.frame();
+        }
+        // the next shape is already sitting on the stack, without a frame passing:
+        assert_eq!(tetris.sticky_bottom_shapes.len(), 1);
+        assert!(tetris.is_grounded());
+    }
+
+    #[test]
+    fn guideline_gravity_speeds_up() {
+        assert!((guideline_gravity(1) - 1.0 / 60.0).abs() < 1e-12);
+        assert!(guideline_gravity(5) > guideline_gravity(4));
+        assert_eq!(guideline_gravity(20), INSTANT_GRAVITY);
+        assert_eq!(guideline_gravity(0), guideline_gravity(1));
+        for level in 115..=200 {
+            assert_eq!(guideline_gravity(level), INSTANT_GRAVITY, "level {}", level);
+        }
+        assert_eq!(guideline_gravity(u32::MAX), INSTANT_GRAVITY);
+
+        let mut tetris = with_gravity(Gravity::Level);
+        assert_eq!(tetris.gravity(), guideline_gravity(1));
+        tetris.lines = 25;
+        assert_eq!(tetris.get_level(), 3);
+        assert_eq!(tetris.gravity(), guideline_gravity(3));
+    }
+
+    #[test]
+    fn grounded_shape_locks_after_delay() {
+        let mut tetris = Tetris::new(10, 20);
+        let ghost = tetris.get_ghost();
+        tetris.current_shape = ghost;
+        for _ in 0..29 {
+            tetris.frame();
+        }
+        assert!(tetris.sticky_bottom_shapes.is_empty());
+        tetris.frame();
+        assert_eq!(tetris.sticky_bottom_shapes.len(), 1);
+    }
+
+    #[test]
+    fn moving_restarts_lock_delay() {
+        let mut tetris = Tetris::new(10, 20);
+        tetris.current_shape = tetris.get_ghost();
+        for _ in 0..20 {
+            tetris.frame();
+        }
+        tetris.move_current_shape(Direction::Left);
+        for _ in 0..20 {
+            tetris.frame();
+        }
+        assert!(tetris.sticky_bottom_shapes.is_empty());
+        for _ in 0..10 {
+            tetris.frame();
+        }
+        assert_eq!(tetris.sticky_bottom_shapes.len(), 1);
+    }
+
+    #[test]
+    fn das_repeats_after_delay() {
+        let mut tetris = Tetris::new(10, 20);
+        let start = leftmost_x(&tetris);
+        tetris.start_shift(Direction::Left);
+        assert_eq!(leftmost_x(&tetris), start - 1);
+        for _ in 0..9 {
+            tetris.frame();
+        }
+        assert_eq!(leftmost_x(&tetris), start - 1);
+        tetris.frame();
+        assert_eq!(leftmost_x(&tetris), start - 2);
+        tetris.frame();
+        tetris.frame();
+        assert_eq!(leftmost_x(&tetris), start - 3);
+        tetris.stop_shift(Direction::Left);
+        for _ in 0..4 {
+            tetris.frame();
+        }
+        assert_eq!(leftmost_x(&tetris), start - 3);
+    }
+
+    #[test]
+    fn delays_hold_back_the_next_shape() {
+        let settings = Settings {
+            timing: Timing {
+                are_frames: 6,
+                line_clear_frames: 20,
+                ..Default::default()
+            },
+            piece_set: crate::tetris_game::piece_set::PieceSet::from_json(
+                r#"{ "name": "bar", "pieces": [{ "name": "I", "kind": "I", "cells": [[-2, 0], [-1, 0], [0, 0], [1, 0]], "center": [0, 0] }] }"#,
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+        let mut tetris = Tetris::with_settings(4, 4, settings);
+        tetris.hard_drop();
+        // the full row stays on the board (and gets scored) until the line clear delay ran out:
+        assert_eq!(tetris.clearing_rows(), &[3]);
+        assert_eq!(tetris.lines, 1);
+        assert_eq!(tetris.get_kind(XY(0, 3)), Some(PieceKind::I));
+        assert!(!tetris.move_down());
+        for _ in 0..19 {
+            tetris.frame();
+        }
+        assert_eq!(tetris.clearing_rows(), &[3]);
+        tetris.frame();
+        assert!(tetris.clearing_rows().is_empty());
+        assert_eq!(tetris.phase(), &Phase::Entry { frame: 0, frames: 6 });
+        assert_eq!(tetris.get_kind(XY(0, 3)), None);
+
+        // then the entry delay:
+        for _ in 0..5 {
+            tetris.frame();
+        }
+        assert!(!tetris.hold());
+        tetris.frame();
+        assert_eq!(tetris.phase(), &Phase::Falling);
+        assert!(tetris.move_down());
+    }
+
+    #[test]
+    fn no_delays_spawn_right_away() {
+        let mut tetris = Tetris::new(10, 20);
+        tetris.hard_drop();
+        assert_eq!(tetris.phase(), &Phase::Falling);
+        assert_eq!(tetris.sticky_bottom_shapes.len(), 1);
+    }
+
+    #[test]
+    fn releasing_the_other_direction_keeps_shifting() {
+        let mut tetris = Tetris::new(10, 20);
+        tetris.start_shift(Direction::Left);
+        tetris.stop_shift(Direction::Right);
+        for _ in 0..30 {
+            tetris.frame();
+        }
+        assert_eq!(leftmost_x(&tetris), 0);
+        assert!(tetris.current_shape.get_pixels().all(|XY(x, _)| x >= 0));
+    }
+}
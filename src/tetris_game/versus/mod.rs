@@ -0,0 +1,286 @@
+use super::event::Event;
+use super::garbage::{AttackTable, Attacker, Board, HolePlacement, Incoming};
+use super::rng::{self, Rng, SeededRng};
+use super::{Settings, Tetris};
+
+/*
+    Versus: two boards side by side, clearing lines on one sends garbage to the other.
+        - update() runs both games, then exchange() looks at every shape that locked since:
+            - a clear attacks (see garbage::AttackTable), the attack cancels the own incoming garbage first
+              and whatever is left goes to the incoming meter of the opponent
+            - a lock that clears nothing lets the own incoming garbage rise (holes by HolePlacement)
+        - inputs go straight to the boards (board_mut()), toggle_pause() pauses both
+        - the first board to top out loses
+    Nothing in here needs a browser: with_seed() plays the same match every time (both boards get the same pieces).
+*/
+
+/// The rules of a match
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersusSettings {
+    pub attack: AttackTable,
+    pub holes: HolePlacement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// the player (0 or 1) that is still standing
+    Win(usize),
+    /// both topped out at the same time
+    Draw,
+}
+
+#[derive(Debug)]
+pub struct Versus {
+    players: [Board; 2],
+    settings: VersusSettings,
+    /// picks the holes of the garbage
+    rng: Box<dyn Rng>,
+    outcome: Option<Outcome>,
+}
+
+impl Versus {
+    /// a match between two new games of the same size and settings
+    pub fn new(width: u32, height: u32, settings: Settings, versus: VersusSettings) -> Self {
+        let board = || Tetris::with_settings(width, height, settings.clone());
+        Self::with_boards([board(), board()], versus, rng::default_rng())
+    }
+
+    /// like new(), but the same seed plays out the same every time (and both boards get the same pieces)
+    pub fn with_seed(
+        width: u32,
+        height: u32,
+        settings: Settings,
+        versus: VersusSettings,
+        seed: u64,
+    ) -> Self {
+        let board = || {
+            Tetris::with_rng(
+                width,
+                height,
+                settings.clone(),
+                Box::new(SeededRng::new(seed)),
+            )
+        };
+        let holes = Box::new(SeededRng::new(seed.wrapping_add(1)));
+        Self::with_boards([board(), board()], versus, holes)
+    }
+
+    /// a match between two games that are already set up (custom positions etc.), holes come from the rng
+    pub fn with_boards(boards: [Tetris; 2], versus: VersusSettings, rng: Box<dyn Rng>) -> Self {
+        Self {
+            players: boards.map(Board::new),
+            settings: versus,
+            rng,
+            outcome: None,
+        }
+    }
+
+    pub fn settings(&self) -> &VersusSettings {
+        &self.settings
+    }
+
+    /// the board of player 0 or 1
+    pub fn board(&self, player: usize) -> &Tetris {
+        &self.players[player].tetris
+    }
+
+    /// for the inputs of the player
+    pub fn board_mut(&mut self, player: usize) -> &mut Tetris {
+        &mut self.players[player].tetris
+    }
+
+    /// the garbage waiting to rise on the board of the player
+    pub fn incoming(&self, player: usize) -> &Incoming {
+        &self.players[player].incoming
+    }
+
+    /// the combo and back to back of the player
+    pub fn attacker(&self, player: usize) -> &Attacker {
+        &self.players[player].attacker
+    }
+
+    /// lines the player sent so far (what got cancelled does not count)
+    pub fn sent(&self, player: usize) -> u32 {
+        self.players[player].sent
+    }
+
+    /// who won, None while both are still playing
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.players.iter().any(|player| player.tetris.is_paused())
+    }
+
+    /// pauses (or resumes) both boards
+    pub fn toggle_pause(&mut self) {
+        let pause = !self.is_paused();
+        for player in self.players.iter_mut() {
+            if player.tetris.is_paused() != pause {
+                player.tetris.toggle_pause();
+            }
+        }
+    }
+
+    /// Advances both games by the real time that passed, then exchanges the garbage
+    pub fn update(&mut self, elapsed_ms: f64) {
+        if self.outcome.is_none() {
+            for player in self.players.iter_mut() {
+                player.tetris.update(elapsed_ms);
+            }
+        }
+        self.exchange();
+    }
+
+    /// Sends the attacks of everything that locked since the last call, and lets garbage rise.
+    /// update() does this on its own, call it directly when driving the boards without time (bots etc.)
+    pub fn exchange(&mut self) {
+        if self.outcome.is_some() {
+            return;
+        }
+        for idx in 0..self.players.len() {
+            let settings = &self.settings;
+            let attacks = self.players[idx].exchange(
+                &settings.attack,
+                settings.holes,
+                self.rng.as_mut(),
+                |attack| attack,
+            );
+            for attack in attacks {
+                self.players[1 - idx].incoming.push(attack);
+            }
+        }
+        self.outcome = match self
+            .players
+            .each_ref()
+            .map(|player| player.tetris.is_game_over())
+        {
+            [false, false] => None,
+            [false, true] => Some(Outcome::Win(0)),
+            [true, false] => Some(Outcome::Win(1)),
+            [true, true] => Some(Outcome::Draw),
+        };
+    }
+
+    /// everything that happened on the board of the player since the last call, oldest first
+    pub fn take_events(&mut self, player: usize) -> Vec<Event> {
+        self.players[player].take_events()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_game::bot::Bot;
+    use crate::tetris_game::position::Position;
+    use crate::tetris_game::shapes::{PieceKind, XY};
+    use crate::tetris_game::Direction;
+
+    // a board with garbage in the rows, except for the columns of the holes
+    fn board(
+        rows: std::ops::Range<i32>,
+        holes: std::ops::Range<i32>,
+        pieces: &[PieceKind],
+    ) -> Tetris {
+        let mut position = Position::empty(10, 20);
+        for y in rows {
+            for x in (0..10).filter(|x| !holes.contains(x)) {
+                position.set(XY(x, y), Some(PieceKind::Garbage));
+            }
+        }
+        position.current = pieces.first().copied();
+        position.queue = pieces[1..].to_vec();
+        position.start_with_rng(Settings::default(), Box::new(SeededRng::new(1)))
+    }
+
+    fn to_the_left(tetris: &mut Tetris) {
+        while tetris.move_current_shape(Direction::Left) {}
+    }
+
+    #[test]
+    fn clears_send_cancel_and_rise() {
+        let settings = VersusSettings {
+            attack: AttackTable {
+                lines: [1, 2, 3, 4],
+                combo: vec![0],
+                back_to_back: 0,
+                perfect_clear: 0,
+                ..Default::default()
+            },
+            holes: HolePlacement::Column(2),
+        };
+        let boards = [
+            board(16..20, 0..1, &[PieceKind::I, PieceKind::I]),
+            board(19..20, 0..4, &[PieceKind::I, PieceKind::O]),
+        ];
+        let mut versus = Versus::with_boards(boards, settings, Box::new(SeededRng::new(1)));
+
+        // a tetris on the left sends 4 lines:
+        let left = versus.board_mut(0);
+        left.move_down();
+        left.move_rotate();
+        to_the_left(left);
+        left.hard_drop();
+        versus.exchange();
+        assert_eq!(versus.board(0).get_lines(), 4);
+        assert_eq!(versus.incoming(1).total(), 4);
+        assert_eq!(versus.sent(0), 4);
+
+        // a single on the right cancels one of them:
+        let right = versus.board_mut(1);
+        to_the_left(right);
+        right.hard_drop();
+        versus.exchange();
+        assert_eq!(versus.incoming(1).total(), 3);
+        assert_eq!(versus.sent(1), 0);
+        assert_eq!(versus.incoming(0).total(), 0);
+
+        // then a lock without lines lets the rest rise:
+        versus.board_mut(1).hard_drop();
+        versus.exchange();
+        assert_eq!(versus.incoming(1).total(), 0);
+        let right = versus.board(1);
+        for y in 17..20 {
+            assert_eq!(right.get_kind(XY(2, y)), None);
+            assert_eq!(right.get_kind(XY(3, y)), Some(PieceKind::Garbage));
+        }
+        assert!(versus.take_events(1).contains(&Event::Garbage { lines: 3 }));
+        assert_eq!(versus.outcome(), None);
+    }
+
+    #[test]
+    fn topping_out_loses() {
+        let mut versus =
+            Versus::with_seed(10, 20, Settings::default(), VersusSettings::default(), 7);
+        versus.toggle_pause();
+        assert!(versus.board(0).is_paused() && versus.board(1).is_paused());
+        versus.toggle_pause();
+        versus.board_mut(1).add_garbage(&[0; 20]);
+        versus.update(100.0);
+        assert_eq!(versus.outcome(), Some(Outcome::Win(0)));
+        // nothing moves anymore:
+        let frames = versus.board(0).frame_count();
+        versus.update(100.0);
+        assert_eq!(versus.board(0).frame_count(), frames);
+    }
+
+    // two bots play the same match twice
+    fn bot_match(seed: u64) -> (Option<Outcome>, [u32; 2]) {
+        let mut versus =
+            Versus::with_seed(10, 20, Settings::default(), VersusSettings::default(), seed);
+        let bot = Bot::default();
+        for _ in 0..12 {
+            for player in 0..2 {
+                bot.play(versus.board_mut(player));
+                versus.exchange();
+            }
+        }
+        (versus.outcome(), [versus.sent(0), versus.sent(1)])
+    }
+
+    #[test]
+    fn matches_are_deterministic() {
+        assert_eq!(bot_match(3), bot_match(3));
+    }
+}